@@ -0,0 +1,73 @@
+-- The tables `pdb-addr2line --export-sqlite` writes, version 2 (the database's user_version).
+--
+-- Addresses are relative to the image base (RVAs) and sizes are in bytes. Every address range
+-- is half-open, [address, address + size). Names are as the PDB stores them; compiler-generated
+-- names of funclets and coroutine parts are already replaced with readable ones.
+
+-- Source files, referenced by line records. The repository columns locate the file in its
+-- source repository as the PDB's SourceLink or srcsrv stream maps it, or are NULL if it does not.
+CREATE TABLE files (
+    id INTEGER PRIMARY KEY,
+    path TEXT NOT NULL,
+    repo_url TEXT,
+    revision TEXT,
+    repo_path TEXT
+);
+
+-- Functions with a procedure record. `language` is the source language of the compiland, like
+-- c, c++, masm or rust, or NULL if it records none. `debug_start` is the offset from `address`
+-- where the prologue has set up the frame, and `system` is 1 for functions of a runtime library
+-- compiland and 0 for others.
+CREATE TABLE functions (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    address INTEGER NOT NULL,
+    size INTEGER NOT NULL,
+    language TEXT,
+    debug_start INTEGER NOT NULL,
+    system INTEGER NOT NULL
+);
+
+-- Line records of the code of each function, with the start column if the PDB records one. For
+-- code inlined into the function, the record gives the line of the outermost call.
+CREATE TABLE lines (
+    function_id INTEGER NOT NULL REFERENCES functions (id),
+    address INTEGER NOT NULL,
+    size INTEGER NOT NULL,
+    file_id INTEGER NOT NULL REFERENCES files (id),
+    line INTEGER NOT NULL,
+    column INTEGER
+);
+
+-- Line records of inlined functions: `name` was inlined into `function_id` at nesting `depth`,
+-- 1 for a call made by the function itself, and the record gives the line within `name`.
+CREATE TABLE inlinees (
+    function_id INTEGER NOT NULL REFERENCES functions (id),
+    depth INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    address INTEGER NOT NULL,
+    size INTEGER NOT NULL,
+    file_id INTEGER NOT NULL REFERENCES files (id),
+    line INTEGER NOT NULL,
+    column INTEGER
+);
+
+-- Public symbols of code, with their decorated names.
+CREATE TABLE publics (
+    address INTEGER NOT NULL,
+    name TEXT NOT NULL
+);
+
+-- The `__annotation` calls of the code, with their strings separated by line feeds.
+CREATE TABLE annotations (
+    address INTEGER NOT NULL,
+    strings TEXT NOT NULL
+);
+
+-- The thunks incremental and `/ORDER` links place between callers and functions, each jumping to
+-- the function at `target`.
+CREATE TABLE trampolines (
+    address INTEGER NOT NULL,
+    size INTEGER NOT NULL,
+    target INTEGER NOT NULL
+);
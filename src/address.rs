@@ -0,0 +1,185 @@
+//! Parsing of queried addresses and their translation to RVAs.
+
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// The target architectures understood by `--arch`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arch {
+    X86,
+    X64,
+    Arm64,
+}
+
+impl FromStr for Arch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "x86" => Ok(Arch::X86),
+            "x64" => Ok(Arch::X64),
+            "arm64" => Ok(Arch::Arm64),
+            _ => Err(format!("unknown architecture '{}' (expected x86, x64 or arm64)", s)),
+        }
+    }
+}
+
+impl Arch {
+    /// The preferred image base the linker uses for executables of this architecture.
+    pub fn default_base(self) -> u64 {
+        match self {
+            Arch::X86 => 0x40_0000,
+            Arch::X64 | Arch::Arm64 => 0x1_4000_0000,
+        }
+    }
+
+    /// Mask for the address width: virtual address arithmetic wraps around at this width.
+    fn mask(self) -> u64 {
+        match self {
+            Arch::X86 => 0xffff_ffff,
+            Arch::X64 | Arch::Arm64 => u64::MAX,
+        }
+    }
+}
+
+/// A range of RVAs given as `START-END` in hexadecimal, the end excluded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RvaRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl RvaRange {
+    /// Whether the `size` bytes at `start` overlap the range.
+    pub fn overlaps(self, start: u32, size: u32) -> bool {
+        start < self.end && u64::from(start) + u64::from(size) > u64::from(self.start)
+    }
+}
+
+impl FromStr for RvaRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid range '{}' (expected START-END, like 0x100000-0x200000)", s);
+        let (start, end) = s.split_once('-').ok_or_else(invalid)?;
+        let rva = |s: &str| u32::from_str_radix(s.trim().trim_start_matches("0x").trim_start_matches("0X"), 16).map_err(|_| invalid());
+        let (start, end) = (rva(start)?, rva(end)?);
+        if start >= end {
+            return Err(invalid());
+        }
+        Ok(RvaRange { start, end })
+    }
+}
+
+/// Parses a hexadecimal address with optional `0x` prefix.
+///
+/// With `Arch::X86`, addresses above 4 GiB are rejected, except for sign-extended 32-bit
+/// addresses (`0xffffffff8xxxxxxx`) as some 64-bit tools print them for /LARGEADDRESSAWARE
+/// processes; those are truncated to 32 bits.
+pub fn parse_address(s: &str, arch: Option<Arch>) -> Result<u64, String> {
+    let digits = s.trim_start_matches("0x").trim_start_matches("0X");
+    let address = u64::from_str_radix(digits, 16).map_err(|_| format!("invalid address '{}'", s))?;
+    if arch == Some(Arch::X86) && address > 0xffff_ffff {
+        if address >> 31 == 0x1_ffff_ffff {
+            return Ok(address & 0xffff_ffff);
+        }
+        return Err(format!("address '{}' does not fit in a 32-bit address space", s));
+    }
+    Ok(address)
+}
+
+/// Translates a virtual address loaded at `base` to an RVA.
+///
+/// The subtraction wraps around at the address width of `arch`, so modules relocated near the
+/// top of a 32-bit address space work too. Fails if the result is not a 32-bit RVA.
+pub fn to_rva(address: u64, base: Option<u64>, arch: Option<Arch>) -> Result<u32, String> {
+    let mask = arch.map_or(u64::MAX, Arch::mask);
+    let rva = match base {
+        Some(base) => address.wrapping_sub(base) & mask,
+        None => address,
+    };
+    u32::try_from(rva).map_err(|_| match base {
+        Some(base) => format!("address {:#x} is not within 4 GiB of base {:#x}", address, base),
+        None => format!("address {:#x} is too large for an RVA (pass --base for virtual addresses)", address),
+    })
+}
+
+/// Image bases the MSVC linker picks by default, tried when guessing whether virtual addresses
+/// were passed instead of RVAs.
+pub const COMMON_BASES: [u64; 4] = [0x40_0000, 0x1000_0000, 0x1_4000_0000, 0x1_8000_0000];
+
+/// Guesses the image base if `addresses` look like virtual addresses rather than RVAs.
+///
+/// That is the case if most addresses lie outside an image of `image_size` bytes but inside it
+/// once one of the `COMMON_BASES` is subtracted.
+pub fn guess_base(addresses: impl IntoIterator<Item = u64>, image_size: u64) -> Option<u64> {
+    let inside = |address: u64, base: u64| address >= base && address - base < image_size;
+    let mut total = 0;
+    let mut as_rvas = 0;
+    let mut counts = [0usize; COMMON_BASES.len()];
+    for address in addresses {
+        total += 1;
+        as_rvas += usize::from(inside(address, 0));
+        for (count, &base) in counts.iter_mut().zip(&COMMON_BASES) {
+            *count += usize::from(inside(address, base));
+        }
+    }
+
+    let majority = total / 2 + 1;
+    if as_rvas >= majority {
+        return None;
+    }
+    COMMON_BASES.iter().zip(&counts).find(|&(_, &count)| count >= majority).map(|(&base, _)| base)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_address, to_rva, Arch, RvaRange};
+
+    #[test]
+    fn addresses() {
+        assert_eq!(parse_address("0x1000", None), Ok(0x1000));
+        assert_eq!(parse_address("1000", None), Ok(0x1000));
+        assert_eq!(parse_address("0X1000", Some(Arch::X64)), Ok(0x1000));
+        assert!(parse_address("main", None).is_err());
+
+        // Sign-extended 32-bit addresses are truncated for x86, other wide ones rejected.
+        assert_eq!(parse_address("0xffffffff80001000", Some(Arch::X86)), Ok(0x8000_1000));
+        assert!(parse_address("0xffffffff00001000", Some(Arch::X86)).is_err());
+        assert!(parse_address("0x100000000", Some(Arch::X86)).is_err());
+        assert_eq!(parse_address("0xffffffff80001000", Some(Arch::X64)), Ok(0xffff_ffff_8000_1000));
+    }
+
+    #[test]
+    fn rvas() {
+        assert_eq!(to_rva(0x40_1000, Some(0x40_0000), Some(Arch::X86)), Ok(0x1000));
+        assert_eq!(to_rva(0x1000, None, None), Ok(0x1000));
+        assert!(to_rva(0x1_0000_0000, None, None).is_err());
+
+        // Below the base the subtraction wraps around at 32 bits for x86, which keeps modules
+        // relocated across the top of the address space working, and fails for 64-bit images.
+        assert_eq!(to_rva(0x1000, Some(0xffff_0000), Some(Arch::X86)), Ok(0x11000));
+        assert_eq!(to_rva(0x3f_f000, Some(0x40_0000), Some(Arch::X86)), Ok(0xffff_f000));
+        assert!(to_rva(0x1_3fff_f000, Some(0x1_4000_0000), Some(Arch::X64)).is_err());
+        assert!(to_rva(0x3f_f000, Some(0x40_0000), None).is_err());
+        assert!(to_rva(0x2_4000_0000, Some(0x1_4000_0000), Some(Arch::Arm64)).is_err());
+    }
+
+    #[test]
+    fn ranges() {
+        let range: RvaRange = "0x100-0x200".parse().unwrap();
+        assert_eq!(range, RvaRange { start: 0x100, end: 0x200 });
+        assert_eq!("100 - 200".parse(), Ok(range));
+        for invalid in ["0x200-0x100", "0x100-0x100", "0x100", "0x100-", "0x100-0x1_0000_0000"] {
+            assert!(invalid.parse::<RvaRange>().is_err(), "{}", invalid);
+        }
+
+        // The end is excluded.
+        assert!(!range.overlaps(0xff, 1));
+        assert!(range.overlaps(0xff, 2));
+        assert!(range.overlaps(0x1ff, 1));
+        assert!(!range.overlaps(0x200, 1));
+        assert!(!range.overlaps(0x100, 0));
+        assert!(range.overlaps(0, u32::MAX));
+    }
+}
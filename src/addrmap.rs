@@ -0,0 +1,185 @@
+//! `--dump-address-map`: the section headers and OMAP tables the resolver translates addresses
+//! with.
+//!
+//! pdb keeps these tables to itself, so they are read from the raw streams named by the DBI
+//! stream's optional debug header.
+
+use std::convert::{TryFrom, TryInto};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use pdb::{StreamIndex, PDB};
+
+use crate::output::{json_string, Format};
+use crate::paths;
+use crate::protobuf;
+
+/// A section of the image as the PDB records it.
+pub struct Section {
+    pub name: String,
+    pub rva: u32,
+    pub size: u32,
+}
+
+/// The address translation tables of a PDB.
+pub struct AddressTables {
+    /// The sections of the final image.
+    pub sections: Vec<Section>,
+    /// The sections before the image was rewritten, present with OMAP.
+    pub original_sections: Option<Vec<Section>>,
+    /// OMAP records translating rewritten RVAs to original ones, as (from, to).
+    pub omap_to_source: Vec<(u32, u32)>,
+    /// OMAP records translating original RVAs to rewritten ones, as (from, to).
+    pub omap_from_source: Vec<(u32, u32)>,
+}
+
+const DBI_STREAM: u16 = 3;
+const OMAP_TO_SOURCE: usize = 3;
+const OMAP_FROM_SOURCE: usize = 4;
+const SECTION_HEADERS: usize = 5;
+const ORIGINAL_SECTION_HEADERS: usize = 10;
+
+/// Reads the address translation tables of the PDB at `path`.
+pub fn read(path: &Path) -> pdb::Result<AddressTables> {
+    let mut pdb = PDB::open(fs::File::open(paths::long_path(path))?)?;
+    let dbi = match pdb.raw_stream(StreamIndex(DBI_STREAM))? {
+        Some(stream) => stream,
+        None => return Err(pdb::Error::StreamNotFound(u32::from(DBI_STREAM))),
+    };
+    let extra = extra_streams(dbi.as_slice()).unwrap_or_default();
+    let mut read_stream = |slot: usize| -> pdb::Result<Option<Vec<u8>>> {
+        match extra.get(slot) {
+            Some(&index) if index != 0xffff => Ok(pdb.raw_stream(StreamIndex(index))?.map(|s| s.as_slice().to_vec())),
+            _ => Ok(None),
+        }
+    };
+    let sections = read_stream(SECTION_HEADERS)?.map(|data| parse_sections(&data)).unwrap_or_default();
+    let original_sections = read_stream(ORIGINAL_SECTION_HEADERS)?.map(|data| parse_sections(&data));
+    let omap_to_source = read_stream(OMAP_TO_SOURCE)?.map(|data| parse_omap(&data)).unwrap_or_default();
+    let omap_from_source = read_stream(OMAP_FROM_SOURCE)?.map(|data| parse_omap(&data)).unwrap_or_default();
+    Ok(AddressTables { sections, original_sections, omap_to_source, omap_from_source })
+}
+
+/// Returns the stream indices of the DBI stream's optional debug header.
+fn extra_streams(dbi: &[u8]) -> Option<Vec<u16>> {
+    let i32_at = |offset: usize| Some(i32::from_le_bytes(dbi.get(offset..offset + 4)?.try_into().ok()?));
+    // The substreams before the debug header: modules, section contributions, section map,
+    // source files, type server map, and at the end of the header the EC substream.
+    let mut offset = 64usize;
+    for field in &[24, 28, 32, 36, 40, 52] {
+        offset = offset.checked_add(usize::try_from(i32_at(*field)?).ok()?)?;
+    }
+    let size = usize::try_from(i32_at(48)?).ok()?;
+    let header = dbi.get(offset..offset.checked_add(size)?)?;
+    Some(header.chunks_exact(2).map(|index| u16::from_le_bytes([index[0], index[1]])).collect())
+}
+
+fn parse_sections(data: &[u8]) -> Vec<Section> {
+    data.chunks_exact(40)
+        .map(|header| {
+            let name = &header[..8];
+            let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(8)];
+            Section {
+                name: String::from_utf8_lossy(name).into_owned(),
+                size: u32::from_le_bytes([header[8], header[9], header[10], header[11]]),
+                rva: u32::from_le_bytes([header[12], header[13], header[14], header[15]]),
+            }
+        })
+        .collect()
+}
+
+fn parse_omap(data: &[u8]) -> Vec<(u32, u32)> {
+    data.chunks_exact(8)
+        .map(|record| {
+            (
+                u32::from_le_bytes([record[0], record[1], record[2], record[3]]),
+                u32::from_le_bytes([record[4], record[5], record[6], record[7]]),
+            )
+        })
+        .collect()
+}
+
+/// Writes the tables as text, JSON or protobuf.
+pub fn write(out: &mut dyn Write, format: Format, tables: &AddressTables) -> io::Result<()> {
+    match format {
+        Format::Text => write_text(out, tables),
+        Format::Json => write_json(out, tables),
+        Format::Protobuf => protobuf::write_address_map(out, tables),
+    }
+}
+
+fn write_text(out: &mut dyn Write, tables: &AddressTables) -> io::Result<()> {
+    let sections = |out: &mut dyn Write, title: &str, sections: &[Section]| -> io::Result<()> {
+        writeln!(out, "{}:", title)?;
+        for (number, section) in (1..).zip(sections) {
+            writeln!(
+                out,
+                "  {:>3} {:8} {:#010x}-{:#010x}",
+                number,
+                section.name,
+                section.rva,
+                u64::from(section.rva) + u64::from(section.size)
+            )?;
+        }
+        Ok(())
+    };
+    sections(out, "sections", &tables.sections)?;
+    if let Some(ref original) = tables.original_sections {
+        sections(out, "original sections", original)?;
+    }
+    for (title, records) in &[("omap to source", &tables.omap_to_source), ("omap from source", &tables.omap_from_source)] {
+        writeln!(out, "{}: {} records", title, records.len())?;
+        for (from, to) in records.iter() {
+            writeln!(out, "  {:#010x} -> {:#010x}", from, to)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_json(out: &mut dyn Write, tables: &AddressTables) -> io::Result<()> {
+    let sections = |sections: &[Section]| -> String {
+        let items: Vec<String> = sections
+            .iter()
+            .map(|s| format!("{{\"name\":{},\"rva\":\"{:#x}\",\"size\":\"{:#x}\"}}", json_string(&s.name), s.rva, s.size))
+            .collect();
+        format!("[{}]", items.join(","))
+    };
+    let omap = |records: &[(u32, u32)]| -> String {
+        let items: Vec<String> =
+            records.iter().map(|(from, to)| format!("{{\"from\":\"{:#x}\",\"to\":\"{:#x}\"}}", from, to)).collect();
+        format!("[{}]", items.join(","))
+    };
+    write!(out, "{{\"sections\":{}", sections(&tables.sections))?;
+    if let Some(ref original) = tables.original_sections {
+        write!(out, ",\"original_sections\":{}", sections(original))?;
+    }
+    writeln!(
+        out,
+        ",\"omap_to_source\":{},\"omap_from_source\":{}}}",
+        omap(&tables.omap_to_source),
+        omap(&tables.omap_from_source)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extra_streams, parse_omap};
+
+    #[test]
+    fn debug_header_follows_the_substreams() {
+        let mut dbi = vec![0u8; 64];
+        dbi[24..28].copy_from_slice(&8i32.to_le_bytes());
+        dbi[48..52].copy_from_slice(&6i32.to_le_bytes());
+        dbi[52..56].copy_from_slice(&4i32.to_le_bytes());
+        dbi.extend_from_slice(&[0xaa; 12]);
+        dbi.extend_from_slice(&[0xff, 0xff, 7, 0, 9, 0]);
+        assert_eq!(extra_streams(&dbi), Some(vec![0xffff, 7, 9]));
+        assert_eq!(extra_streams(&dbi[..70]), None);
+    }
+
+    #[test]
+    fn omap_records() {
+        assert_eq!(parse_omap(&[0, 0x10, 0, 0, 0, 0x20, 0, 0, 1]), vec![(0x1000, 0x2000)]);
+    }
+}
@@ -0,0 +1,123 @@
+//! `--audit-log`: a record of every query answered, with the symbol file and its debug id, for
+//! teams that must be able to show what was symbolized with which artifacts.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::output::{json_string, Resolution};
+use crate::paths;
+use crate::provider::{self, Limits};
+use crate::sqlite;
+use crate::store;
+
+/// Appends one JSON line per result to the log at `log`: the time, the address and its module,
+/// the symbol file with its debug id, and the frames.
+///
+/// `symbol_file` is `None` when the addresses were resolved against several modules; each line
+/// then names its module and carries no symbol file. A PDB's debug id is read within `limits`.
+pub fn append(
+    log: &Path,
+    symbol_file: Option<&Path>,
+    results: &[Resolution],
+    limits: Option<Limits>,
+) -> Result<(), String> {
+    let timestamp = json_string(&utc_timestamp(SystemTime::now()));
+    let artifact = match symbol_file {
+        Some(path) => format!(
+            "\"symbol_file\":{},\"debug_id\":{}",
+            json_string(&path.display().to_string()),
+            debug_id(path, limits).map_or_else(|| "null".to_string(), |id| json_string(&id))
+        ),
+        None => "\"symbol_file\":null,\"debug_id\":null".to_string(),
+    };
+
+    let mut lines = String::new();
+    for result in results {
+        lines.push_str(&format!("{{\"timestamp\":{},\"address\":\"{:#x}\",", timestamp, result.address));
+        if let Some(ref module) = result.module {
+            lines.push_str(&format!("\"module\":{},", json_string(module)));
+        }
+        lines.push_str(&artifact);
+        let frames: Vec<String> = result
+            .frames
+            .iter()
+            .map(|frame| {
+                format!(
+                    "{{\"function\":{},\"file\":{},\"line\":{},\"inline\":{},\"source\":{}}}",
+                    json_string(&frame.function),
+                    json_string(&frame.file),
+                    frame.line,
+                    frame.inline,
+                    json_string(frame.source.as_str())
+                )
+            })
+            .collect();
+        lines.push_str(&format!(",\"frames\":[{}]}}\n", frames.join(",")));
+    }
+
+    // One write per run keeps the lines of concurrent runs from interleaving.
+    let error = |e: std::io::Error| format!("cannot write audit log '{}': {}", log.display(), e);
+    let mut file = OpenOptions::new().create(true).append(true).open(paths::long_path(log)).map_err(error)?;
+    file.write_all(lines.as_bytes()).map_err(error)
+}
+
+/// The debug id of a PDB or Breakpad symbol file; databases carry none.
+fn debug_id(path: &Path, limits: Option<Limits>) -> Option<String> {
+    if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("sym")) {
+        // `MODULE windows x86_64 ID NAME`
+        let text = fs::read_to_string(paths::long_path(path)).ok()?;
+        let first = text.lines().next()?;
+        let mut fields = first.split(' ');
+        return match fields.next() {
+            Some("MODULE") => fields.nth(2).map(str::to_string),
+            _ => None,
+        };
+    }
+    if sqlite::is_database(path) {
+        return None;
+    }
+    provider::guard(path, limits, || store::debug_id(path).map_err(|e| e.to_string())).ok()
+}
+
+/// Formats `time` as an RFC 3339 UTC timestamp with second precision.
+fn utc_timestamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs());
+    let (days, rest) = (seconds / 86400, seconds % 86400);
+
+    // The civil date of a day count since 1970-01-01, after Howard Hinnant's `civil_from_days`.
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::utc_timestamp;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(utc_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(utc_timestamp(UNIX_EPOCH + Duration::from_secs(951_827_696)), "2000-02-29T12:34:56Z");
+        assert_eq!(utc_timestamp(UNIX_EPOCH + Duration::from_secs(1_791_072_000)), "2026-10-04T00:00:00Z");
+    }
+}
@@ -0,0 +1,149 @@
+//! `--panic-backtrace`: filling in the function names and locations of a Rust panic backtrace
+//! printed without symbols, as `RUST_BACKTRACE=full` shows them when the PDB was not found.
+
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+use crate::output::{Resolution, Source};
+use crate::provider::SymbolProvider;
+
+/// A frame line of a backtrace, `  3:     0x7ff6a1b2c3d4 - <unknown>`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub index: usize,
+    pub address: u64,
+    /// The function name printed for the frame, or `None` for `<unknown>` or no name.
+    pub name: Option<String>,
+}
+
+impl Frame {
+    /// The address to look up: the frame's own for the first frame, and one byte before the
+    /// return address for its callers, which lies in the call instruction.
+    pub fn lookup_address(&self) -> u64 {
+        if self.index == 0 {
+            self.address
+        } else {
+            self.address.saturating_sub(1)
+        }
+    }
+}
+
+/// Parses a frame line, or returns `None` for any other line of the backtrace.
+pub fn parse_frame(line: &str) -> Option<Frame> {
+    let (index, rest) = line.trim_start().split_once(':')?;
+    let index = index.parse().ok()?;
+    let rest = rest.trim_start();
+    let (address, name) = match rest.split_once(" - ") {
+        Some((address, name)) => (address.trim(), name.trim()),
+        None => (rest.trim(), ""),
+    };
+    let address = u64::from_str_radix(address.strip_prefix("0x")?, 16).ok()?;
+    let name = if name.is_empty() || name == "<unknown>" { None } else { Some(name.to_string()) };
+    Some(Frame { index, address, name })
+}
+
+/// Finds where the image was loaded from the unnamed `frames`, as ASLR moves it on every run.
+///
+/// Images are loaded at 64 KiB boundaries, so each base that puts some frame inside an image of
+/// `image_size` bytes is tried, and the one under which the most frames resolve to line
+/// information wins. Returns `None` if no base does or several tie.
+pub fn infer_base(frames: &[Frame], image_size: u64, provider: &mut dyn SymbolProvider) -> Result<Option<u64>, String> {
+    const ALIGNMENT: u64 = 0x10000;
+    let addresses: Vec<u64> = frames.iter().filter(|frame| frame.name.is_none()).map(Frame::lookup_address).collect();
+    let mut bases = BTreeSet::new();
+    for &address in &addresses {
+        let highest = address & !(ALIGNMENT - 1);
+        let mut base = highest;
+        while highest - base < image_size {
+            bases.insert(base);
+            base = match base.checked_sub(ALIGNMENT) {
+                Some(base) => base,
+                None => break,
+            };
+        }
+    }
+
+    // All candidates are resolved in one pass over the symbol file.
+    let mut queries = Vec::new();
+    for (i, &base) in bases.iter().enumerate() {
+        for &address in &addresses {
+            if let Some(rva) = address.checked_sub(base).filter(|&rva| rva < image_size) {
+                queries.push((i, rva as u32));
+            }
+        }
+    }
+    let results = provider.resolve(&mut queries.iter().map(|&(_, rva)| rva))?;
+    let mut scores = vec![0usize; bases.len()];
+    for (&(i, _), result) in queries.iter().zip(&results) {
+        if result.frames.iter().any(|frame| frame.source == Source::LineInfo) {
+            scores[i] += 1;
+        }
+    }
+
+    let best = scores.iter().copied().max().unwrap_or(0);
+    if best == 0 || scores.iter().filter(|&&score| score == best).count() > 1 {
+        return Ok(None);
+    }
+    Ok(bases.iter().zip(&scores).find(|&(_, &score)| score == best).map(|(&base, _)| base))
+}
+
+/// Reprints the backtrace `text` with the unnamed frames named after `resolved`, which holds the
+/// resolution of each unnamed frame in turn, or `None` for frames outside the image.
+///
+/// Functions inlined at a frame follow it as `- name` lines without an index or address, the
+/// way Rust prints them, and each name is followed by its `at file:line` line.
+pub fn write(out: &mut dyn Write, text: &str, resolved: &[Option<Resolution>]) -> io::Result<()> {
+    let mut resolved = resolved.iter();
+    for line in text.lines() {
+        let frame = match parse_frame(line) {
+            Some(frame) if frame.name.is_none() => frame,
+            _ => {
+                writeln!(out, "{}", line)?;
+                continue;
+            }
+        };
+        let result = match resolved.next() {
+            Some(Some(result)) if !result.frames.is_empty() => result,
+            _ => {
+                writeln!(out, "{}", line)?;
+                continue;
+            }
+        };
+        // Inlined functions line up their `- ` with the frame's, and locations with the names.
+        let prefix = &line[..line.find("0x").expect("frame lines have an address")];
+        let address = format!("{:#x}", frame.address);
+        let dash = " ".repeat(prefix.len() + address.len() + 1);
+        let at = " ".repeat(dash.len() + 2);
+        for (i, resolved) in result.frames.iter().rev().enumerate() {
+            if i == 0 {
+                writeln!(out, "{}{} - {}", prefix, address, resolved.function)?;
+            } else {
+                writeln!(out, "{}- {}", dash, resolved.function)?;
+            }
+            if !resolved.file.is_empty() {
+                writeln!(out, "{}at {}:{}", at, resolved.file, resolved.line)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_frame, Frame};
+
+    #[test]
+    fn frame_lines() {
+        assert_eq!(
+            parse_frame("   3:     0x7ff6a1b2c3d4 - <unknown>"),
+            Some(Frame { index: 3, address: 0x7ff6_a1b2_c3d4, name: None })
+        );
+        assert_eq!(
+            parse_frame("  12:     0x7ff6a1b2c3d4 - std::rt::lang_start"),
+            Some(Frame { index: 12, address: 0x7ff6_a1b2_c3d4, name: Some("std::rt::lang_start".to_string()) })
+        );
+        assert_eq!(parse_frame("   4: 0x7ff6a1b2c3d4"), Some(Frame { index: 4, address: 0x7ff6_a1b2_c3d4, name: None }));
+        assert_eq!(parse_frame("stack backtrace:"), None);
+        assert_eq!(parse_frame("                               at /rustc/library/std/src/rt.rs:12"), None);
+    }
+}
@@ -0,0 +1,109 @@
+//! `cargo pdb-addr2line`: runs pdb-addr2line on the PDB of the current crate's latest build, so
+//! Rust developers on Windows need not spell out where cargo put it.
+//!
+//!     cargo pdb-addr2line [--release | --profile NAME] [--target TRIPLE] [--bin NAME] [ARGS...]
+//!
+//! ARGS are passed on to pdb-addr2line after `-e PDB`, so addresses given there are resolved and
+//! without any, they are read from stdin. A panic backtrace pasted on stdin is symbolized with
+//! `cargo pdb-addr2line --panic-backtrace`.
+
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+fn exit_with(message: &str) -> ! {
+    writeln!(&mut io::stderr(), "cargo-pdb-addr2line: {}", message).expect("stderr write");
+    process::exit(1);
+}
+
+/// The build directory cargo uses for the current workspace, from `cargo metadata`.
+fn target_directory() -> Result<PathBuf, String> {
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let output = Command::new(cargo)
+        .args(["metadata", "--format-version", "1", "--no-deps"])
+        .output()
+        .map_err(|e| format!("cannot run cargo metadata: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    let metadata = String::from_utf8_lossy(&output.stdout);
+    // `"target_directory":"C:\\work\\app\\target"`; paths need no escapes other than `\\`.
+    let start = metadata.find("\"target_directory\":\"").ok_or("cargo metadata names no target directory")? + 20;
+    let end = metadata[start..].find('"').ok_or("malformed cargo metadata")? + start;
+    Ok(PathBuf::from(metadata[start..end].replace("\\\\", "\\")))
+}
+
+/// The PDB of `bin` in `dir`, or the most recently written PDB there if no binary is named.
+fn find_pdb(dir: &Path, bin: Option<&str>) -> Result<PathBuf, String> {
+    if let Some(bin) = bin {
+        // rustc names the PDB after the crate, with dashes made underscores.
+        let path = dir.join(format!("{}.pdb", bin.replace('-', "_")));
+        return if path.is_file() { Ok(path) } else { Err(format!("'{}' does not exist; is the binary built?", path.display())) };
+    }
+    let entries = fs::read_dir(dir).map_err(|e| format!("cannot read '{}': {}", dir.display(), e))?;
+    entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().extension() == Some(OsStr::new("pdb")))
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .max()
+        .map(|(_, path)| path)
+        .ok_or_else(|| format!("no PDB in '{}'; build the crate for an MSVC target first", dir.display()))
+}
+
+/// pdb-addr2line installed next to this binary, as `cargo install` puts them, or from `PATH`.
+fn pdb_addr2line() -> PathBuf {
+    let name = format!("pdb-addr2line{}", env::consts::EXE_SUFFIX);
+    env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join(&name)))
+        .filter(|path| path.is_file())
+        .unwrap_or_else(|| PathBuf::from(name))
+}
+
+fn main() {
+    let mut args = env::args().skip(1).peekable();
+    // cargo passes the subcommand name first.
+    if args.peek().map(String::as_str) == Some("pdb-addr2line") {
+        args.next();
+    }
+
+    let mut profile = "debug".to_string();
+    let mut target = None;
+    let mut bin = None;
+    let mut rest = Vec::new();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().unwrap_or_else(|| exit_with(&format!("{} needs a value", name)));
+        match arg.as_str() {
+            "--release" => profile = "release".to_string(),
+            "--profile" => {
+                // The `dev` and `test` profiles build into `debug`, `bench` into `release`.
+                profile = match value("--profile").as_str() {
+                    "dev" | "test" => "debug".to_string(),
+                    "bench" => "release".to_string(),
+                    other => other.to_string(),
+                }
+            }
+            "--target" => target = Some(value("--target")),
+            "--bin" => bin = Some(value("--bin")),
+            _ => rest.push(arg),
+        }
+    }
+
+    let mut dir = target_directory().unwrap_or_else(|e| exit_with(&e));
+    if let Some(target) = target {
+        dir.push(target);
+    }
+    dir.push(profile);
+    let pdb = find_pdb(&dir, bin.as_deref()).unwrap_or_else(|e| exit_with(&e));
+
+    let status = Command::new(pdb_addr2line())
+        .arg("-e")
+        .arg(&pdb)
+        .args(&rest)
+        .status()
+        .unwrap_or_else(|e| exit_with(&format!("cannot run pdb-addr2line: {}", e)));
+    process::exit(status.code().unwrap_or(1));
+}
@@ -0,0 +1,259 @@
+//! Reader for Breakpad text symbol files (`.sym`).
+//!
+//! Understands the `FILE`, `FUNC`, line, `INLINE_ORIGIN`, `INLINE` and `PUBLIC` records; `STACK`
+//! and `INFO` records carry nothing needed for symbolization and are skipped.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::paths;
+use crate::output::{Frame, Resolution, Source};
+use crate::provider::{ProviderOptions, SymbolProvider};
+use crate::resolve::{self, Boundary};
+
+/// A `FUNC` record with the line and inline records that follow it.
+#[derive(Debug)]
+struct Function {
+    address: u64,
+    size: u64,
+    name: String,
+    /// Line records as (address, size, line, file number), in file order.
+    lines: Vec<(u64, u64, u64, u64)>,
+    inlines: Vec<Inline>,
+}
+
+/// An `INLINE` record: a call of `origin` inlined at nesting `depth` over `ranges`.
+#[derive(Debug)]
+struct Inline {
+    depth: u32,
+    call_line: u64,
+    call_file: u64,
+    origin: u64,
+    ranges: Vec<(u64, u64)>,
+}
+
+/// The parsed contents of a Breakpad symbol file.
+#[derive(Debug, Default)]
+pub struct SymbolFile {
+    files: HashMap<u64, String>,
+    origins: HashMap<u64, String>,
+    /// Functions sorted by address.
+    functions: Vec<Function>,
+    /// Public symbols as (address, name), sorted by address.
+    publics: Vec<(u32, String)>,
+}
+
+impl SymbolFile {
+    /// Parses the text of a symbol file, failing with the number of the first malformed line.
+    pub fn parse(text: &str) -> Result<SymbolFile, String> {
+        let mut symbols = SymbolFile::default();
+        for (number, line) in (1..).zip(text.lines()) {
+            symbols
+                .parse_line(line.trim_end())
+                .ok_or_else(|| format!("malformed record on line {}", number))?;
+        }
+        symbols.functions.sort_by_key(|function| function.address);
+        symbols.publics.sort_by_key(|&(address, _)| address);
+        Ok(symbols)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Option<()> {
+        let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match keyword {
+            "FILE" => {
+                let (number, name) = rest.split_once(' ')?;
+                self.files.insert(number.parse().ok()?, name.to_string());
+            }
+            "INLINE_ORIGIN" => {
+                let (number, name) = rest.split_once(' ')?;
+                self.origins.insert(number.parse().ok()?, name.to_string());
+            }
+            "FUNC" => {
+                let rest = rest.strip_prefix("m ").unwrap_or(rest);
+                let mut fields = rest.splitn(4, ' ');
+                let address = hex(fields.next()?)?;
+                let size = hex(fields.next()?)?;
+                let _parameter_size = fields.next()?;
+                self.functions.push(Function {
+                    address,
+                    size,
+                    name: fields.next().unwrap_or("").to_string(),
+                    lines: Vec::new(),
+                    inlines: Vec::new(),
+                });
+            }
+            "PUBLIC" => {
+                let rest = rest.strip_prefix("m ").unwrap_or(rest);
+                let mut fields = rest.splitn(3, ' ');
+                let address = u32::try_from(hex(fields.next()?)?).ok()?;
+                let _parameter_size = fields.next()?;
+                self.publics.push((address, fields.next().unwrap_or("").to_string()));
+            }
+            "INLINE" => {
+                // Nesting depth, call site line, call site file and origin, then address ranges.
+                let fields: Vec<&str> = rest.split(' ').collect();
+                if fields.len() < 6 || !fields.len().is_multiple_of(2) {
+                    return None;
+                }
+                let ranges = fields[4..]
+                    .chunks(2)
+                    .map(|pair| Some((hex(pair[0])?, hex(pair[1])?)))
+                    .collect::<Option<Vec<_>>>()?;
+                let inline = Inline {
+                    depth: fields[0].parse().ok()?,
+                    call_line: fields[1].parse().ok()?,
+                    call_file: fields[2].parse().ok()?,
+                    origin: fields[3].parse().ok()?,
+                    ranges,
+                };
+                self.functions.last_mut()?.inlines.push(inline);
+            }
+            "" => {}
+            // Records added to the format later, and those like STACK that are not needed here.
+            keyword if keyword.chars().all(|c| c.is_ascii_uppercase() || c == '_') => {}
+            _ => {
+                let mut fields = line.split(' ');
+                let address = hex(fields.next()?)?;
+                let size = hex(fields.next()?)?;
+                let line = fields.next()?.parse().ok()?;
+                let file = fields.next()?.parse().ok()?;
+                self.functions.last_mut()?.lines.push((address, size, line, file));
+            }
+        }
+        Some(())
+    }
+
+    /// Resolves `address` to the function containing it and the functions inlined into it,
+    /// outermost first.
+    fn frames(&self, address: u32, boundary: Boundary) -> Vec<Frame> {
+        let target = u64::from(address);
+        let index = self.functions.partition_point(|function| function.address <= target);
+        let function = match index.checked_sub(1).map(|index| &self.functions[index]) {
+            Some(function) if resolve::in_range(function.address, function.size, target) => function,
+            _ => return Vec::new(),
+        };
+
+        let file = |number: u64| self.files.get(&number).cloned().unwrap_or_default();
+        let covering: Vec<_> = function.lines.iter().filter(|l| resolve::in_range(l.0, l.1, target)).collect();
+        let starts: Vec<u64> = covering.iter().map(|l| l.0).collect();
+        let line = resolve::select_line(&starts, target, boundary).map(|index| covering[index]);
+
+        let mut inlines: Vec<&Inline> = function
+            .inlines
+            .iter()
+            .filter(|inline| inline.ranges.iter().any(|&(start, size)| resolve::in_range(start, size, target)))
+            .collect();
+        inlines.sort_by_key(|inline| inline.depth);
+
+        // Each frame's location is the call site of the next deeper inline, the innermost frame's
+        // location is the line record.
+        let mut names = vec![(function.name.clone(), false)];
+        names.extend(inlines.iter().map(|inline| (self.origins.get(&inline.origin).cloned().unwrap_or_default(), true)));
+        let mut locations: Vec<Option<(String, u64)>> =
+            inlines.iter().map(|inline| Some((file(inline.call_file), inline.call_line))).collect();
+        locations.push(line.map(|&(_, _, line, number)| (file(number), line)));
+
+        let innermost = names.len() - 1;
+        let line_start = line.map(|&(start, _, _, _)| start);
+        names
+            .into_iter()
+            .zip(locations)
+            .enumerate()
+            .map(|(index, ((name, inline), location))| {
+                let (source, (file, line)) = match location {
+                    Some(location) => (Source::LineInfo, location),
+                    None => (Source::ProcedureOnly, (String::new(), 0)),
+                };
+                Frame {
+                    function: name,
+                    file,
+                    line,
+                    column: None,
+                    function_offset: if inline { None } else { Some((target - function.address) as u32) },
+                    line_offset: line_start.filter(|_| index == innermost).map(|start| (target - start) as u32),
+                    inline,
+                    source,
+                    repo: None,
+                    aliases: Vec::new(),
+                    signature: None,
+                    language: None,
+                    system: false,
+                    candidates: Vec::new(),
+                    prologue: false,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Resolves addresses with the functions, lines and publics of a Breakpad symbol file.
+pub struct BreakpadProvider {
+    pub path: PathBuf,
+    pub options: ProviderOptions,
+}
+
+impl SymbolProvider for BreakpadProvider {
+    fn kind(&self) -> &'static str {
+        "Breakpad .sym"
+    }
+
+    fn resolve(&mut self, addresses: &mut dyn Iterator<Item = u32>) -> Result<Vec<Resolution>, String> {
+        let text = fs::read_to_string(paths::long_path(&self.path)).map_err(|e| e.to_string())?;
+        let symbols = SymbolFile::parse(&text)?;
+        let mut results: Vec<Resolution> = addresses
+            .map(|address| Resolution::new(address, symbols.frames(address, self.options.boundary)))
+            .collect();
+
+        let procedures: Vec<(u32, u32, String)> = symbols
+            .functions
+            .iter()
+            .filter_map(|f| Some((u32::try_from(f.address).ok()?, u32::try_from(f.size).ok()?, f.name.clone())))
+            .collect();
+        resolve::resolve_unmatched(&procedures, &symbols.publics, &mut results);
+        Ok(results)
+    }
+}
+
+fn hex(s: &str) -> Option<u64> {
+    u64::from_str_radix(s, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYMBOLS: &str = "MODULE windows x86_64 0102030405060708090A0B0C0D0E0F101 app.pdb
+FILE 0 c:\\src\\main.cpp
+FILE 1 c:\\src\\util.h
+INLINE_ORIGIN 0 util::helper()
+FUNC 1000 40 0 main
+INLINE 0 12 0 0 1010 10
+1000 10 10 0
+1010 10 30 1
+1020 20 14 0
+PUBLIC 1080 0 other
+STACK WIN 4 1000 40 0 0 0 0 0 0 1 $T0 .raSearch =
+";
+
+    fn locations(frames: &[Frame]) -> Vec<(&str, &str, u64)> {
+        frames.iter().map(|f| (f.function.as_str(), f.file.as_str(), f.line)).collect()
+    }
+
+    #[test]
+    fn inline_frames_use_call_sites() {
+        let symbols = SymbolFile::parse(SYMBOLS).unwrap();
+        assert_eq!(
+            locations(&symbols.frames(0x1014, Boundary::Last)),
+            [("main", "c:\\src\\main.cpp", 12), ("util::helper()", "c:\\src\\util.h", 30)]
+        );
+        assert_eq!(locations(&symbols.frames(0x1020, Boundary::Last)), [("main", "c:\\src\\main.cpp", 14)]);
+        assert!(symbols.frames(0x1040, Boundary::Last).is_empty());
+    }
+
+    #[test]
+    fn malformed_line_is_reported() {
+        assert_eq!(SymbolFile::parse("FUNC 1000 zz 0 main").unwrap_err(), "malformed record on line 1");
+    }
+}
@@ -0,0 +1,92 @@
+//! What this build of the tool supports, for `--version` and scripts probing for features.
+
+use crate::output::{json_string, SCHEMA_VERSION};
+
+/// The crate version.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The commit the binary was built from, or `unknown` outside a git checkout.
+pub const GIT_HASH: &str = env!("PDB_ADDR2LINE_GIT_HASH");
+
+/// Cargo features and whether they were enabled for this build.
+pub const FEATURES: &[(&str, bool)] = &[("demangle", cfg!(feature = "demangle"))];
+
+/// Versions of the JSON output schema this build can write.
+pub const SCHEMA_VERSIONS: &[&str] = &[SCHEMA_VERSION];
+
+/// What the resolver can report, so clients can degrade gracefully with older builds.
+pub const ABILITIES: &[(&str, bool)] = &[
+    ("inline-frames", true),
+    // Columns are only printed by the gcc style, not in the structured output.
+    ("columns", false),
+    ("data-symbols", false),
+    ("locals", true),
+    ("blocks", true),
+    ("signatures", true),
+    ("source-repositories", true),
+    ("breakpad-symbols", true),
+    ("loaded-modules", true),
+];
+
+/// The methods of the `--stdio-jsonrpc` server.
+pub const RPC_METHODS: &[&str] =
+    &["capabilities", "resolve", "findSymbol", "listModules", "loadedSources", "sourceLookup"];
+
+/// The enabled cargo features.
+pub fn enabled_features() -> impl Iterator<Item = &'static str> {
+    FEATURES.iter().filter(|&&(_, enabled)| enabled).map(|&(name, _)| name)
+}
+
+/// The text printed by `--version`.
+pub fn version_text() -> String {
+    let features: Vec<&str> = enabled_features().collect();
+    format!(
+        "pdb-addr2line {} ({})\nfeatures: {}\njson schema versions: {}\n",
+        VERSION,
+        GIT_HASH,
+        if features.is_empty() { "none".to_string() } else { features.join(", ") },
+        SCHEMA_VERSIONS.join(", ")
+    )
+}
+
+/// The text printed by the `capabilities` subcommand: `--version` and the abilities.
+pub fn capabilities_text() -> String {
+    let mut text = version_text();
+    for &(name, supported) in ABILITIES {
+        text.push_str(&format!("{}: {}\n", name, if supported { "yes" } else { "no" }));
+    }
+    text
+}
+
+/// Everything above as one JSON object, printed by `capabilities --json`.
+pub fn to_json() -> String {
+    let flags = |flags: &[(&str, bool)]| {
+        let members: Vec<String> = flags.iter().map(|&(name, on)| format!("{}:{}", json_string(name), on)).collect();
+        format!("{{{}}}", members.join(","))
+    };
+    let list = |items: &[&str]| {
+        let items: Vec<String> = items.iter().map(|item| json_string(item)).collect();
+        format!("[{}]", items.join(","))
+    };
+    format!(
+        "{{\"version\":{},\"commit\":{},\"features\":{},\"schema_versions\":{},\"capabilities\":{},\"rpc_methods\":{}}}",
+        json_string(VERSION),
+        json_string(GIT_HASH),
+        flags(FEATURES),
+        list(SCHEMA_VERSIONS),
+        flags(ABILITIES),
+        list(RPC_METHODS)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::{self, Value};
+
+    #[test]
+    fn capabilities_are_valid_json() {
+        let value = json::parse(&super::to_json()).unwrap();
+        assert_eq!(value.get("version").and_then(Value::as_str), Some(super::VERSION));
+        assert_eq!(value.get("capabilities").and_then(|c| c.get("inline-frames")), Some(&Value::Bool(true)));
+    }
+}
@@ -0,0 +1,123 @@
+//! Column numbers of the line records of a module, read from its C13 line subsections directly:
+//! pdb 0.6 decodes the column entries of module line records from the line entries before them.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+use pdb::{AddressMap, PdbInternalSectionOffset, StreamIndex, PDB};
+
+use crate::msf::{self, ModuleRecord};
+
+const DEBUG_S_LINES: u32 = 0xf2;
+const DEBUG_S_IGNORE: u32 = 0x8000_0000;
+/// The flag of a lines subsection whose blocks have a column entry per line entry.
+const CV_LINES_HAVE_COLUMNS: u16 = 1;
+
+/// The module records of the PDB, or none if its DBI stream cannot be read.
+pub fn module_records<'s, S: pdb::Source<'s> + 's>(pdb: &mut PDB<'s, S>) -> Vec<ModuleRecord> {
+    match pdb.raw_stream(StreamIndex(3)) {
+        Ok(Some(dbi)) => msf::module_records(dbi.as_slice()),
+        _ => Vec::new(),
+    }
+}
+
+/// The start column of each line record of `module` that has one, by RVA and line number.
+pub fn module_columns<'s, S: pdb::Source<'s> + 's>(
+    pdb: &mut PDB<'s, S>,
+    module: &ModuleRecord,
+    address_map: &AddressMap,
+) -> HashMap<(u64, u64), u32> {
+    let stream = match pdb.raw_stream(StreamIndex(module.stream)) {
+        Ok(Some(stream)) => stream,
+        _ => return HashMap::new(),
+    };
+    let start = module.symbols_size as usize + module.c11_lines_size as usize;
+    let c13 = match stream.as_slice().get(start..start + module.c13_lines_size as usize) {
+        Some(c13) => c13,
+        None => return HashMap::new(),
+    };
+    line_columns(c13)
+        .into_iter()
+        .filter_map(|(section, offset, line, column)| {
+            let rva = PdbInternalSectionOffset { offset, section }.to_rva(address_map)?;
+            Some(((u64::from(rva.0), line), column))
+        })
+        .collect()
+}
+
+/// The section, offset, line and start column of the line entries of the C13 line information
+/// `c13` that have a column other than 0.
+fn line_columns(c13: &[u8]) -> Vec<(u16, u32, u64, u32)> {
+    let mut columns = Vec::new();
+    let mut offset = 0;
+    while let (Some(kind), Some(size)) = (u32_at(c13, offset), u32_at(c13, offset + 4)) {
+        let data = match c13.get(offset + 8..offset + 8 + size as usize) {
+            Some(data) => data,
+            None => break,
+        };
+        offset = (offset + 8 + size as usize + 3) & !3;
+        if kind & !DEBUG_S_IGNORE != DEBUG_S_LINES || kind & DEBUG_S_IGNORE != 0 {
+            continue;
+        }
+        let (base, section, flags) = match (u32_at(data, 0), u16_at(data, 4), u16_at(data, 6)) {
+            (Some(base), Some(section), Some(flags)) => (base, section, flags),
+            _ => continue,
+        };
+        if flags & CV_LINES_HAVE_COLUMNS == 0 {
+            continue;
+        }
+
+        // Each block is a file index, the number of entries and the block's size, followed by
+        // the 8-byte line entries and then the 4-byte column entries.
+        let mut block = 12;
+        while let (Some(count), Some(block_size)) = (u32_at(data, block + 4), u32_at(data, block + 8)) {
+            let count = count as usize;
+            for i in 0..count {
+                let line_entry = block + 12 + 8 * i;
+                let column_entry = block + 12 + 8 * count + 4 * i;
+                if let (Some(entry_offset), Some(line), Some(column)) =
+                    (u32_at(data, line_entry), u32_at(data, line_entry + 4), u16_at(data, column_entry))
+                {
+                    if column != 0 {
+                        let line = u64::from(line & 0x00ff_ffff);
+                        columns.push((section, base.wrapping_add(entry_offset), line, u32::from(column)));
+                    }
+                }
+            }
+            if block_size < 12 {
+                break;
+            }
+            block += block_size as usize;
+        }
+    }
+    columns
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::line_columns;
+
+    #[test]
+    fn columns_follow_the_line_entries() {
+        let words: Vec<u32> = vec![
+            // A lines subsection of section 1 from offset 0x100, with columns.
+            0xf2, 48, 0x100, 1 << 16 | 1, 0x20,
+            // One block of file 0 with two entries: lines 10 and 11 at 0 and 8.
+            0, 2, 36, 0, 0x8000_000a, 8, 0x8000_000b,
+        ];
+        let mut c13: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes().to_vec()).collect();
+        // Columns 5-9 and 13-20.
+        for column in &[5u16, 9, 13, 20] {
+            c13.extend_from_slice(&column.to_le_bytes());
+        }
+        assert_eq!(line_columns(&c13), vec![(1, 0x100, 10, 5), (1, 0x108, 11, 13)]);
+    }
+}
@@ -0,0 +1,63 @@
+//! `--confidence`: a score from 0 to 1 per frame saying how much to trust it, so pipelines
+//! processing many stacks can weight or flag frames the symbol file only roughly placed.
+
+use crate::output::{Frame, Source};
+use crate::resolve::HIDDEN_LINES;
+
+/// The confidence in `frame`: 1 for an address within a line record, less for addresses in the
+/// prologue, shared with other line records or in a gap between line records, and least for
+/// frames that only name the nearest symbol.
+pub fn score(frame: &Frame) -> f64 {
+    match frame.source {
+        Source::Padding => 0.1,
+        Source::PublicSymbol => 0.3,
+        Source::ProcedureOnly if frame.prologue => 0.6,
+        Source::ProcedureOnly => 0.5,
+        Source::Fallback => 0.7,
+        Source::LineInfo => {
+            let mut score: f64 = 1.0;
+            if frame.line == 0 || HIDDEN_LINES.contains(&frame.line) {
+                score = score.min(0.4);
+            }
+            if !frame.candidates.is_empty() {
+                score = score.min(0.7);
+            }
+            if frame.prologue {
+                score = score.min(0.8);
+            }
+            score
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::score;
+    use crate::output::{Frame, Source};
+
+    #[test]
+    fn scores() {
+        let frame = Frame {
+            function: "main".to_string(),
+            file: "a.cpp".to_string(),
+            line: 12,
+            column: None,
+            function_offset: Some(8),
+            line_offset: Some(0),
+            inline: false,
+            source: Source::LineInfo,
+            repo: None,
+            aliases: Vec::new(),
+            signature: None,
+            language: None,
+            system: false,
+            candidates: Vec::new(),
+            prologue: false,
+        };
+        assert_eq!(score(&frame), 1.0);
+        assert_eq!(score(&Frame { prologue: true, ..frame.clone() }), 0.8);
+        assert_eq!(score(&Frame { candidates: vec![("a.h".to_string(), 7)], prologue: true, ..frame.clone() }), 0.7);
+        assert_eq!(score(&Frame { line: 0xfeefee, ..frame.clone() }), 0.4);
+        assert_eq!(score(&Frame { source: Source::PublicSymbol, ..frame }), 0.3);
+    }
+}
@@ -0,0 +1,377 @@
+//! `--db`: resolving addresses against a SQLite database written by `--export-sqlite`, which
+//! starts instantly and can be shared without distributing the PDBs.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::path::PathBuf;
+
+use crate::export;
+use crate::output::{Annotation, Frame, Resolution, Source, ANNOTATION_DISTANCE};
+use crate::provider::{ProviderOptions, SymbolProvider};
+use crate::repo::SourceRepo;
+use crate::resolve;
+use crate::sqlite::{Database, Value};
+use crate::{language_name, Trampoline};
+
+/// A row of the `functions` table with its `lines` and `inlinees` rows.
+struct Function {
+    name: String,
+    address: u64,
+    size: u64,
+    language: Option<&'static str>,
+    /// Offset of the end of the prologue.
+    debug_start: u64,
+    system: bool,
+    /// Line records as (address, size, file id, line, column), by address.
+    lines: Vec<(u64, u64, i64, u64, Option<u32>)>,
+    /// Inlinee line records, by depth.
+    inlines: Vec<Inline>,
+}
+
+/// A row of the `inlinees` table.
+struct Inline {
+    depth: i64,
+    name: String,
+    address: u64,
+    size: u64,
+    file: i64,
+    line: u64,
+    column: Option<u32>,
+}
+
+/// The contents of a database.
+struct Symbols {
+    files: HashMap<i64, String>,
+    /// Repository locations of the files that have one, by file id.
+    repos: HashMap<i64, SourceRepo>,
+    /// Functions by address, in table order for the same address.
+    functions: Vec<Function>,
+    /// Public symbols as (address, name), by address.
+    publics: Vec<(u32, String)>,
+    /// Annotations in table order.
+    annotations: Vec<Annotation>,
+    trampolines: Vec<Trampoline>,
+}
+
+impl Symbols {
+    fn read(database: &Database) -> Result<Symbols, String> {
+        let version = database.user_version();
+        if version != export::SCHEMA_VERSION {
+            return Err(format!("unsupported database version {} (expected {})", version, export::SCHEMA_VERSION));
+        }
+        let malformed = |table: &str, rowid: i64| format!("malformed row {} of table '{}'", rowid, table);
+        let integer = |values: &[Value], index: usize| values.get(index).and_then(Value::as_integer);
+        let text = |values: &[Value], index: usize| values.get(index).and_then(Value::as_text).map(str::to_string);
+        let column = |values: &[Value], index: usize| integer(values, index).and_then(|c| u32::try_from(c).ok());
+
+        let mut files = HashMap::new();
+        let mut repos = HashMap::new();
+        for (rowid, values) in database.table("files")? {
+            files.insert(rowid, text(&values, 1).ok_or_else(|| malformed("files", rowid))?);
+            if let (Some(repo_url), Some(revision), Some(repo_relative_path)) =
+                (text(&values, 2), text(&values, 3), text(&values, 4))
+            {
+                repos.insert(rowid, SourceRepo { repo_url, revision, repo_relative_path });
+            }
+        }
+
+        let mut functions = Vec::new();
+        let mut by_id = HashMap::new();
+        for (rowid, values) in database.table("functions")? {
+            let function = (|| {
+                Some(Function {
+                    name: text(&values, 1)?,
+                    address: u64::try_from(integer(&values, 2)?).ok()?,
+                    size: u64::try_from(integer(&values, 3)?).ok()?,
+                    language: text(&values, 4).and_then(|name| (0..=u8::MAX).filter_map(language_name).find(|&l| l == name)),
+                    debug_start: u64::try_from(integer(&values, 5)?).ok()?,
+                    system: integer(&values, 6)? != 0,
+                    lines: Vec::new(),
+                    inlines: Vec::new(),
+                })
+            })()
+            .ok_or_else(|| malformed("functions", rowid))?;
+            by_id.insert(rowid, functions.len());
+            functions.push(function);
+        }
+
+        for (rowid, values) in database.table("lines")? {
+            let line = (|| {
+                let function = *by_id.get(&integer(&values, 0)?)?;
+                let record = (
+                    u64::try_from(integer(&values, 1)?).ok()?,
+                    u64::try_from(integer(&values, 2)?).ok()?,
+                    integer(&values, 3)?,
+                    u64::try_from(integer(&values, 4)?).ok()?,
+                    column(&values, 5),
+                );
+                Some((function, record))
+            })()
+            .ok_or_else(|| malformed("lines", rowid))?;
+            functions[line.0].lines.push(line.1);
+        }
+
+        for (rowid, values) in database.table("inlinees")? {
+            let inline = (|| {
+                let function = *by_id.get(&integer(&values, 0)?)?;
+                let inline = Inline {
+                    depth: integer(&values, 1)?,
+                    name: text(&values, 2)?,
+                    address: u64::try_from(integer(&values, 3)?).ok()?,
+                    size: u64::try_from(integer(&values, 4)?).ok()?,
+                    file: integer(&values, 5)?,
+                    line: u64::try_from(integer(&values, 6)?).ok()?,
+                    column: column(&values, 7),
+                };
+                Some((function, inline))
+            })()
+            .ok_or_else(|| malformed("inlinees", rowid))?;
+            functions[inline.0].inlines.push(inline.1);
+        }
+
+        let mut publics = Vec::new();
+        for (rowid, values) in database.table("publics")? {
+            let address = integer(&values, 0).and_then(|address| u32::try_from(address).ok());
+            match (address, text(&values, 1)) {
+                (Some(address), Some(name)) => publics.push((address, name)),
+                _ => return Err(malformed("publics", rowid)),
+            }
+        }
+
+        let mut annotations = Vec::new();
+        for (rowid, values) in database.table("annotations")? {
+            let address = integer(&values, 0).and_then(|address| u32::try_from(address).ok());
+            match (address, text(&values, 1)) {
+                (Some(address), Some(strings)) => {
+                    let strings =
+                        if strings.is_empty() { Vec::new() } else { strings.split('\n').map(str::to_string).collect() };
+                    annotations.push(Annotation { address, strings });
+                }
+                _ => return Err(malformed("annotations", rowid)),
+            }
+        }
+
+        let mut trampolines = Vec::new();
+        for (rowid, values) in database.table("trampolines")? {
+            let trampoline = (|| {
+                Some(Trampoline {
+                    start: u32::try_from(integer(&values, 0)?).ok()?,
+                    size: u16::try_from(integer(&values, 1)?).ok()?,
+                    target: u32::try_from(integer(&values, 2)?).ok()?,
+                })
+            })()
+            .ok_or_else(|| malformed("trampolines", rowid))?;
+            trampolines.push(trampoline);
+        }
+
+        for function in &mut functions {
+            function.lines.sort_by_key(|line| line.0);
+            function.inlines.sort_by_key(|inline| inline.depth);
+        }
+        functions.sort_by_key(|function| function.address);
+        publics.sort_by_key(|&(address, _)| address);
+        Ok(Symbols { files, repos, functions, publics, annotations, trampolines })
+    }
+
+    /// Resolves `address` to the functions containing it, several if identical COMDAT folding
+    /// merged them, each followed by the functions inlined into it, outermost first.
+    fn frames(&self, address: u32, options: &ProviderOptions) -> Vec<Frame> {
+        let target = u64::from(address);
+        let end = self.functions.partition_point(|function| function.address <= target);
+        let start = match end.checked_sub(1) {
+            Some(last) => self.functions[..end].partition_point(|f| f.address < self.functions[last].address),
+            None => return Vec::new(),
+        };
+        let file = |id: i64| self.files.get(&id).cloned().unwrap_or_default();
+        let repo = |id: i64| self.repos.get(&id).cloned();
+
+        let mut frames = Vec::new();
+        for function in self.functions[start..end].iter().filter(|f| resolve::in_range(f.address, f.size, target)) {
+            // As from the PDB, --functions-only leaves the line records of functions unread.
+            let lines = if options.functions_only { &[][..] } else { &function.lines[..] };
+            let starts: Vec<u64> = lines.iter().map(|line| line.0).collect();
+            let records: Vec<(u64, Option<u64>)> = lines.iter().map(|line| (line.0, Some(line.1))).collect();
+            let function_offset = Some((target - function.address) as u32);
+            let prologue = target - function.address < function.debug_start;
+            frames.push(match resolve::select_line(&starts, target, options.boundary) {
+                Some(index) => {
+                    let (line_address, _, file_id, line, column) = lines[index];
+                    Frame {
+                        function: function.name.clone(),
+                        file: file(file_id),
+                        line,
+                        column,
+                        function_offset,
+                        line_offset: Some((target - line_address) as u32),
+                        inline: false,
+                        source: Source::LineInfo,
+                        repo: repo(file_id),
+                        aliases: Vec::new(),
+                        signature: None,
+                        language: function.language,
+                        system: function.system,
+                        candidates: resolve::line_candidates(&records, target, index, |other| {
+                            (file(lines[other].2), lines[other].3)
+                        }),
+                        prologue,
+                    }
+                }
+                None => Frame {
+                    function: function.name.clone(),
+                    file: String::new(),
+                    line: 0,
+                    column: None,
+                    function_offset,
+                    line_offset: None,
+                    inline: false,
+                    source: Source::ProcedureOnly,
+                    repo: None,
+                    aliases: Vec::new(),
+                    signature: None,
+                    language: function.language,
+                    system: function.system,
+                    candidates: Vec::new(),
+                    prologue,
+                },
+            });
+            for inline in function.inlines.iter().filter(|inline| resolve::in_range(inline.address, inline.size, target)) {
+                frames.push(Frame {
+                    function: inline.name.clone(),
+                    file: file(inline.file),
+                    line: inline.line,
+                    column: inline.column,
+                    function_offset: None,
+                    line_offset: Some((target - inline.address) as u32),
+                    inline: true,
+                    source: Source::LineInfo,
+                    repo: repo(inline.file),
+                    aliases: Vec::new(),
+                    signature: None,
+                    language: function.language,
+                    system: function.system,
+                    candidates: Vec::new(),
+                    prologue: false,
+                });
+            }
+        }
+        frames
+    }
+
+    /// Resolves `addresses` as reading the PDB does: with the functions and inlinees covering
+    /// them, the annotations near them and, with `follow`, the targets of the trampolines they
+    /// are in, falling back to publics for the rest.
+    fn resolve(
+        &self,
+        addresses: &mut dyn Iterator<Item = u32>,
+        options: &ProviderOptions,
+        follow: bool,
+    ) -> Vec<Resolution> {
+        let mut results: Vec<Resolution> =
+            addresses.map(|address| Resolution::new(address, self.frames(address, options))).collect();
+        let queries = resolve::sort_queries(results.iter().map(|result| result.address));
+        for annotation in &self.annotations {
+            let start = u64::from(annotation.address.saturating_sub(ANNOTATION_DISTANCE));
+            let end = u64::from(annotation.address) + u64::from(ANNOTATION_DISTANCE) + 1;
+            for index in resolve::in_sorted_range(&queries, start, end - start) {
+                results[index].annotations.push(annotation.clone());
+            }
+        }
+        if follow {
+            self.follow_trampolines(options, &queries, &mut results);
+        }
+
+        let procedures: Vec<(u32, u32, String)> = self
+            .functions
+            .iter()
+            .filter_map(|f| Some((u32::try_from(f.address).ok()?, u32::try_from(f.size).ok()?, f.name.clone())))
+            .collect();
+        resolve::resolve_unmatched(&procedures, &self.publics, &mut results);
+        resolve::merge_folded(&self.publics, &mut results);
+        results
+    }
+
+    /// The database side of `follow_trampolines` in main.rs, with the same frames.
+    fn follow_trampolines(&self, options: &ProviderOptions, queries: &[(u32, usize)], results: &mut [Resolution]) {
+        let within = |trampoline: &Trampoline, address: u32| {
+            address.wrapping_sub(trampoline.start) < u32::from(trampoline.size)
+        };
+        let mut hits = Vec::new();
+        for trampoline in &self.trampolines {
+            if self.trampolines.iter().any(|other| within(other, trampoline.target)) {
+                continue;
+            }
+            for index in resolve::in_sorted_range(queries, trampoline.start.into(), trampoline.size.into()) {
+                if results[index].frames.is_empty() {
+                    hits.push((index, trampoline));
+                }
+            }
+        }
+        if hits.is_empty() {
+            return;
+        }
+        let targets = self.resolve(&mut hits.iter().map(|(_, trampoline)| trampoline.target), options, false);
+        for ((index, trampoline), target) in hits.into_iter().zip(targets) {
+            let result = &mut results[index];
+            let name =
+                target.frames.first().map_or_else(|| format!("{:#x}", trampoline.target), |frame| frame.function.clone());
+            result.frames.push(Frame {
+                function: format!("trampoline to {}", name),
+                file: String::new(),
+                line: 0,
+                column: None,
+                function_offset: Some(result.address - trampoline.start),
+                line_offset: None,
+                inline: false,
+                source: Source::ProcedureOnly,
+                repo: None,
+                aliases: Vec::new(),
+                signature: None,
+                language: None,
+                system: false,
+                candidates: Vec::new(),
+                prologue: false,
+            });
+            // The target's offsets are from the target address, not from the one looked up.
+            let target_frames = target.frames.into_iter().map(|frame| Frame { function_offset: None, line_offset: None, ..frame });
+            result.frames.extend(target_frames);
+        }
+    }
+}
+
+/// Resolves addresses with the tables of a database written by `--export-sqlite`.
+///
+/// The tables are read once, on first use or by `load`, and kept for all later lookups.
+pub struct DbProvider {
+    path: PathBuf,
+    options: ProviderOptions,
+    symbols: Option<Symbols>,
+    kind: &'static str,
+}
+
+impl DbProvider {
+    pub fn new(path: PathBuf, options: ProviderOptions) -> DbProvider {
+        DbProvider { path, options, symbols: None, kind: "SQLite database" }
+    }
+
+    /// Reads the database `warm` wrote for a PDB at `path` right away, failing if it cannot be
+    /// read. The provider stands in for the PDB, and names itself one in messages.
+    pub fn warm(path: PathBuf, options: ProviderOptions) -> Result<DbProvider, String> {
+        let symbols = Symbols::read(&Database::open(&path)?)?;
+        Ok(DbProvider { path, options, symbols: Some(symbols), kind: "PDB" })
+    }
+}
+
+impl SymbolProvider for DbProvider {
+    fn kind(&self) -> &'static str {
+        self.kind
+    }
+
+    fn resolve(&mut self, addresses: &mut dyn Iterator<Item = u32>) -> Result<Vec<Resolution>, String> {
+        let options = self.options;
+        let symbols = match self.symbols {
+            Some(ref symbols) => symbols,
+            None => self.symbols.insert(Symbols::read(&Database::open(&self.path)?)?),
+        };
+        Ok(symbols.resolve(addresses, &options, true))
+    }
+}
@@ -0,0 +1,120 @@
+//! Demangling of symbol names, by the built-in MSVC and Rust demanglers or by ones installed in
+//! their place, such as one reversing an internal name obfuscation.
+//!
+//! Every name the resolver shows for a public symbol and every name `findSymbol` matches goes
+//! through the installed demanglers, the first one that recognizes a name winning.
+
+use std::sync::RwLock;
+
+/// How much of a demangled name to keep.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Style {
+    /// The full signature where the scheme encodes one, like `int __cdecl main(void)`.
+    Full,
+    /// Just the qualified name, as procedure records name functions.
+    NameOnly,
+}
+
+/// A mangling scheme.
+pub trait Demangler: Send + Sync {
+    /// The name `--demangler` selects the demangler by.
+    fn name(&self) -> &'static str;
+
+    /// Demangles `symbol`, or returns `None` if it is not mangled in this scheme.
+    fn demangle(&self, symbol: &str, style: Style) -> Option<String>;
+}
+
+/// MSVC C++ decorated names like `?main@@YAHXZ`.
+#[cfg(feature = "demangle")]
+pub struct Msvc;
+
+#[cfg(feature = "demangle")]
+impl Demangler for Msvc {
+    fn name(&self) -> &'static str {
+        "msvc"
+    }
+
+    fn demangle(&self, symbol: &str, style: Style) -> Option<String> {
+        let flags = match style {
+            Style::Full => msvc_demangler::DemangleFlags::llvm(),
+            Style::NameOnly => msvc_demangler::DemangleFlags::NAME_ONLY,
+        };
+        msvc_demangler::demangle(symbol, flags).ok()
+    }
+}
+
+/// Legacy Rust symbols like `_ZN3std2rt10lang_start17h0123456789abcdefE`, without the hash.
+pub struct Rust;
+
+impl Demangler for Rust {
+    fn name(&self) -> &'static str {
+        "rust"
+    }
+
+    fn demangle(&self, symbol: &str, _: Style) -> Option<String> {
+        crate::rust::demangle(symbol)
+    }
+}
+
+/// The built-in demanglers, in the order they are tried by default.
+pub fn builtins() -> Vec<Box<dyn Demangler>> {
+    vec![
+        #[cfg(feature = "demangle")]
+        Box::new(Msvc),
+        Box::new(Rust),
+    ]
+}
+
+/// The installed demanglers, or `None` for the built-ins.
+static INSTALLED: RwLock<Option<Vec<Box<dyn Demangler>>>> = RwLock::new(None);
+
+/// Replaces the demanglers tried for every name, in order. An embedder adding its own scheme
+/// installs it followed by `builtins()`.
+pub fn install(demanglers: Vec<Box<dyn Demangler>>) {
+    *INSTALLED.write().expect("demangler lock") = Some(demanglers);
+}
+
+/// Demangles `name` with the first installed demangler that recognizes it, or returns it
+/// unchanged.
+pub fn demangle(name: &str, style: Style) -> String {
+    let installed = INSTALLED.read().expect("demangler lock");
+    let defaults;
+    let demanglers = match *installed {
+        Some(ref demanglers) => demanglers,
+        None => {
+            defaults = builtins();
+            &defaults
+        }
+    };
+    demanglers.iter().find_map(|demangler| demangler.demangle(name, style)).unwrap_or_else(|| name.to_string())
+}
+
+/// Parses a `--demangler` list: comma-separated names of built-in demanglers, or `none`.
+pub fn parse_list(list: &str) -> Result<Vec<Box<dyn Demangler>>, String> {
+    if list == "none" {
+        return Ok(Vec::new());
+    }
+    list.split(',')
+        .map(|name| {
+            builtins().into_iter().find(|demangler| demangler.name() == name).ok_or_else(|| {
+                let known: Vec<&str> = builtins().iter().map(|demangler| demangler.name()).collect();
+                format!("unknown demangler '{}', expected none or a list of {}", name, known.join(", "))
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn demangler_lists() {
+        let names = |list: &str| parse_list(list).map(|list| list.iter().map(|d| d.name()).collect::<Vec<_>>());
+        assert_eq!(names("rust"), Ok(vec!["rust"]));
+        assert_eq!(names("none"), Ok(vec![]));
+        assert!(names("rust,swift").is_err());
+        assert_eq!(Rust.demangle("_ZN3app4main17h0123456789abcdefE", Style::Full).as_deref(), Some("app::main"));
+        assert_eq!(Rust.demangle("?main@@YAHXZ", Style::Full), None);
+    }
+}
@@ -0,0 +1,76 @@
+//! `--exec-per-frame`: runs a user command for every resolved frame.
+
+use std::io::{self, Write};
+use std::process::Command;
+
+use crate::output::Resolution;
+
+/// Runs the command `template` once per frame of `results`, outermost frame first.
+///
+/// The template is split into arguments at whitespace outside of single or double quotes and
+/// run without a shell. In each argument `{addr}`, `{func}`, `{file}` and `{line}` are replaced
+/// with the frame's values, so names with spaces stay one argument. Commands that cannot be
+/// started or that fail are reported on stderr and do not stop the remaining frames.
+pub fn run_per_frame(template: &str, results: &[Resolution]) -> Result<(), String> {
+    let words = split(template)?;
+    if words.is_empty() {
+        return Err("--exec-per-frame needs a command".to_string());
+    }
+    for result in results {
+        for frame in &result.frames {
+            let args: Vec<String> = words
+                .iter()
+                .map(|word| {
+                    word.replace("{addr}", &format!("{:#x}", result.address))
+                        .replace("{func}", &frame.function)
+                        .replace("{file}", &frame.file)
+                        .replace("{line}", &frame.line.to_string())
+                })
+                .collect();
+            let status = Command::new(&args[0]).args(&args[1..]).status();
+            let failure = match status {
+                Ok(status) if status.success() => continue,
+                Ok(status) => format!("'{}' exited with {}", args[0], status),
+                Err(e) => format!("cannot run '{}': {}", args[0], e),
+            };
+            writeln!(&mut io::stderr(), "warning: {} for {:#x}", failure, result.address).expect("stderr write");
+        }
+    }
+    Ok(())
+}
+
+/// Splits a command line at whitespace, keeping quoted parts together without their quotes.
+pub fn split(command: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    for c in command.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(format!("unterminated quote in '{}'", command));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split;
+
+    #[test]
+    fn quoting() {
+        assert_eq!(split("code -g '{file}:{line}'").unwrap(), vec!["code", "-g", "{file}:{line}"]);
+        assert_eq!(split(r#"  echo "a b" '' c "#).unwrap(), vec!["echo", "a b", "", "c"]);
+        assert!(split("echo 'open").is_err());
+    }
+}
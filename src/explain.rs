@@ -0,0 +1,167 @@
+//! The `explain` subcommand: every step of resolving one address, with its intermediate values.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use pdb::{FallibleIterator, Rva, SymbolData, PDB};
+
+use crate::resolve::{self, Boundary};
+use crate::{collect_lines, load_publics, paths};
+
+/// Writes how the address `rva` of the PDB at `path` resolves, step by step.
+pub fn explain(out: &mut dyn Write, path: &Path, rva: u32, boundary: Boundary) -> Result<(), String> {
+    let mut steps = Vec::new();
+    let result = explain_steps(path, rva, boundary, &mut steps);
+    for step in &steps {
+        writeln!(out, "{}", step).map_err(|e| e.to_string())?;
+    }
+    result.map_err(|e| e.to_string())
+}
+
+/// Collects the narrative in `steps`, keeping the steps before an error.
+fn explain_steps(path: &Path, rva: u32, boundary: Boundary, steps: &mut Vec<String>) -> pdb::Result<()> {
+    let mut pdb = PDB::open(fs::File::open(paths::long_path(path))?)?;
+    let address_map = pdb.address_map()?;
+    let string_table = pdb.string_table()?;
+    let dbi = pdb.debug_information()?;
+    let ipi = pdb.id_information()?;
+    let target = u64::from(rva);
+
+    steps.push(format!("address: rva {:#x}", rva));
+    match Rva(rva).to_internal_rva(&address_map) {
+        Some(internal) if internal.0 == rva => steps.push("omap: none applied, the PDB describes the image as linked".to_string()),
+        Some(internal) => steps.push(format!("omap: rva {:#x} was {:#x} before the image was rewritten", rva, internal.0)),
+        None => {
+            steps.push("omap: the address was added after linking and has no debug information".to_string());
+            return Ok(());
+        }
+    }
+    match Rva(rva).to_internal_offset(&address_map) {
+        Some(offset) => steps.push(format!("section offset: section {} + {:#x}", offset.section, offset.offset)),
+        None => {
+            steps.push("section offset: the address is outside every section".to_string());
+            return Ok(());
+        }
+    }
+
+    let modules: Vec<String> = dbi.modules()?.map(|module| Ok(module.module_name().into_owned())).collect()?;
+    let mut contributing = None;
+    if let Ok(mut contributions) = dbi.section_contributions() {
+        while let Ok(Some(contribution)) = contributions.next() {
+            let start = match contribution.offset.to_rva(&address_map) {
+                Some(start) => start.0,
+                None => continue,
+            };
+            if resolve::in_range(start.into(), contribution.size.into(), target) {
+                let module = usize::from(contribution.module);
+                steps.push(format!(
+                    "section contribution: {:#x}-{:#x} from module {} ({})",
+                    start,
+                    u64::from(start) + u64::from(contribution.size),
+                    module,
+                    modules.get(module).map_or("?", String::as_str)
+                ));
+                contributing = Some(module);
+                break;
+            }
+        }
+    }
+    if contributing.is_none() {
+        steps.push("section contribution: none found, searching all modules".to_string());
+    }
+
+    let mut found = false;
+    let mut module_list = dbi.modules()?;
+    let mut index = 0;
+    while let Some(module) = module_list.next()? {
+        index += 1;
+        if contributing.is_some_and(|contributing| contributing != index - 1) {
+            continue;
+        }
+        let info = match pdb.module_info(&module)? {
+            Some(info) => info,
+            None => continue,
+        };
+        let program = info.line_program()?;
+        let inlinees: BTreeMap<_, _> = info.inlinees()?.map(|i| Ok((i.index(), i))).collect()?;
+        let mut parent = None;
+        let mut symbols = info.symbols()?;
+        while let Some(symbol) = symbols.next()? {
+            match symbol.parse() {
+                Ok(SymbolData::Procedure(proc)) => {
+                    let start = match proc.offset.to_rva(&address_map) {
+                        Some(start) => start.0,
+                        None => continue,
+                    };
+                    if !resolve::in_range(start.into(), proc.len.into(), target) {
+                        parent = None;
+                        continue;
+                    }
+                    found = true;
+                    parent = Some(proc.offset);
+                    steps.push(format!(
+                        "procedure: {} at {:#x}-{:#x} in module {}, offset +{:#x}",
+                        proc.name,
+                        start,
+                        u64::from(start) + u64::from(proc.len),
+                        module.module_name(),
+                        rva - start
+                    ));
+                    let lines = collect_lines(program.lines_at_offset(proc.offset), &program, &address_map, &string_table, usize::MAX)?;
+                    let starts: Vec<u64> = lines.iter().map(|line| line.address).collect();
+                    let chosen = resolve::select_line(&starts, target, boundary);
+                    steps.push(format!("line records: {} for the procedure, boundary rule '{}'", lines.len(), format!("{:?}", boundary).to_lowercase()));
+                    for (i, line) in lines.iter().enumerate() {
+                        let end = line.size.map_or_else(|| "?".to_string(), |size| format!("{:#x}", line.address + size));
+                        steps.push(format!(
+                            "  {} {:#x}-{} {}:{}",
+                            if Some(i) == chosen { "->" } else { "  " },
+                            line.address,
+                            end,
+                            line.file,
+                            line.line
+                        ));
+                    }
+                    if chosen.is_none() {
+                        steps.push("  no line record starts at or before the address".to_string());
+                    }
+                }
+                Ok(SymbolData::InlineSite(site)) => {
+                    let (parent_offset, inlinee) = match (parent, inlinees.get(&site.inlinee)) {
+                        (Some(parent), Some(inlinee)) => (parent, inlinee),
+                        _ => continue,
+                    };
+                    let lines = collect_lines(inlinee.lines(parent_offset, &site), &program, &address_map, &string_table, usize::MAX)?;
+                    let hit = lines.iter().find(|line| resolve::in_range(line.address, line.size.unwrap_or(0), target));
+                    if let Some(line) = hit {
+                        let name = ipi
+                            .iter()
+                            .iterator()
+                            .flatten()
+                            .find(|id| id.index() == site.inlinee)
+                            .and_then(|id| match id.parse() {
+                                Ok(pdb::IdData::Function(f)) => Some(f.name.to_string().into_owned()),
+                                Ok(pdb::IdData::MemberFunction(f)) => Some(f.name.to_string().into_owned()),
+                                _ => None,
+                            })
+                            .unwrap_or_else(|| "unknown_inline_function".to_string());
+                        steps.push(format!("inline site: {} at {}:{} ({:#x})", name, line.file, line.line, line.address));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if !found {
+        steps.push("procedure: none covers the address, so it resolves through the public symbols".to_string());
+        let publics = load_publics(&mut pdb, &address_map, usize::MAX)?;
+        match publics[..publics.partition_point(|&(start, _)| start <= rva)].last() {
+            Some((start, name)) => steps.push(format!("public symbol: {} at {:#x}, offset +{:#x}", name, start, rva - start)),
+            None => steps.push("public symbol: none precedes the address".to_string()),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,249 @@
+//! `--export-sqlite`: the functions, line records, inlinees, files and public symbols of a whole
+//! PDB as a SQLite database, with the tables documented in `schema/symbols.sql`.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use pdb::{FallibleIterator, SymbolData, PDB};
+
+use crate::address::RvaRange;
+use crate::columns;
+use crate::paths;
+use crate::repo::SourceMap;
+use crate::resolve;
+use crate::sqlite::{self, Table, Value};
+use crate::system;
+use crate::{annotation, collect_lines, compile_language, language_name, load_publics, read_module_lines, trampoline};
+
+/// The `CREATE TABLE` statements of the database, with comments documenting the columns.
+pub const SCHEMA: &str = include_str!("../schema/symbols.sql");
+
+/// Version of the tables, stored as the database's `user_version`.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// The rows of all tables, each without its rowid.
+#[derive(Default)]
+struct Rows {
+    files: Vec<Vec<Value>>,
+    file_ids: HashMap<String, i64>,
+    functions: Vec<Vec<Value>>,
+    lines: Vec<Vec<Value>>,
+    inlinees: Vec<Vec<Value>>,
+    publics: Vec<Vec<Value>>,
+    annotations: Vec<Vec<Value>>,
+    trampolines: Vec<Vec<Value>>,
+}
+
+impl Rows {
+    fn file_id(&mut self, path: &str) -> i64 {
+        if let Some(&id) = self.file_ids.get(path) {
+            return id;
+        }
+        self.files.push(vec![Value::Null, Value::Text(path.to_string()), Value::Null, Value::Null, Value::Null]);
+        let id = self.files.len() as i64;
+        self.file_ids.insert(path.to_string(), id);
+        id
+    }
+}
+
+/// Writes the symbols of the PDB at `path` to a new SQLite database at `out`.
+///
+/// With a `range`, only the modules contributing code or data to it and the public symbols in it
+/// are exported, and the line programs of the other modules are never read.
+pub fn export_sqlite(path: &Path, out: &Path, range: Option<RvaRange>) -> Result<(), String> {
+    let rows = read_pdb(path, range).map_err(|e| format!("error reading '{}': {}", path.display(), e))?;
+
+    let mut tables = Vec::new();
+    let mut contents =
+        vec![rows.files, rows.functions, rows.lines, rows.inlinees, rows.publics, rows.annotations, rows.trampolines]
+            .into_iter();
+    let statements: Vec<&str> = SCHEMA.lines().filter(|line| !line.trim_start().starts_with("--")).collect();
+    for statement in statements.join("\n").split(';') {
+        let sql = statement.trim().to_string();
+        let name = match sql.strip_prefix("CREATE TABLE ") {
+            Some(rest) => rest.split_whitespace().next().unwrap_or_default().to_string(),
+            None => continue,
+        };
+        let rows = contents.next().expect("a table for every statement");
+        tables.push(Table { name, sql, rows: (1..).zip(rows).collect() });
+    }
+
+    let data = sqlite::write(&tables, SCHEMA_VERSION)?;
+    fs::write(paths::long_path(out), data).map_err(|e| format!("cannot write '{}': {}", out.display(), e))
+}
+
+fn read_pdb(path: &Path, range: Option<RvaRange>) -> pdb::Result<Rows> {
+    let mut pdb = PDB::open(fs::File::open(paths::long_path(path))?)?;
+    let address_map = pdb.address_map()?;
+    let string_table = pdb.string_table()?;
+    let dbi = pdb.debug_information()?;
+    let ipi = pdb.id_information()?;
+    let module_records = columns::module_records(&mut pdb);
+
+    let mut inline_names = HashMap::new();
+    let mut ids = ipi.iter();
+    while let Some(id) = ids.next()? {
+        match id.parse() {
+            Ok(pdb::IdData::Function(function)) => {
+                inline_names.insert(id.index(), function.name.to_string().into_owned());
+            }
+            Ok(pdb::IdData::MemberFunction(function)) => {
+                inline_names.insert(id.index(), function.name.to_string().into_owned());
+            }
+            _ => {}
+        }
+    }
+
+    // The modules with section contributions in the range, or all if there is no range or the
+    // contributions cannot be read.
+    let wanted = range.and_then(|range| {
+        let mut wanted = HashSet::new();
+        let mut contributions = dbi.section_contributions().ok()?;
+        while let Some(contribution) = contributions.next().ok()? {
+            if contribution.offset.to_rva(&address_map).is_some_and(|start| range.overlaps(start.0, contribution.size)) {
+                wanted.insert(usize::from(contribution.module));
+            }
+        }
+        Some(wanted)
+    });
+
+    let mut rows = Rows::default();
+    let mut modules = dbi.modules()?.enumerate();
+    while let Some((index, module)) = modules.next()? {
+        if wanted.as_ref().is_some_and(|wanted| !wanted.contains(&index)) {
+            continue;
+        }
+        let info = match pdb.module_info(&module)? {
+            Some(info) => info,
+            None => continue,
+        };
+        let inlinees: BTreeMap<_, _> = info.inlinees()?.map(|i| Ok((i.index(), i))).collect()?;
+        let program = info.line_program()?;
+        let module_lines =
+            read_module_lines(&mut pdb, module_records.get(index), &program, &address_map, &string_table, usize::MAX)?;
+        let system = system::is_runtime_compiland(&module.object_file_name());
+
+        // The procedures and inline sites enclosing the current symbol, with their scope depth.
+        let mut procedures = Vec::new();
+        let mut inline_depths: Vec<usize> = Vec::new();
+        let mut depth: usize = 0;
+        let mut inc_next = false;
+        let mut language = None;
+        let mut symbols = info.symbols()?;
+        while let Some(symbol) = symbols.next()? {
+            if inc_next {
+                depth += 1;
+            }
+            inc_next = symbol.starts_scope();
+            if symbol.ends_scope() {
+                depth = depth.saturating_sub(1);
+                if procedures.last().is_some_and(|&(d, _, _)| d >= depth) {
+                    procedures.pop();
+                }
+                if inline_depths.last().is_some_and(|&d| d >= depth) {
+                    inline_depths.pop();
+                }
+            }
+            if let Some(code) = compile_language(&symbol) {
+                language = language_name(code);
+            }
+            if let Some(annotation) = annotation(&symbol, &address_map) {
+                let strings = annotation.strings.join("\n");
+                rows.annotations.push(vec![Value::Integer(annotation.address.into()), Value::Text(strings)]);
+            }
+            if let Some(trampoline) = trampoline(&symbol, &address_map) {
+                rows.trampolines.push(vec![
+                    Value::Integer(trampoline.start.into()),
+                    Value::Integer(trampoline.size.into()),
+                    Value::Integer(trampoline.target.into()),
+                ]);
+            }
+
+            match symbol.parse() {
+                Ok(SymbolData::Procedure(proc)) => {
+                    let start = match proc.offset.to_rva(&address_map) {
+                        Some(start) => u64::from(start.0),
+                        None => continue,
+                    };
+                    let name = proc.name.to_string();
+                    let name = resolve::generated_name(&name).unwrap_or_else(|| name.into_owned());
+                    rows.functions.push(vec![
+                        Value::Null,
+                        Value::Text(name),
+                        Value::Integer(start as i64),
+                        Value::Integer(i64::from(proc.len)),
+                        language.map_or(Value::Null, |language| Value::Text(language.to_string())),
+                        Value::Integer(i64::from(proc.dbg_start_offset)),
+                        Value::Integer(i64::from(system)),
+                    ]);
+                    let id = rows.functions.len() as i64;
+                    procedures.push((depth, proc.offset, id));
+
+                    let end = start + u64::from(proc.len);
+                    let lines = &module_lines[module_lines.partition_point(|line| line.address < start)
+                        ..module_lines.partition_point(|line| line.address < end)];
+                    for line in lines {
+                        let file_id = rows.file_id(&line.file);
+                        rows.lines.push(vec![
+                            Value::Integer(id),
+                            Value::Integer(line.address as i64),
+                            Value::Integer(line.size.unwrap_or(0) as i64),
+                            Value::Integer(file_id),
+                            Value::Integer(line.line as i64),
+                            line.column.map_or(Value::Null, |column| Value::Integer(column.into())),
+                        ]);
+                    }
+                }
+                Ok(SymbolData::InlineSite(site)) => {
+                    let (parent_offset, id) = match procedures.last() {
+                        Some(&(_, offset, id)) => (offset, id),
+                        None => continue,
+                    };
+                    inline_depths.push(depth);
+                    let inlinee = match inlinees.get(&site.inlinee) {
+                        Some(inlinee) => inlinee,
+                        None => continue,
+                    };
+                    let name = inline_names.get(&site.inlinee).cloned().unwrap_or_else(|| "unknown_inline_function".to_string());
+                    let lines = collect_lines(inlinee.lines(parent_offset, &site), &program, &address_map, &string_table, usize::MAX)?;
+                    for line in lines {
+                        let file_id = rows.file_id(&line.file);
+                        rows.inlinees.push(vec![
+                            Value::Integer(id),
+                            Value::Integer(inline_depths.len() as i64),
+                            Value::Text(name.clone()),
+                            Value::Integer(line.address as i64),
+                            Value::Integer(line.size.unwrap_or(0) as i64),
+                            Value::Integer(file_id),
+                            Value::Integer(line.line as i64),
+                            line.column.map_or(Value::Null, |column| Value::Integer(column.into())),
+                        ]);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    for (address, name) in load_publics(&mut pdb, &address_map, usize::MAX)? {
+        if range.is_some_and(|range| !range.overlaps(address, 1)) {
+            continue;
+        }
+        rows.publics.push(vec![Value::Integer(i64::from(address)), Value::Text(name)]);
+    }
+
+    if let Some(sources) = SourceMap::load(&mut pdb) {
+        for file in &mut rows.files {
+            let repo = file[1].as_text().filter(|path| !path.is_empty()).and_then(|path| sources.lookup(path));
+            if let Some(repo) = repo {
+                file[2..].clone_from_slice(&[
+                    Value::Text(repo.repo_url),
+                    Value::Text(repo.revision),
+                    Value::Text(repo.repo_relative_path),
+                ]);
+            }
+        }
+    }
+    Ok(rows)
+}
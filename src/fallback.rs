@@ -0,0 +1,150 @@
+//! `--fallback-symbolizer`: forwarding the addresses the symbol file cannot resolve to another
+//! symbolizer, so this tool can stay the single entry point while some modules still need
+//! llvm-symbolizer or a dbghelp-based one.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use crate::exec;
+use crate::output::{Frame, Resolution, Source};
+
+/// A running symbolizer speaking llvm-symbolizer's protocol on stdin and stdout, and the answers
+/// it gave so far.
+pub struct Fallback {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    answers: HashMap<u32, Vec<Frame>>,
+}
+
+impl Fallback {
+    /// Starts `command`, split into arguments as `--exec-per-frame` does and run without a shell.
+    pub fn spawn(command: &str) -> Result<Fallback, String> {
+        let words = exec::split(command)?;
+        if words.is_empty() {
+            return Err("--fallback-symbolizer needs a command".to_string());
+        }
+        let mut child = Command::new(&words[0])
+            .args(&words[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("cannot run '{}': {}", words[0], e))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        Ok(Fallback { child, stdin, stdout, answers: HashMap::new() })
+    }
+
+    /// Gives the results without any frame the frames of the fallback symbolizer. Each distinct
+    /// address is forwarded once; repeated ones are answered from the earlier reply.
+    pub fn fill(&mut self, results: &mut [Resolution]) -> Result<(), String> {
+        for result in results.iter_mut().filter(|result| result.frames.is_empty()) {
+            if !self.answers.contains_key(&result.address) {
+                let frames = self.ask(result.address)?;
+                self.answers.insert(result.address, frames);
+            }
+            result.frames = self.answers[&result.address].clone();
+        }
+        Ok(())
+    }
+
+    /// Sends the image-relative `address` and reads the reply: a function line and a
+    /// `file:line:column` line per frame, innermost first, ended by an empty line.
+    fn ask(&mut self, address: u32) -> Result<Vec<Frame>, String> {
+        let broken = |e: std::io::Error| format!("fallback symbolizer: {}", e);
+        writeln!(self.stdin, "{:#x}", address).and_then(|()| self.stdin.flush()).map_err(broken)?;
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            if self.stdout.read_line(&mut line).map_err(broken)? == 0 {
+                return Err(format!("fallback symbolizer exited before answering {:#x}", address));
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.is_empty() {
+                break;
+            }
+            lines.push(line.to_string());
+        }
+        Ok(parse_reply(&lines))
+    }
+}
+
+impl Drop for Fallback {
+    fn drop(&mut self) {
+        // All questions are answered by now, so the symbolizer is not left waiting for more.
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Turns the lines of one reply into frames, outermost first. `??` stands for an unknown
+/// function or file, and a reply of only unknowns for no frames at all.
+fn parse_reply(lines: &[String]) -> Vec<Frame> {
+    let mut frames: Vec<Frame> = lines
+        .chunks(2)
+        .filter_map(|pair| {
+            let function = pair[0].as_str();
+            let (file, line) = split_location(pair.get(1).map_or("??", String::as_str));
+            let file = if file == "??" { "" } else { file };
+            if function == "??" && file.is_empty() {
+                return None;
+            }
+            Some(Frame {
+                function: if function == "??" { String::new() } else { function.to_string() },
+                file: file.to_string(),
+                line,
+                column: None,
+                function_offset: None,
+                line_offset: None,
+                inline: false,
+                source: Source::Fallback,
+                repo: None,
+                aliases: Vec::new(),
+                signature: None,
+                language: None,
+                system: false,
+                candidates: Vec::new(),
+                prologue: false,
+            })
+        })
+        .collect();
+    frames.reverse();
+    for frame in frames.iter_mut().skip(1) {
+        frame.inline = true;
+    }
+    frames
+}
+
+/// Splits `file:line:column` or `file:line` into the file and line. File names have colons of
+/// their own, so the numbers are taken off the end.
+fn split_location(location: &str) -> (&str, u64) {
+    let mut file = location;
+    let mut numbers = Vec::new();
+    while numbers.len() < 2 {
+        match file.rsplit_once(':') {
+            Some((rest, number)) if number.parse::<u64>().is_ok() => {
+                numbers.push(number);
+                file = rest;
+            }
+            _ => break,
+        }
+    }
+    (file, numbers.last().map_or(0, |line| line.parse().expect("checked above")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_reply;
+
+    #[test]
+    fn replies() {
+        let lines: Vec<String> = ["inner", r"C:\src\a.h:12:5", "outer", r"C:\src\a.cpp:40:0"].iter().map(|s| s.to_string()).collect();
+        let frames = parse_reply(&lines);
+        assert_eq!(frames.len(), 2);
+        assert_eq!((frames[0].function.as_str(), frames[0].file.as_str(), frames[0].line, frames[0].inline), ("outer", r"C:\src\a.cpp", 40, false));
+        assert_eq!((frames[1].function.as_str(), frames[1].file.as_str(), frames[1].line, frames[1].inline), ("inner", r"C:\src\a.h", 12, true));
+        assert!(parse_reply(&["??".to_string(), "??:0:0".to_string()]).is_empty());
+        assert_eq!(parse_reply(&["f".to_string(), "a.c:7".to_string()])[0].line, 7);
+    }
+}
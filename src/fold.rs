@@ -0,0 +1,123 @@
+//! `--fold-rules`: folding the frames of a stack that match user rules, such as all of `std::`
+//! or the CRT startup, into one line per run so reports highlight the user's own code.
+
+use crate::output::{Frame, Resolution};
+
+/// Folds the functions matching `pattern` into `group`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    pub group: String,
+    /// A function name where `*` matches any run of characters, as in `std::*`.
+    pub pattern: String,
+}
+
+/// Parses a rules file with one `GROUP = PATTERN` rule per line. Empty lines and lines starting
+/// with `#` are skipped. The first matching rule wins.
+pub fn parse_rules(text: &str) -> Result<Vec<Rule>, String> {
+    let mut rules = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.split_once('=') {
+            Some((group, pattern)) if !group.trim().is_empty() && !pattern.trim().is_empty() => {
+                rules.push(Rule { group: group.trim().to_string(), pattern: pattern.trim().to_string() })
+            }
+            _ => return Err(format!("line {}: invalid rule '{}', expected GROUP = PATTERN", number + 1, line)),
+        }
+    }
+    Ok(rules)
+}
+
+/// Returns the group of the first rule matching `function`.
+pub fn group<'r>(rules: &'r [Rule], function: &str) -> Option<&'r str> {
+    rules.iter().find(|rule| matches(&rule.pattern, function)).map(|rule| rule.group.as_str())
+}
+
+/// Whether `name` matches `pattern` as a whole, `*` matching any run of characters.
+fn matches(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let mut rest = match name.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let parts: Vec<&str> = parts.collect();
+    let (last, middle) = match parts.split_last() {
+        Some(split) => split,
+        // No `*`: the whole name must be the pattern.
+        None => return rest.is_empty(),
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Replaces each run of consecutive results whose function falls into the same group with one
+/// result at the run's first address, named `[GROUP]` and the number of frames folded.
+///
+/// A result's function is that of its outermost frame, the one the address lies in physically;
+/// code inlined into a user function from a folded library does not fold the user function.
+pub fn fold(results: &[Resolution], rules: &[Rule]) -> Vec<Resolution> {
+    let groups: Vec<Option<&str>> =
+        results.iter().map(|result| result.frames.first().and_then(|frame| group(rules, &frame.function))).collect();
+    let mut folded = Vec::new();
+    let mut i = 0;
+    while i < results.len() {
+        let run = groups[i..].iter().take_while(|&&g| g.is_some() && g == groups[i]).count();
+        match groups[i] {
+            Some(name) => {
+                let frame = &results[i].frames[0];
+                let plural = if run == 1 { "" } else { "s" };
+                let mut result = Resolution::new(results[i].address, Vec::new());
+                result.module = results[i].module.clone();
+                result.frames.push(Frame {
+                    function: format!("[{}] ({} frame{})", name, run, plural),
+                    file: String::new(),
+                    line: 0,
+                    column: None,
+                    function_offset: None,
+                    line_offset: None,
+                    inline: false,
+                    source: frame.source,
+                    repo: None,
+                    aliases: Vec::new(),
+                    signature: None,
+                    language: frame.language,
+                    system: frame.system,
+                    candidates: Vec::new(),
+                    prologue: false,
+                });
+                folded.push(result);
+                i += run;
+            }
+            None => {
+                folded.push(results[i].clone());
+                i += 1;
+            }
+        }
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{matches, parse_rules};
+
+    #[test]
+    fn rule_patterns() {
+        let rules = parse_rules("# library code\nstd = std::*\n\ncrt = *_CRT_*\nexit = exit\n").unwrap();
+        assert_eq!(rules.len(), 3);
+        assert!(matches(&rules[0].pattern, "std::vector<int>::push_back"));
+        assert!(!matches(&rules[0].pattern, "mystd::sort"));
+        assert!(matches(&rules[1].pattern, "__scrt_common_main_CRT_startup"));
+        assert!(matches(&rules[2].pattern, "exit"));
+        assert!(!matches(&rules[2].pattern, "exit2"));
+        assert!(parse_rules("std::*").is_err());
+    }
+}
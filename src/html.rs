@@ -0,0 +1,118 @@
+//! Static HTML report of the source lines hit by the queried addresses.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::output::Resolution;
+use crate::paths;
+
+/// The addresses and functions that resolved to one source line.
+type Hits<'a> = BTreeMap<u64, Vec<(u32, &'a str)>>;
+
+/// Writes `index.html` and one page per source file into `dir`, creating it if needed.
+///
+/// Each file page lists the file's source with the hit lines highlighted and annotated with the
+/// addresses that resolved to them. Sources that cannot be read locally are shown as the list of
+/// hit lines only.
+pub fn write_report(dir: &Path, results: &[Resolution]) -> io::Result<()> {
+    let mut files: BTreeMap<&str, Hits> = BTreeMap::new();
+    for result in results {
+        for frame in result.frames.iter().filter(|frame| !frame.file.is_empty()) {
+            files
+                .entry(&frame.file)
+                .or_default()
+                .entry(frame.line)
+                .or_default()
+                .push((result.address, &frame.function));
+        }
+    }
+
+    let dir = &paths::long_path(dir);
+    fs::create_dir_all(dir)?;
+    let mut index = io::BufWriter::new(fs::File::create(dir.join("index.html"))?);
+    write_header(&mut index, "Address report")?;
+    writeln!(index, "<table>\n<tr><th>File</th><th>Lines</th><th>Addresses</th></tr>")?;
+    for (number, (file, lines)) in files.iter().enumerate() {
+        let page = format!("file-{}.html", number);
+        let addresses: usize = lines.values().map(Vec::len).sum();
+        writeln!(
+            index,
+            "<tr><td><a href=\"{}\">{}</a></td><td>{}</td><td>{}</td></tr>",
+            page,
+            escape(file),
+            lines.len(),
+            addresses
+        )?;
+        write_file_page(&dir.join(page), file, lines)?;
+    }
+    writeln!(index, "</table>\n</body>\n</html>")?;
+    index.flush()
+}
+
+fn write_file_page(path: &Path, file: &str, lines: &Hits) -> io::Result<()> {
+    let mut out = io::BufWriter::new(fs::File::create(path)?);
+    write_header(&mut out, file)?;
+    writeln!(out, "<p><a href=\"index.html\">index</a></p>\n<table>")?;
+    match fs::read_to_string(paths::long_path(Path::new(file))) {
+        Ok(source) => {
+            for (number, text) in (1u64..).zip(source.lines()) {
+                write_line(&mut out, number, text, lines.get(&number))?;
+            }
+        }
+        Err(_) => {
+            writeln!(out, "<caption>source not available</caption>")?;
+            for (&number, hits) in lines {
+                write_line(&mut out, number, "", Some(hits))?;
+            }
+        }
+    }
+    writeln!(out, "</table>\n</body>\n</html>")?;
+    out.flush()
+}
+
+fn write_line(out: &mut dyn Write, number: u64, text: &str, hits: Option<&Vec<(u32, &str)>>) -> io::Result<()> {
+    let (class, notes) = match hits {
+        Some(hits) => {
+            let notes: Vec<String> =
+                hits.iter().map(|&(address, function)| format!("{:#x} {}", address, escape(function))).collect();
+            (" class=\"hit\"", notes.join("<br>"))
+        }
+        None => ("", String::new()),
+    };
+    writeln!(
+        out,
+        "<tr{} id=\"L{}\"><td class=\"n\">{}</td><td><pre>{}</pre></td><td>{}</td></tr>",
+        class,
+        number,
+        number,
+        escape(text),
+        notes
+    )
+}
+
+fn write_header(out: &mut dyn Write, title: &str) -> io::Result<()> {
+    writeln!(out, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>{}</title>", escape(title))?;
+    writeln!(
+        out,
+        "<style>pre {{ margin: 0; }} td.n {{ text-align: right; color: #888; }} tr.hit {{ background: #fdd; }}</style>"
+    )?;
+    writeln!(out, "</head>\n<body>\n<h1>{}</h1>", escape(title))
+}
+
+/// Escapes the characters with a special meaning in HTML text and attributes.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,225 @@
+//! Local variables in scope at the queried addresses, with where each one lives there.
+//!
+//! Optimized code describes a variable's location with `S_DEFRANGE_*` records following its
+//! `S_LOCAL` record, each valid for an address range minus gaps. Unoptimized code uses
+//! `S_REGREL32` and `S_REGISTER`, valid throughout the enclosing scope.
+
+use std::convert::TryInto;
+
+use pdb::{AddressMap, PdbInternalSectionOffset, Symbol, SymbolData};
+
+use crate::output::Resolution;
+use crate::resolve;
+
+/// A variable in scope at a queried address.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Local {
+    pub name: String,
+    /// Where the value lives at the address, e.g. `rcx`, `[rsp+0x20]` or `optimized out`.
+    pub location: String,
+}
+
+const S_DEFRANGE_REGISTER: u16 = 0x1141;
+const S_DEFRANGE_FRAMEPOINTER_REL: u16 = 0x1142;
+const S_DEFRANGE_SUBFIELD_REGISTER: u16 = 0x1143;
+const S_DEFRANGE_FRAMEPOINTER_REL_FULL_SCOPE: u16 = 0x1144;
+const S_DEFRANGE_REGISTER_REL: u16 = 0x1145;
+
+/// Walks the symbols of one module and records the locals in scope at each queried address.
+pub struct Collector<'a, 's> {
+    address_map: &'a AddressMap<'s>,
+    queries: &'a [(u32, usize)],
+    /// For each open scope, the queried addresses (as result indices) inside it.
+    scopes: Vec<Vec<usize>>,
+    /// The `S_LOCAL` whose location records are being read, with the addresses it may cover
+    /// that no location record has covered yet.
+    pending: Option<(String, Vec<usize>)>,
+}
+
+impl<'a, 's> Collector<'a, 's> {
+    pub fn new(address_map: &'a AddressMap<'s>, queries: &'a [(u32, usize)]) -> Self {
+        Collector { address_map, queries, scopes: Vec::new(), pending: None }
+    }
+
+    /// Processes the next symbol of the module.
+    pub fn visit(&mut self, symbol: &Symbol, results: &mut [Resolution]) {
+        let kind = symbol.raw_kind();
+        if !(S_DEFRANGE_REGISTER..=S_DEFRANGE_REGISTER_REL).contains(&kind) {
+            self.finish_pending(results);
+        }
+
+        if symbol.ends_scope() {
+            self.scopes.pop();
+            return;
+        }
+        if symbol.starts_scope() {
+            let inside = match symbol.parse() {
+                Ok(SymbolData::Procedure(proc)) => self.inside(proc.offset, proc.len),
+                Ok(SymbolData::Block(block)) => self.inside(block.offset, block.len),
+                // Inlined code shares the frame of the function it was inlined into.
+                Ok(SymbolData::InlineSite(_)) => self.scopes.last().cloned().unwrap_or_default(),
+                _ => Vec::new(),
+            };
+            self.scopes.push(inside);
+            return;
+        }
+
+        let scope = match self.scopes.last() {
+            Some(scope) if !scope.is_empty() => scope,
+            _ => return,
+        };
+        match symbol.parse() {
+            Ok(SymbolData::Local(local)) => {
+                self.pending = Some((local.name.to_string().into_owned(), scope.clone()));
+            }
+            Ok(SymbolData::RegisterRelative(local)) => {
+                let location = format!("[{}{}]", register_name(local.register.0), signed_hex(local.offset));
+                add(results, scope, &local.name.to_string(), &location);
+            }
+            Ok(SymbolData::RegisterVariable(local)) => {
+                add(results, scope, &local.name.to_string(), &register_name(local.register.0));
+            }
+            _ => {
+                if let Some(range) = parse_defrange(symbol.raw_bytes()) {
+                    self.apply(range, results);
+                }
+            }
+        }
+    }
+
+    /// Records the location from a `S_DEFRANGE_*` record for the pending local.
+    fn apply(&mut self, range: DefRange, results: &mut [Resolution]) {
+        let address_map = self.address_map;
+        let (name, uncovered) = match self.pending {
+            Some(ref mut pending) => pending,
+            None => return,
+        };
+        let covers = |address: u32| match range.range {
+            None => true,
+            Some(Range { section, offset, len, ref gaps }) => {
+                let start = PdbInternalSectionOffset { offset, section }.to_rva(address_map);
+                start.is_some_and(|start| {
+                    let start = u64::from(start.0);
+                    let address = u64::from(address);
+                    resolve::in_range(start, len.into(), address)
+                        && !gaps.iter().any(|&(gap, gap_len)| resolve::in_range(start + u64::from(gap), gap_len.into(), address))
+                })
+            }
+        };
+        let (hit, rest): (Vec<usize>, Vec<usize>) =
+            uncovered.iter().partition(|&&index| covers(results[index].address));
+        add(results, &hit, name, &range.location);
+        *uncovered = rest;
+    }
+
+    fn finish_pending(&mut self, results: &mut [Resolution]) {
+        if let Some((name, uncovered)) = self.pending.take() {
+            add(results, &uncovered, &name, "optimized out");
+        }
+    }
+
+    fn inside(&self, offset: PdbInternalSectionOffset, len: u32) -> Vec<usize> {
+        match offset.to_rva(self.address_map) {
+            Some(start) => resolve::in_sorted_range(self.queries, start.0.into(), len.into()).collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+fn add(results: &mut [Resolution], indices: &[usize], name: &str, location: &str) {
+    for &index in indices {
+        results[index].locals.push(Local { name: name.to_string(), location: location.to_string() });
+    }
+}
+
+/// A decoded `S_DEFRANGE_*` record.
+struct DefRange {
+    location: String,
+    /// The addresses the location is valid for, or `None` for the whole enclosing scope.
+    range: Option<Range>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Range {
+    section: u16,
+    offset: u32,
+    len: u16,
+    /// Holes in the range as (offset from the start, length).
+    gaps: Vec<(u16, u16)>,
+}
+
+fn parse_defrange(data: &[u8]) -> Option<DefRange> {
+    let u16_at = |offset: usize| Some(u16::from_le_bytes(data.get(offset..offset + 2)?.try_into().ok()?));
+    let i32_at = |offset: usize| Some(i32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?));
+    let u32_at = |offset: usize| Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?));
+    let range_at = |offset: usize| {
+        let gaps = data
+            .get(offset + 8..)?
+            .chunks_exact(4)
+            .map(|gap| (u16::from_le_bytes([gap[0], gap[1]]), u16::from_le_bytes([gap[2], gap[3]])))
+            .collect();
+        Some(Some(Range { section: u16_at(offset + 4)?, offset: u32_at(offset)?, len: u16_at(offset + 6)?, gaps }))
+    };
+
+    // Fields start after the two byte record kind.
+    let (location, range) = match u16_at(0)? {
+        S_DEFRANGE_REGISTER => (register_name(u16_at(2)?), range_at(6)?),
+        S_DEFRANGE_FRAMEPOINTER_REL => (format!("[frame{}]", signed_hex(i32_at(2)?)), range_at(6)?),
+        S_DEFRANGE_SUBFIELD_REGISTER => {
+            let parent_offset = u32_at(6)? & 0xfff;
+            (format!("{} (at offset {:#x})", register_name(u16_at(2)?), parent_offset), range_at(10)?)
+        }
+        S_DEFRANGE_FRAMEPOINTER_REL_FULL_SCOPE => (format!("[frame{}]", signed_hex(i32_at(2)?)), None),
+        S_DEFRANGE_REGISTER_REL => {
+            (format!("[{}{}]", register_name(u16_at(2)?), signed_hex(i32_at(6)?)), range_at(10)?)
+        }
+        _ => return None,
+    };
+    Some(DefRange { location, range })
+}
+
+/// Formats a register offset as `+0x10` or `-0x10`.
+fn signed_hex(offset: i32) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    format!("{}{:#x}", sign, offset.unsigned_abs())
+}
+
+/// Names a CodeView register number of the x86 and x64 register sets.
+fn register_name(register: u16) -> String {
+    const X86: [&str; 8] = ["eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi"];
+    const X64: [&str; 16] = [
+        "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15",
+    ];
+    match register {
+        17..=24 => X86[usize::from(register - 17)].to_string(),
+        154..=169 => format!("xmm{}", register - 154),
+        328..=343 => X64[usize::from(register - 328)].to_string(),
+        // r8d-r15d
+        360..=367 => format!("r{}d", register - 352),
+        _ => format!("reg{}", register),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_defrange, register_name, Range};
+
+    #[test]
+    fn register_relative_range_with_gap() {
+        // S_DEFRANGE_REGISTER_REL: rsp, no parent, -0x18, range 0x10 bytes at 1:0x20, gap at +4.
+        let mut data = vec![0x45, 0x11, 0x4f, 0x01, 0, 0];
+        data.extend_from_slice(&(-0x18i32).to_le_bytes());
+        data.extend_from_slice(&[0x20, 0, 0, 0, 1, 0, 0x10, 0, 4, 0, 2, 0]);
+        let range = parse_defrange(&data).unwrap();
+        assert_eq!(range.location, "[rsp-0x18]");
+        assert_eq!(range.range, Some(Range { section: 1, offset: 0x20, len: 0x10, gaps: vec![(4, 2)] }));
+    }
+
+    #[test]
+    fn truncated_records_are_ignored() {
+        assert!(parse_defrange(&[0x41, 0x11, 0x4f]).is_none());
+        assert!(parse_defrange(&[0x0e, 0x11, 0, 0, 0, 0]).is_none());
+        assert_eq!(register_name(333), "rdi");
+        assert_eq!(register_name(19), "edx");
+    }
+}
@@ -1,10 +1,59 @@
 use std::env;
 use std::io::Write;
+use std::convert::TryInto;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
 
-use getopts::Options;
-use std::collections::BTreeMap;
+use getopts::{Matches, Options};
+use std::collections::{BTreeMap, BTreeSet};
 
-use pdb::{FallibleIterator, SymbolData, PDB, LineProgram, AddressMap};
+use pdb::{FallibleIterator, SymbolData, PDB, LineProgram, AddressMap, DebugInformation};
+
+mod address;
+mod addrmap;
+mod audit;
+mod backtrace;
+mod breakpad;
+mod capabilities;
+mod columns;
+mod confidence;
+mod db;
+mod demangle;
+mod exec;
+mod explain;
+mod export;
+mod fallback;
+mod fold;
+mod html;
+mod json;
+mod locals;
+mod modules;
+mod msf;
+mod output;
+mod paths;
+mod pe;
+mod plan;
+mod protobuf;
+mod provider;
+mod rename;
+mod repo;
+mod resolve;
+mod signature;
+mod sqlite;
+mod rpc;
+mod rust;
+mod sample;
+mod store;
+mod system;
+mod trace;
+mod types;
+mod warm;
+
+use address::Arch;
+use provider::{Limits, ProviderOptions, SymbolProvider};
+use resolve::Boundary;
+use output::{Annotation, Binutils, Block, Format, Frame, Gap, RenderOptions, Resolution, Sink, Source, Style, ANNOTATION_DISTANCE};
 
 
 /// File and line number mapping for an instruction address.
@@ -18,6 +67,8 @@ pub struct LineInfo {
     pub file: String,
     /// Absolute line number starting at 1. Zero means no line number.
     pub line: u64,
+    /// Column number starting at 1, if the line program records columns.
+    pub column: Option<u32>,
 }
 
 fn collect_lines<I>(
@@ -25,6 +76,7 @@ fn collect_lines<I>(
     program: &LineProgram,
     address_map: &AddressMap,
     string_table: &pdb::StringTable,
+    max_name: usize,
 ) -> Result<Vec<LineInfo>, pdb::Error>
 where
     I: FallibleIterator<Item = pdb::LineInfo, Error = pdb::Error>
@@ -32,39 +84,179 @@ where
 
     let mut lines = Vec::new();
     while let Some(line_info) = line_iter.next()? {
-        let rva = match line_info.offset.to_rva(&address_map) {
+        let rva = match line_info.offset.to_rva(address_map) {
             Some(rva) => u64::from(rva.0),
             None => continue,
         };
 
         let file_info = program.get_file_info(line_info.file_index)?;
+        let file = file_info.name.to_string_lossy(string_table)?;
+        check_name(file.len(), max_name)?;
         lines.push(LineInfo {
             address: rva,
             size: line_info.length.map(u64::from),
-            file: file_info.name.to_string_lossy(string_table).unwrap().to_string(),
+            file: file.into_owned(),
             line: line_info.line_start.into(),
+            column: line_info.column_start.filter(|&column| column != 0),
         });
     }
 
     Ok(lines)
 }
 
-fn dump_pdb(filename: &str, targets: Vec<u32>) -> pdb::Result<()> {
-    let file = std::fs::File::open(filename)?;
-    let mut pdb = PDB::open(file)?;
+/// Reads the line records of a module's line `program` sorted by address, with their columns
+/// read from its C13 line information through its module `record`, see columns.rs.
+fn read_module_lines<'s, S: pdb::Source<'s> + 's>(
+    pdb: &mut PDB<'s, S>,
+    record: Option<&msf::ModuleRecord>,
+    program: &LineProgram,
+    address_map: &AddressMap,
+    string_table: &pdb::StringTable,
+    max_name: usize,
+) -> pdb::Result<Vec<LineInfo>> {
+    let mut lines = collect_lines(program.lines(), program, address_map, string_table, max_name)?;
+    let module_columns = record.map(|record| columns::module_columns(pdb, record, address_map)).unwrap_or_default();
+    for line in &mut lines {
+        line.column = module_columns.get(&(line.address, line.line)).copied();
+    }
+    lines.sort_by_key(|line| line.address);
+    Ok(lines)
+}
 
-    let address_map = pdb.address_map()?;
-    let string_table = pdb.string_table();
-    let string_table = match string_table {
-        Ok(string_table) => string_table,
-        _ => panic!("No string table in .pdb.")
-    };
+/// Rejects names longer than `max` bytes, see `Limits::max_string_length`, before they are
+/// copied out of the PDB.
+fn check_name(len: usize, max: usize) -> pdb::Result<()> {
+    if len > max {
+        let message = format!("name longer than the limit of {} bytes", max);
+        return Err(pdb::Error::IoError(std::io::Error::new(std::io::ErrorKind::InvalidData, message)));
+    }
+    Ok(())
+}
+
+/// Resolves addresses with the line, inline and public symbol information of a PDB.
+///
+/// The PDB is opened on the first call to `resolve` and kept open with the tables every lookup
+/// needs, so resolving addresses in batches reads them once.
+pub struct PdbProvider {
+    path: PathBuf,
+    options: ProviderOptions,
+    loaded: Option<LoadedPdb>,
+}
+
+impl PdbProvider {
+    pub fn new(path: PathBuf, options: ProviderOptions) -> PdbProvider {
+        PdbProvider { path, options, loaded: None }
+    }
+}
+
+impl SymbolProvider for PdbProvider {
+    fn kind(&self) -> &'static str {
+        "PDB"
+    }
+
+    fn resolve(&mut self, addresses: &mut dyn Iterator<Item = u32>) -> Result<Vec<Resolution>, String> {
+        let loaded = match self.loaded {
+            Some(ref mut loaded) => loaded,
+            None => {
+                let loaded = LoadedPdb::open(&self.path, &self.options).map_err(|e| describe_error(&self.path, e))?;
+                self.loaded.get_or_insert(loaded)
+            }
+        };
+        dump_pdb(loaded, addresses, &self.options).map_err(|e| describe_error(&self.path, e))
+    }
+}
+
+/// A PDB opened for resolving, with the tables read up front and those only some lookups need
+/// read on first use.
+struct LoadedPdb {
+    pdb: PDB<'static, std::fs::File>,
+    address_map: AddressMap<'static>,
+    string_table: pdb::StringTable<'static>,
+    dbi: DebugInformation<'static>,
+    ipi: pdb::IdInformation<'static>,
+    module_records: Vec<msf::ModuleRecord>,
+    /// The type records, with `--signatures`.
+    types: Option<pdb::TypeInformation<'static>>,
+    publics: Option<Vec<(u32, String)>>,
+    sources: Option<Option<repo::SourceMap>>,
+}
+
+impl LoadedPdb {
+    fn open(filename: &Path, options: &ProviderOptions) -> pdb::Result<LoadedPdb> {
+        let file = std::fs::File::open(paths::long_path(filename))?;
+        let mut pdb = PDB::open(file)?;
+        let address_map = pdb.address_map()?;
+        let string_table = pdb.string_table()?;
+        let dbi = pdb.debug_information()?;
+        let module_records = columns::module_records(&mut pdb);
+        let ipi = pdb.id_information()?;
+        let types = if options.signatures { Some(pdb.type_information()?) } else { None };
+        Ok(LoadedPdb { pdb, address_map, string_table, dbi, ipi, module_records, types, publics: None, sources: None })
+    }
+
+    /// The code public symbols, see `load_publics`.
+    fn publics(&mut self, max_name: usize) -> pdb::Result<&[(u32, String)]> {
+        if self.publics.is_none() {
+            self.publics = Some(load_publics(&mut self.pdb, &self.address_map, max_name)?);
+        }
+        Ok(self.publics.as_deref().unwrap_or_default())
+    }
+
+    /// The SourceLink and srcsrv mappings, if the PDB has either.
+    fn sources(&mut self) -> Option<&repo::SourceMap> {
+        let pdb = &mut self.pdb;
+        self.sources.get_or_insert_with(|| repo::SourceMap::load(pdb)).as_ref()
+    }
+}
+
+/// Describes `e`, explaining the missing DBI stream (stream 3) of type server PDBs, which would
+/// otherwise read as a corrupt file.
+fn describe_error(path: &Path, e: pdb::Error) -> String {
+    match e {
+        pdb::Error::StreamNotFound(3) if is_type_server(path) => format!(
+            "'{}' is a type server PDB: it holds only the type records of the objects compiled \
+             against it, not the modules, symbols and line information addresses are resolved \
+             with; use the PDB the linker wrote for the image, or look up types with the type subcommand",
+            path.display()
+        ),
+        e => e.to_string(),
+    }
+}
+
+/// Whether the PDB at `path` has type records but no DBI stream, as the type servers of `/Zi`
+/// compiles do.
+fn is_type_server(path: &Path) -> bool {
+    let pdb = std::fs::File::open(paths::long_path(path)).ok().and_then(|file| PDB::open(file).ok());
+    pdb.is_some_and(|mut pdb| pdb.type_information().is_ok() && pdb.debug_information().is_err())
+}
+
+fn dump_pdb(
+    loaded: &mut LoadedPdb,
+    targets: &mut dyn Iterator<Item = u32>,
+    options: &ProviderOptions,
+) -> pdb::Result<Vec<Resolution>> {
+    let max_depth = options.limits.map_or(usize::MAX, |limits| limits.max_scope_depth);
+    let max_name = options.limits.map_or(usize::MAX, |limits| limits.max_string_length);
+    let mut results: Vec<Resolution> = targets.map(|address| Resolution::new(address, Vec::new())).collect();
+    let queries = resolve::sort_queries(results.iter().map(|result| result.address));
 
-    let dbi = pdb.debug_information()?;
-    let ipi = pdb.id_information()?;
+    let LoadedPdb { ref mut pdb, ref address_map, ref string_table, ref dbi, ref ipi, ref module_records, ref types, .. } =
+        *loaded;
+    let signatures = match types {
+        Some(types) => Some(signature::Signatures::new(types)?),
+        None => None,
+    };
 
+    let needed = modules_to_read(dbi, address_map, &queries);
+    let mut procedures = Vec::new();
+    let mut trampolines = Vec::new();
     let mut modules = dbi.modules()?;
+    let mut index = 0;
     while let Some(module) = modules.next()? {
+        index += 1;
+        if needed.as_ref().is_some_and(|needed| !needed.contains(&(index - 1))) {
+            continue;
+        }
 
         let info = match pdb.module_info(&module)? {
             Some(info) => info,
@@ -79,61 +271,166 @@ fn dump_pdb(filename: &str, targets: Vec<u32>) -> pdb::Result<()> {
         let program = info.line_program()?;
         let mut symbols = info.symbols()?;
 
-        let mut depth = 0;
+        let mut depth: usize = 0;
         let mut inc_next = false;
 
         let mut proc_offsets = Vec::new();
+        let mut language = None;
+        let system = system::is_runtime_compiland(&module.object_file_name());
+        let mut module_lines: Option<Vec<LineInfo>> = None;
+        let mut locals = if options.locals { Some(locals::Collector::new(address_map, &queries)) } else { None };
 
         while let Some(symbol) = symbols.next()? {
+            if let Some(ref mut locals) = locals {
+                locals.visit(&symbol, &mut results);
+            }
 
             if inc_next {
                 depth += 1;
+                // Scopes nest a few levels deep in practice; deeper nesting means a corrupt or
+                // hostile module, whose remaining symbols are skipped.
+                if depth > max_depth {
+                    break;
+                }
             }
 
             inc_next = symbol.starts_scope();
             if symbol.ends_scope() {
-                depth -= 1;
+                depth = depth.saturating_sub(1);
 
-                if proc_offsets.last().map_or(false, |&(d, _)| d >= depth) {
+                if proc_offsets.last().is_some_and(|&(d, _)| d >= depth) {
                     proc_offsets.pop();
                 }
             }
 
+            if let Some(code) = compile_language(&symbol) {
+                language = language_name(code);
+            }
+            if let Some(annotation) = annotation(&symbol, address_map) {
+                let start = u64::from(annotation.address.saturating_sub(ANNOTATION_DISTANCE));
+                let end = u64::from(annotation.address) + u64::from(ANNOTATION_DISTANCE) + 1;
+                for index in resolve::in_sorted_range(&queries, start, end - start) {
+                    results[index].annotations.push(annotation.clone());
+                }
+            }
+
+            if let Some(trampoline) = trampoline(&symbol, address_map) {
+                trampolines.push(trampoline);
+            }
+
             match symbol.parse() {
                 Ok(SymbolData::Procedure(proc)) => {
                     proc_offsets.push((depth, proc.offset));
-                    
-                    match proc.offset.to_rva(&address_map) {
-                        Some(start) => {
-                            for target in &targets {
-                                if start.0 <= *target && *target < start.0 + proc.len {
-
-                                    let mut lines = program.lines_at_offset(proc.offset).peekable();
-                                    while let Some(line_info) = lines.next()? {
-                                        let rva = line_info.offset.to_rva(&address_map).expect("invalid rva");
-                                        let file_info = program.get_file_info(line_info.file_index)?;
-                                        let file_name = file_info.name.to_string_lossy(&string_table)?;
-                                        match lines.peek()? {
-                                            Some(info) => {
-                                                if rva.0 <= *target && info.offset.to_rva(&address_map).expect("invalid rva").0 > *target {
-                                                    println!("{:#x} {} ({}:{})", target, proc.name, file_name, line_info.line_start);
-                                                    break;
-                                                }
-                                            }
-                                            _ => println!("{:#x} {} ({}:{})", target, proc.name, file_name, line_info.line_start),
-                                        };
-                                    }
-                                }
+
+                    if let Some(start) = proc.offset.to_rva(address_map) {
+                        check_name(proc.name.len(), max_name)?;
+                        let name = proc.name.to_string();
+                        let name = resolve::generated_name(&name).unwrap_or_else(|| name.into_owned());
+                        procedures.push((start.0, proc.len, name.clone()));
+                        let start = u64::from(start.0);
+                        let mut hits = resolve::in_sorted_range(&queries, start, proc.len.into()).peekable();
+                        if hits.peek().is_none() {
+                            continue;
+                        }
+
+                        // A procedure's lines can be spread over several line subsections, for
+                        // example when the compiler separates its hot and cold parts, so they are
+                        // taken from all line records of the module by address. Reading them is
+                        // most of the work, which --functions-only saves.
+                        let module_lines = match module_lines {
+                            Some(ref lines) => lines,
+                            None if options.functions_only => module_lines.get_or_insert(Vec::new()),
+                            None => module_lines.get_or_insert(read_module_lines(
+                                pdb,
+                                module_records.get(index - 1),
+                                &program,
+                                address_map,
+                                string_table,
+                                max_name,
+                            )?),
+                        };
+                        let end = start + u64::from(proc.len);
+                        let lines = &module_lines[module_lines.partition_point(|line| line.address < start)
+                            ..module_lines.partition_point(|line| line.address < end)];
+                        let starts: Vec<u64> = lines.iter().map(|l| l.address).collect();
+                        let records: Vec<(u64, Option<u64>)> = lines.iter().map(|l| (l.address, l.size)).collect();
+                        let numbered: Vec<(u64, u64)> = lines.iter().map(|l| (l.address, l.line)).collect();
+                        let signature = signatures.as_ref().and_then(|s| s.signature(&name, proc.type_index));
+
+                        for index in hits {
+                            let result = &mut results[index];
+                            let target = u64::from(result.address);
+                            let function_offset = Some((target - start) as u32);
+                            // The debug start is where the prologue has set up the frame.
+                            let prologue = target - start < u64::from(proc.dbg_start_offset);
+                            let selected = resolve::select_line(&starts, target, options.boundary);
+                            if options.gap_context {
+                                let neighbour = |index: Option<usize>| {
+                                    let line = &lines[index?];
+                                    Some((line.file.clone(), line.line, target.abs_diff(line.address)))
+                                };
+                                result.gap = resolve::gap_neighbours(&numbered, target, selected)
+                                    .map(|(before, after)| Gap { before: neighbour(before), after: neighbour(after) });
                             }
+                            result.frames.push(match selected {
+                                Some(index) => Frame {
+                                    function: name.clone(),
+                                    file: lines[index].file.clone(),
+                                    line: lines[index].line,
+                                    column: lines[index].column,
+                                    function_offset,
+                                    line_offset: Some((target - lines[index].address) as u32),
+                                    inline: false,
+                                    source: Source::LineInfo,
+                                    repo: None,
+                                    aliases: Vec::new(),
+                                    signature: signature.clone(),
+                                    language,
+                                    system,
+                                    candidates: resolve::line_candidates(&records, target, index, |other| {
+                                        (lines[other].file.clone(), lines[other].line)
+                                    }),
+                                    prologue,
+                                },
+                                None => Frame {
+                                    function: name.clone(),
+                                    file: String::new(),
+                                    line: 0,
+                                    column: None,
+                                    function_offset,
+                                    line_offset: None,
+                                    inline: false,
+                                    source: Source::ProcedureOnly,
+                                    repo: None,
+                                    aliases: Vec::new(),
+                                    signature: signature.clone(),
+                                    language,
+                                    system,
+                                    candidates: Vec::new(),
+                                    prologue,
+                                },
+                            });
                         }
-                        _ => {}
                     }
 
                 }
+                Ok(SymbolData::Block(block)) if options.blocks => {
+                    if let Some(start) = block.offset.to_rva(address_map) {
+                        check_name(block.name.len(), max_name)?;
+                        for index in resolve::in_sorted_range(&queries, start.0.into(), block.len.into()) {
+                            results[index].blocks.push(Block {
+                                start: start.0,
+                                len: block.len,
+                                name: block.name.to_string().into_owned(),
+                            });
+                        }
+                    }
+                }
                 Ok(SymbolData::InlineSite(site)) => {
-                    let parent_offset = proc_offsets
-                        .last()
-                        .map(|&(_, offset)| offset).unwrap();
+                    let parent_offset = match proc_offsets.last() {
+                        Some(&(_, offset)) => offset,
+                        None => continue,
+                    };
 
                     // We can assume that inlinees will be listed in the inlinee table. If missing,
                     // skip silently instead of erroring out. Missing a single inline function is
@@ -141,35 +438,46 @@ fn dump_pdb(filename: &str, targets: Vec<u32>) -> pdb::Result<()> {
                     if let Some(inlinee) = inlinees.get(&site.inlinee) {
                         // println!("Found inline parent_offset {:?} {:?} {:?}", parent_offset.to_rva(&address_map), site, inlinee);
                         let line_iter = inlinee.lines(parent_offset, &site);
-                        let lines = collect_lines(line_iter, &program, &address_map, &string_table)?;
+                        let lines = collect_lines(line_iter, &program, address_map, string_table, max_name)?;
                         for l in lines {
-                            for target in &targets {
-                                if l.address <= (*target).into() && l.address + l.size.unwrap() > (*target).into() {
-                                    let mut found_inlinee = false;
-                                    for i in ipi.iter().iterator() {
-                                        if let Ok(i) = i {
-                                            if i.index() == site.inlinee {
-                                                match i.parse() {
-                                                    Ok(pdb::IdData::Function(i)) => {
-                                                        println!("{:#x} {} ({}:{})", target, i.name, l.file, l.line);
-                                                        found_inlinee = true;
-                                                        break;
-                                                    }
-                                                    Ok(pdb::IdData::MemberFunction(i)) => {
-                                                        println!("{:#x} {} ({}:{})", target, i.name, l.file, l.line);
-                                                        found_inlinee = true;
-                                                        break;
-                                                    }
-                                                    _ => {}
-                                                }
-                                                // func_name = p.
-                                                break;
-                                            }
+                            for index in resolve::in_sorted_range(&queries, l.address, l.size.unwrap_or(0)) {
+                                let result = &mut results[index];
+                                let mut function = None;
+                                for i in ipi.iter().iterator().flatten() {
+                                    if i.index() == site.inlinee {
+                                        match i.parse() {
+                                            Ok(pdb::IdData::Function(i)) => function = Some((i.name, i.function_type)),
+                                            Ok(pdb::IdData::MemberFunction(i)) => function = Some((i.name, i.function_type)),
+                                            _ => {}
                                         }
+                                        break;
                                     }
-
-                                    if !found_inlinee { println!("{:#x} {} ({}:{})", target, "unknown_inline_function", l.file, l.line); }
                                 }
+                                if let Some((name, _)) = function {
+                                    check_name(name.len(), max_name)?;
+                                }
+                                let signature = function.and_then(|(name, function_type)| {
+                                    signatures.as_ref()?.signature(&name.to_string(), function_type)
+                                });
+
+                                result.frames.push(Frame {
+                                    function: function
+                                        .map_or_else(|| "unknown_inline_function".to_string(), |(name, _)| name.to_string().into_owned()),
+                                    file: l.file.clone(),
+                                    line: l.line,
+                                    column: l.column,
+                                    function_offset: None,
+                                    line_offset: Some((u64::from(result.address) - l.address) as u32),
+                                    inline: true,
+                                    source: Source::LineInfo,
+                                    repo: None,
+                                    aliases: Vec::new(),
+                                    signature: signature.clone(),
+                                    language,
+                                    system,
+                                    candidates: Vec::new(),
+                                    prologue: false,
+                                });
                             }
                         }
                     }
@@ -179,37 +487,1273 @@ fn dump_pdb(filename: &str, targets: Vec<u32>) -> pdb::Result<()> {
         }
     }
 
+    if let Some(sources) = loaded.sources() {
+        for frame in results.iter_mut().flat_map(|result| result.frames.iter_mut()) {
+            if !frame.file.is_empty() {
+                frame.repo = sources.lookup(&frame.file);
+            }
+        }
+    }
+
+    if !trampolines.is_empty() {
+        follow_trampolines(loaded, options, &trampolines, &queries, &mut results)?;
+    }
+
+    if !results.is_empty() {
+        let publics = loaded.publics(max_name)?;
+        procedures.sort_by_key(|&(start, _, _)| start);
+        resolve::resolve_unmatched(&procedures, publics, &mut results);
+        resolve::merge_folded(publics, &mut results);
+    }
+
+    Ok(results)
+}
+
+/// Returns the `CV_CFL_LANG` code of an `S_COMPILE2` or `S_COMPILE3` record.
+///
+/// The code is read from the raw record because pdb maps the languages it does not know, Rust
+/// among them, to MASM.
+fn compile_language(symbol: &pdb::Symbol) -> Option<u8> {
+    const S_COMPILE2: u16 = 0x1116;
+    const S_COMPILE3: u16 = 0x113c;
+    match symbol.raw_kind() {
+        S_COMPILE2 | S_COMPILE3 => symbol.raw_bytes().get(2).copied(),
+        _ => None,
+    }
+}
+
+/// Reads an `S_ANNOTATION` record, which pdb does not parse: the section offset of the
+/// annotation followed by the number of strings and the null-terminated strings.
+fn annotation(symbol: &pdb::Symbol, address_map: &AddressMap) -> Option<Annotation> {
+    const S_ANNOTATION: u16 = 0x1019;
+    if symbol.raw_kind() != S_ANNOTATION {
+        return None;
+    }
+    let data = symbol.raw_bytes().get(2..)?;
+    let offset = u32::from_le_bytes(data.get(0..4)?.try_into().ok()?);
+    let section = u16::from_le_bytes(data.get(4..6)?.try_into().ok()?);
+    let count = u16::from_le_bytes(data.get(6..8)?.try_into().ok()?);
+    let address = pdb::PdbInternalSectionOffset { offset, section }.to_rva(address_map)?.0;
+    let strings = data[8..]
+        .split(|&b| b == 0)
+        .take(usize::from(count))
+        .map(|s| String::from_utf8_lossy(s).into_owned())
+        .collect();
+    Some(Annotation { address, strings })
+}
+
+/// A thunk an incremental or `/ORDER` link placed between callers and a function: its RVA, its
+/// size and the RVA of the function it jumps to.
+struct Trampoline {
+    start: u32,
+    size: u16,
+    target: u32,
+}
+
+/// Reads an `S_TRAMPOLINE` record, which pdb does not parse: the kind and size of the thunk,
+/// the offsets of the thunk and its target, then their sections.
+fn trampoline(symbol: &pdb::Symbol, address_map: &AddressMap) -> Option<Trampoline> {
+    const S_TRAMPOLINE: u16 = 0x112c;
+    if symbol.raw_kind() != S_TRAMPOLINE {
+        return None;
+    }
+    let data = symbol.raw_bytes().get(2..)?;
+    let u16_at = |offset: usize| Some(u16::from_le_bytes(data.get(offset..offset + 2)?.try_into().ok()?));
+    let u32_at = |offset: usize| Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?));
+    let size = u16_at(2)?;
+    let start = pdb::PdbInternalSectionOffset { offset: u32_at(4)?, section: u16_at(12)? }.to_rva(address_map)?.0;
+    let target = pdb::PdbInternalSectionOffset { offset: u32_at(8)?, section: u16_at(14)? }.to_rva(address_map)?.0;
+    Some(Trampoline { start, size, target })
+}
+
+/// Resolves the addresses within a trampoline that no procedure covers to `trampoline to`
+/// the target function, followed by the target's own frames.
+///
+/// Targets that are trampolines themselves are left alone, so a malformed PDB whose
+/// trampolines jump to each other cannot send the lookups of their targets round in circles.
+fn follow_trampolines(
+    loaded: &mut LoadedPdb,
+    options: &ProviderOptions,
+    trampolines: &[Trampoline],
+    queries: &[(u32, usize)],
+    results: &mut [Resolution],
+) -> pdb::Result<()> {
+    let within = |trampoline: &Trampoline, address: u32| address.wrapping_sub(trampoline.start) < u32::from(trampoline.size);
+    let mut hits = Vec::new();
+    for trampoline in trampolines {
+        if trampolines.iter().any(|other| within(other, trampoline.target)) {
+            continue;
+        }
+        for index in resolve::in_sorted_range(queries, trampoline.start.into(), trampoline.size.into()) {
+            if results[index].frames.is_empty() {
+                hits.push((index, trampoline));
+            }
+        }
+    }
+    if hits.is_empty() {
+        return Ok(());
+    }
+    let targets = dump_pdb(loaded, &mut hits.iter().map(|(_, trampoline)| trampoline.target), options)?;
+    for ((index, trampoline), target) in hits.into_iter().zip(targets) {
+        let result = &mut results[index];
+        let name = target.frames.first().map_or_else(|| format!("{:#x}", trampoline.target), |frame| frame.function.clone());
+        result.frames.push(Frame {
+            function: format!("trampoline to {}", name),
+            file: String::new(),
+            line: 0,
+            column: None,
+            function_offset: Some(result.address - trampoline.start),
+            line_offset: None,
+            inline: false,
+            source: Source::ProcedureOnly,
+            repo: None,
+            aliases: Vec::new(),
+            signature: None,
+            language: None,
+            system: false,
+            candidates: Vec::new(),
+            prologue: false,
+        });
+        // The target's offsets are from the target address, not from the one looked up.
+        result.frames.extend(target.frames.into_iter().map(|frame| Frame { function_offset: None, line_offset: None, ..frame }));
+    }
+    Ok(())
+}
+
+/// Names a `CV_CFL_LANG` source language code.
+fn language_name(code: u8) -> Option<&'static str> {
+    Some(match code {
+        0x00 => "c",
+        0x01 => "c++",
+        0x02 => "fortran",
+        0x03 => "masm",
+        0x04 => "pascal",
+        0x05 => "basic",
+        0x06 => "cobol",
+        0x07 => "link",
+        0x08 => "cvtres",
+        0x09 => "cvtpgd",
+        0x0a => "c#",
+        0x0b => "visual-basic",
+        0x0c => "ilasm",
+        0x0d => "java",
+        0x0e => "jscript",
+        0x0f => "msil",
+        0x10 => "hlsl",
+        0x11 => "objective-c",
+        0x12 => "objective-c++",
+        0x13 => "swift",
+        0x14 => "aliasobj",
+        0x15 => "rust",
+        0x16 => "go",
+        0x44 => "d",
+        _ => return None,
+    })
+}
+
+/// Returns the indices of the modules that can contain the queried addresses, or `None` if the
+/// PDB has no section contributions to tell.
+///
+/// Each address needs the module of the last contribution starting at or before it: the one
+/// covering it or, for an address in padding, the one whose last procedure tells padding from
+/// code without debug information. Huge PDBs such as the kernel's then only have the few modules
+/// a stack touches read. Functions that identical COMDAT folding merged into a skipped module's
+/// code are still reported as aliases through their public symbols.
+fn modules_to_read(dbi: &DebugInformation, address_map: &AddressMap, queries: &[(u32, usize)]) -> Option<BTreeSet<usize>> {
+    let mut contributions = dbi.section_contributions().ok()?;
+    let mut starts = Vec::new();
+    while let Some(contribution) = contributions.next().ok()? {
+        if let Some(start) = contribution.offset.to_rva(address_map) {
+            starts.push((start.0, usize::from(contribution.module)));
+        }
+    }
+    if starts.is_empty() {
+        return None;
+    }
+    starts.sort_unstable();
+    let needed = queries
+        .iter()
+        .filter_map(|&(address, _)| starts[..starts.partition_point(|&(start, _)| start <= address)].last())
+        .map(|&(_, module)| module)
+        .collect();
+    Some(needed)
+}
+
+/// Reads the RVAs and names of all code public symbols, sorted by RVA, rejecting names longer
+/// than `max_name` bytes.
+fn load_publics<'s, S: pdb::Source<'s> + 's>(
+    pdb: &mut PDB<'s, S>,
+    address_map: &AddressMap,
+    max_name: usize,
+) -> pdb::Result<Vec<(u32, String)>> {
+    let mut publics = Vec::new();
+    let globals = match pdb.global_symbols() {
+        Ok(globals) => globals,
+        Err(pdb::Error::GlobalSymbolsNotFound) => return Ok(publics),
+        Err(e) => return Err(e),
+    };
+    let mut symbols = globals.iter();
+    while let Some(symbol) = symbols.next()? {
+        if let Ok(SymbolData::Public(public)) = symbol.parse() {
+            if !(public.function || public.code) {
+                continue;
+            }
+            if let Some(rva) = public.offset.to_rva(address_map) {
+                check_name(public.name.len(), max_name)?;
+                publics.push((rva.0, public.name.to_string().into_owned()));
+            }
+        }
+    }
+    publics.sort_by_key(|&(rva, _)| rva);
+    Ok(publics)
+}
+
+/// Returns the size of the loaded image described by the PDB's section headers, reading the PDB
+/// within `limits`.
+fn image_size(filename: &Path, limits: Option<Limits>) -> Result<Option<u64>, String> {
+    provider::guard(filename, limits, || {
+        let read = || -> pdb::Result<Option<u64>> {
+            let mut pdb = PDB::open(std::fs::File::open(paths::long_path(filename))?)?;
+            let sections = pdb.sections()?;
+            Ok(sections.and_then(|sections| {
+                sections
+                    .iter()
+                    .map(|section| u64::from(section.virtual_address) + u64::from(section.physical_address))
+                    .max()
+            }))
+        };
+        read().map_err(|e| e.to_string())
+    })
+}
+
+/// Returns the value of the option `name`, falling back to the environment variable `var`.
+///
+/// Flags given on the command line always take precedence over the environment, which is not
+/// read at all with `--deterministic`.
+fn opt_or_env(matches: &Matches, name: &str, var: &str) -> Option<String> {
+    if matches.opt_present("deterministic") {
+        return matches.opt_str(name);
+    }
+    matches.opt_str(name).or_else(|| env::var(var).ok().filter(|value| !value.is_empty()))
+}
+
+/// Finds the PDB to open for `filename`.
+///
+/// A path that exists is used as is. Otherwise the file name is looked up in the cache directory
+/// and then in each directory of the `;`-separated symbol path, in order.
+fn locate_pdb(filename: &str, cache: Option<&str>, symbol_path: Option<&str>) -> Option<PathBuf> {
+    let path = Path::new(filename);
+    if paths::long_path(path).is_file() {
+        return Some(path.to_path_buf());
+    }
+
+    let name = path.file_name()?;
+    let search_path = symbol_path.map_or_else(Vec::new, |p| p.split(';').collect());
+    cache
+        .into_iter()
+        .chain(search_path)
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| Path::new(dir).join(name))
+        .find(|candidate| paths::long_path(candidate).is_file())
+}
+
+/// Runs `store add FILE.pdb... --store DIR`, printing where each PDB was placed.
+fn run_store(program: &str, opts: &Options, matches: &Matches) {
+    let (store, files) = match (matches.opt_str("store"), matches.free.get(1).map(String::as_str)) {
+        (Some(store), Some("add")) if matches.free.len() > 2 => (store, &matches.free[2..]),
+        _ => {
+            print_usage(program, opts);
+            return;
+        }
+    };
+    for file in files {
+        match store::add(Path::new(file), Path::new(&store)) {
+            Ok(destination) => println!("{}", destination.display()),
+            Err(e) => exit_with(&format!("error adding '{}' to the store: {}", file, e)),
+        }
+    }
+}
+
+/// Runs `warm --store DIR`, writing the `--export-sqlite` database of every PDB in the store.
+fn run_warm(program: &str, opts: &Options, matches: &Matches) {
+    let store = match matches.opt_str("store") {
+        Some(store) if matches.free.len() == 1 => store,
+        _ => {
+            print_usage(program, opts);
+            return;
+        }
+    };
+    if store.contains("://") {
+        exit_with(&format!("warm reads local symbol stores only, not URLs like '{}'", store));
+    }
+    let filter = matches.opt_str("filter").unwrap_or_else(|| "*.pdb".to_string());
+    let jobs = matches.opt_str("jobs").map_or_else(
+        || std::thread::available_parallelism().map_or(1, usize::from),
+        |jobs| jobs.parse().unwrap_or_else(|_| exit_with(&format!("invalid --jobs '{}'", jobs))),
+    );
+    // Parallel workers print the databases in the order they finish them; a single one prints
+    // them in the sorted order of the store.
+    let jobs = if matches.opt_present("deterministic") {
+        if jobs > 1 && matches.opt_present("jobs") {
+            exit_with("--jobs cannot be more than 1 with --deterministic");
+        }
+        1
+    } else {
+        jobs
+    };
+    match warm::warm(Path::new(&store), &filter, jobs) {
+        Ok(0) => {}
+        Ok(failures) => exit_with(&format!("PDBs that could not be read: {}", failures)),
+        Err(e) => exit_with(&e),
+    }
+}
+
+/// Runs `streams FILE.pdb`, listing the MSF streams, or `streams FILE.pdb --extract N OUT`,
+/// writing stream N to OUT.
+fn run_streams(program: &str, opts: &Options, matches: &Matches) {
+    let file = match (&matches.free[1..], matches.opt_str("extract")) {
+        ([file], None) | ([file, _], Some(_)) => file,
+        _ => {
+            print_usage(program, opts);
+            return;
+        }
+    };
+    let msf = msf::Msf::open(Path::new(file)).unwrap_or_else(|e| exit_with(&format!("error reading '{}': {}", file, e)));
+    if let Some(index) = matches.opt_str("extract") {
+        let out = &matches.free[2];
+        // Streams are named by number or, for named streams like srcsrv, by name.
+        let index: usize = index.parse().unwrap_or_else(|_| {
+            let named = msf::named_streams(Path::new(file)).into_iter().find(|(name, _)| *name == index);
+            named.map_or_else(|| exit_with(&format!("no stream named '{}'", index)), |(_, index)| index)
+        });
+        let bytes = match msf.read(index) {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => exit_with(&format!("stream {} does not exist or is nil", index)),
+            Err(e) => exit_with(&format!("error reading stream {}: {}", index, e)),
+        };
+        if let Err(e) = std::fs::write(paths::long_path(Path::new(out)), bytes) {
+            exit_with(&format!("cannot write '{}': {}", out, e));
+        }
+        return;
+    }
+    let names = msf.stream_names(Path::new(file));
+    let stdout = std::io::stdout();
+    if let Err(e) = msf::write_streams(&mut stdout.lock(), &msf, &names) {
+        exit_with(&format!("error writing output: {}", e));
+    }
+}
+
+/// Resolves `addresses` against every PDB in `dir` and prints which of them cover each address.
+fn compare_pdbs(dir: &Path, addresses: &[u32], options: &ProviderOptions) -> std::io::Result<()> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(paths::long_path(dir))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pdb")))
+        .collect();
+    paths.sort();
+
+    let builds: Vec<(String, Result<Vec<Resolution>, String>)> = paths
+        .iter()
+        .map(|path| {
+            let name = path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+            (name, provider::open(path, options).resolve(&mut addresses.iter().copied()))
+        })
+        .collect();
+
+    let stdout = std::io::stdout();
+    output::write_comparison(&mut stdout.lock(), addresses, &builds)
+}
+
+/// Locates the file named on the command line and the symbol file to read for it, which for an
+/// executable is its PDB.
+fn locate_symbols(
+    filename: &str,
+    cache: Option<&str>,
+    symbol_path: Option<&str>,
+    limits: Option<Limits>,
+) -> Result<(PathBuf, PathBuf), String> {
+    let input = locate_pdb(filename, cache, symbol_path).ok_or_else(|| format!("could not find PDB '{}'", filename))?;
+    let path = if pe::is_pe(&input) { locate_pdb_for_image(&input, cache, symbol_path, limits)? } else { input.clone() };
+    Ok((input, path))
+}
+
+/// Finds the PDB named by the CodeView record of the executable or DLL at `image`.
+///
+/// The PDB is looked for next to the image, under the recorded name and under the image's own
+/// name for PDBs renamed with it, at the path the linker recorded, by debug id in symbol store
+/// layout in the cache and symbol path and then by file name there. The first candidate whose
+/// debug id matches the image wins; if none matches, the first one found is used with a warning.
+///
+/// Images linked with `/PDBALTPATH:%_PDB%` record only the file name, which the searches by name
+/// find wherever the PDB was moved. The candidates are read within `limits`, see
+/// `provider::guard`.
+fn locate_pdb_for_image(
+    image: &Path,
+    cache: Option<&str>,
+    symbol_path: Option<&str>,
+    limits: Option<Limits>,
+) -> Result<PathBuf, String> {
+    let codeview = pe::read_codeview(image).map_err(|e| format!("cannot find the PDB for '{}': {}", image.display(), e))?;
+    let expected = codeview.debug_id();
+    let name = codeview.pdb_name();
+    let stores = cache.into_iter().chain(symbol_path.map_or_else(Vec::new, |p| p.split(';').collect())).filter(|dir| !dir.is_empty());
+    let mut candidates: Vec<PathBuf> = Vec::new();
+    for candidate in vec![image.with_file_name(name), image.with_extension("pdb"), PathBuf::from(&codeview.pdb_path)]
+        .into_iter()
+        .chain(stores.map(|dir| store::store_path(Path::new(dir), name, &expected)))
+        .chain(locate_pdb(name, cache, symbol_path))
+    {
+        if !candidates.contains(&candidate) && paths::long_path(&candidate).is_file() {
+            candidates.push(candidate);
+        }
+    }
+
+    let debug_id = |candidate: &PathBuf| provider::guard(candidate, limits, || store::debug_id(candidate).map_err(|e| e.to_string()));
+    if let Some(found) = candidates.iter().find(|candidate| debug_id(candidate).ok().as_ref() == Some(&expected)) {
+        return Ok(found.clone());
+    }
+    let found = candidates
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("could not find PDB '{}' for '{}'", codeview.pdb_path, image.display()))?;
+    writeln!(
+        &mut std::io::stderr(),
+        "warning: '{}' does not match '{}' (expected debug id {})",
+        found.display(),
+        image.display(),
+        expected
+    )
+    .expect("stderr write");
+    Ok(found)
+}
+
+/// Resolves the addresses that are entries of the incremental linking table (ILT) of a debug
+/// build to the functions they jump to, reading the jumps from the image `data`.
+///
+/// An ILT entry has no line information of its own and would otherwise be attributed to a
+/// public symbol or the padding of whatever precedes the table.
+fn follow_ilt(data: &[u8], provider: &mut dyn SymbolProvider, results: &mut [Resolution]) -> Result<(), String> {
+    let mut thunks = Vec::new();
+    for (i, result) in results.iter().enumerate() {
+        if result.frames.iter().all(|frame| frame.source != Source::LineInfo) {
+            if let Some(target) = pe::jump_target(data, result.address) {
+                thunks.push((i, target));
+            }
+        }
+    }
+    if thunks.is_empty() {
+        return Ok(());
+    }
+    let resolved = provider.resolve(&mut thunks.iter().map(|&(_, target)| target))?;
+    for ((i, target), resolution) in thunks.into_iter().zip(resolved) {
+        // A jump into nothing known is no ILT entry after all.
+        if resolution.frames.iter().any(|frame| frame.source == Source::LineInfo || frame.source == Source::ProcedureOnly) {
+            results[i].frames = resolution.frames;
+            results[i].ilt_target = Some(target);
+        }
+    }
     Ok(())
 }
 
+/// Marks the addresses that lie on the patch site of a hot-patchable function in the image
+/// `data`: its two-byte first instruction or the padding before it a patch overwrites.
+fn mark_hotpatch_sites(data: &[u8], results: &mut [Resolution]) {
+    for result in results {
+        let entry = pe::hotpatch_entry(data, result.address);
+        // Within a function, the site must be that function's own entry; padding after another
+        // function is where the patch's long jump goes.
+        let function_entry = result
+            .frames
+            .first()
+            .filter(|frame| frame.source != Source::Padding)
+            .and_then(|frame| frame.function_offset)
+            .map(|offset| result.address - offset);
+        if entry.is_some() && (function_entry.is_none() || function_entry == entry) {
+            result.hotpatch_entry = entry;
+        }
+    }
+}
+
+/// Reads whitespace-separated words from stdin.
+fn read_stdin_words() -> std::io::Result<Vec<String>> {
+    let mut input = String::new();
+    std::io::Read::read_to_string(&mut std::io::stdin(), &mut input)?;
+    Ok(input.split_whitespace().map(str::to_string).collect())
+}
+
+/// Parses an option value, exiting with the parse error if it is invalid.
+fn parse_or_exit<T: FromStr<Err = String>>(value: &str) -> T {
+    value.parse::<T>().unwrap_or_else(|e| exit_with(&e))
+}
+
+/// Prints `message` to stderr and exits with a failure status.
+fn exit_with(message: &str) -> ! {
+    writeln!(&mut std::io::stderr(), "{}", message).expect("stderr write");
+    std::process::exit(1);
+}
+
+fn print_usage(program: &str, opts: &Options) {
+    let brief = format!("Usage: {0} [options] FILE.pdb|FILE.sym|FILE.exe ADDRESS...\n       {0} [options] --each-pdb DIR ADDRESS...\n       {0} [options] -e FILE.exe [ADDRESS...]\n       {0} [options] explain FILE.pdb|FILE.exe ADDRESS\n       {0} [options] compare-stacks A.txt B.txt --pdb FILE.pdb|FILE.exe\n       {0} [options] type FILE.pdb NAME\n       {0} streams FILE.pdb [--extract N|NAME OUT]\n       {0} store add FILE.pdb... --store DIR\n       {0} warm --store DIR [--filter PATTERN] [--jobs N]\n       {0} capabilities [--json]", program);
+    print!("{}", opts.usage(&brief));
+    println!();
+    println!("Environment:");
+    println!("    PDB_ADDR2LINE_SYMBOL_PATH  default for --symbol-path");
+    println!("    PDB_ADDR2LINE_CACHE        default for --cache");
+    println!("    PDB_ADDR2LINE_FORMAT       default for --format");
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
+    let program = &args[0];
 
     let mut opts = Options::new();
     opts.optflag("h", "help", "print this help menu");
+    opts.optflag("V", "version", "print version, commit and enabled features and exit");
+    opts.optflag("v", "verbose", "show how each frame was resolved in text output");
+    opts.optflag("", "schema", "print the JSON Schema of the json format and exit");
+    opts.optopt("f", "format", "output format: text (default), json or protobuf (schema/results.proto)", "FORMAT");
+    opts.optflag("", "json", "same as --format json");
+    opts.optmulti("o", "output", "write results to PATH ('-' for stdout) as FORMAT, repeatable", "FORMAT=PATH");
+    opts.optopt("e", "exe", "executable to find the PDB from; addresses are read from stdin if none are given", "FILE");
+    opts.optopt("", "style", "text layout: default, windbg, gcc or binutils", "STYLE");
+    opts.optflag("a", "addresses", "as in GNU addr2line, print each address before its frames (binutils style)");
+    opts.optflag("p", "pretty-print", "as in GNU addr2line, print each frame on one line (binutils style)");
+    opts.optflag("i", "inlines", "as in GNU addr2line, also print the callers of inlined functions (binutils style)");
+    opts.optflag("", "functions", "as in GNU addr2line -f, print function names (binutils style)");
+    opts.optopt("", "boundary", "line record owning an address on a record boundary: last (default) or first", "WHICH");
+    opts.optflag("", "functions-only", "print function names without files and lines, skipping the line programs for speed");
+    opts.optflag("", "confidence", "show a 0 to 1 confidence score for each frame, from how it was resolved");
+    opts.optflag("", "relative-to-function", "show the offsets of each address from its function and line record starts");
+    opts.optflag("", "basenames-only-unique", "shorten file paths to the shortest suffix that is unique among the results");
+    opts.optflagopt(
+        "",
+        "crash-signature",
+        "print only the signature of the crash stack: its first N (default 5) private, unfolded frames",
+        "N",
+    );
+    opts.optopt("", "fold-rules", "fold runs of frames matching the GROUP = PATTERN rules in FILE in all but JSON output", "FILE");
+    opts.optflag("", "collapse-templates", "show template arguments as <...> in all but JSON output");
+    opts.optopt("", "demangler", "demanglers to try on public symbol names, in order: msvc, rust or none (default: all)", "LIST");
+    opts.optopt("", "rename-map", "restore obfuscated names from the JSON object of obfuscated to original names in FILE", "FILE");
+    opts.optflag("", "hide-system-frames", "drop frames of the C runtime and C++ standard library, tagged [system] otherwise");
+    opts.optflag("", "rust-pretty", "clean up Rust function names: no hashes, closures as {{closure}}, fewer generics");
+    opts.optflag("", "signatures", "show the full signatures of functions, from the PDB's type records");
+    opts.optflag("", "gap-context", "for addresses in gaps of the line information, show the nearest lines before and after");
+    opts.optflag("", "blocks", "list the lexical blocks containing each address with their ranges");
+    opts.optflag("", "locals", "list the local variables in scope at each address and where they live");
+    opts.optflag("", "untrusted", "bound resources and contain crashes when reading untrusted symbol files");
+    opts.optopt("", "arch", "architecture of the image: x86, x64 or arm64", "ARCH");
+    opts.optopt("", "base", "image base to subtract from the addresses, or 'default' for the --arch default", "ADDR");
+    opts.optopt("", "rebase-from-trace", "infer the image base from a frame naming a public symbol in the stack trace in FILE", "FILE");
+    opts.optflag("", "auto-base", "subtract a guessed image base if the addresses look like virtual addresses");
+    opts.optflagopt(
+        "",
+        "aggregate",
+        "read ADDRESS COUNT pairs and print the total count per function, or per innermost inline frame with 'inline'",
+        "inline",
+    );
+    opts.optflag("", "panic-backtrace", "read a Rust panic backtrace from stdin and fill in its unknown frames");
+    opts.optflag("", "edges", "read CALLER->CALLEE address pairs and print the symbolized call edges");
+    opts.optflag("", "lbr", "read FROM/TO/FLAG branch records (perf brstack) and print the symbolized branches");
+    opts.optopt("", "db", "resolve against a database written by --export-sqlite; addresses are read from stdin if none are given", "DB");
+    opts.optopt("", "export-sqlite", "write the functions, lines, inlinees, files and publics of the PDB to a SQLite DB and exit", "DB");
+    opts.optopt("", "only-range", "with --export-sqlite, export only the modules and publics in the RVA range START-END", "RANGE");
+    opts.optopt("", "sample", "resolve N random addresses of the executable sections and report how many have line info", "N");
+    opts.optopt("", "seed", "seed for picking the --sample addresses (default 0)", "SEED");
+    opts.optopt("", "dump-address-map", "print the section headers and OMAP tables of FILE and exit", "FILE");
+    opts.optflag("", "stdio-jsonrpc", "serve JSON-RPC 2.0 requests (capabilities, resolve, findSymbol, ...) on stdin");
+    opts.optflag("", "dry-run", "show which symbol file and modules would be read, without resolving");
+    opts.optopt(
+        "",
+        "exec-per-frame",
+        "run CMD for each frame, replacing {addr}, {func}, {file} and {line} in its arguments",
+        "CMD",
+    );
+    opts.optopt("", "audit-log", "append each query, its answer, the symbol file's debug id and the time to FILE as JSON lines", "FILE");
+    opts.optopt("", "html-report", "also write an HTML page per source file with the hit lines to DIR", "DIR");
+    opts.optflag("", "deterministic", "make the output depend only on the arguments and files: no environment, cache, timeout, timings or parallel warm");
+    opts.optopt("", "timeout", "stop resolving after SECONDS and mark the remaining addresses as timed out", "SECONDS");
+    opts.optopt("", "loaded-modules", "resolve virtual addresses against the modules of the WinDbg 'lm' listing in FILE", "FILE");
+    opts.optopt(
+        "",
+        "fallback-symbolizer",
+        "forward addresses the symbol file leaves unresolved to COMMAND, speaking llvm-symbolizer's protocol",
+        "COMMAND",
+    );
+    opts.optflag(
+        "",
+        "list-modules",
+        "list the symbol file, debug id, origin and contents of each --loaded-modules module and exit",
+    );
+    opts.optopt("", "each-pdb", "resolve the addresses against every PDB in DIR and compare", "DIR");
+    opts.optopt("", "pdb", "symbol file or image the compare-stacks subcommand resolves both stacks with", "FILE");
+    opts.optmulti("", "extract", "with the streams subcommand, write the raw stream N, a number or name, to OUT", "N");
+    opts.optopt("", "store", "symbol store directory for the store and warm subcommands", "DIR");
+    opts.optopt("", "filter", "with warm, only the PDBs whose names match PATTERN, with * and ? (default *.pdb)", "PATTERN");
+    opts.optopt("", "jobs", "with warm, the number of PDBs read at once; defaults to the number of CPUs", "N");
+    opts.optopt("", "symbol-path", "';'-separated directories searched for the PDB", "PATH");
+    opts.optopt("", "cache", "directory searched for the PDB before the symbol path", "DIR");
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => m,
-        Err(f) => panic!(f.to_string()),
+        Err(f) => panic!("{}", f),
     };
 
-    let (filename, addresses_str) = if matches.free.len() >= 2 {
-        (&matches.free[0], &matches.free[1..])
+    if matches.opt_present("h") {
+        print_usage(program, &opts);
+        return;
+    }
+    if matches.opt_present("version") {
+        print!("{}", capabilities::version_text());
+        return;
+    }
+    if matches.opt_present("schema") {
+        print!("{}", output::SCHEMA);
+        return;
+    }
+
+    if matches.free.first().map(String::as_str) == Some("warm") {
+        run_warm(program, &opts, &matches);
+        return;
+    }
+    if matches.free.first().map(String::as_str) == Some("store") {
+        run_store(program, &opts, &matches);
+        return;
+    }
+
+    let format = if matches.opt_present("json") {
+        Format::Json
     } else {
-        //print_usage(&program, opts);
-        println!("specify path to a PDB");
+        opt_or_env(&matches, "format", "PDB_ADDR2LINE_FORMAT").map_or(Format::Text, |f| parse_or_exit(&f))
+    };
+
+    if matches.free.first().map(String::as_str) == Some("streams") {
+        run_streams(program, &opts, &matches);
+        return;
+    }
+    if matches.free.first().map(String::as_str) == Some("capabilities") {
+        match format {
+            Format::Json => println!("{}", capabilities::to_json()),
+            Format::Text => print!("{}", capabilities::capabilities_text()),
+            Format::Protobuf => {
+                if let Err(e) = protobuf::write_capabilities(&mut std::io::stdout().lock()) {
+                    exit_with(&format!("error writing output: {}", e));
+                }
+            }
+        }
+        return;
+    }
+    if let Some(list) = matches.opt_str("demangler") {
+        demangle::install(demangle::parse_list(&list).unwrap_or_else(|e| exit_with(&e)));
+    }
+    let renames = matches.opt_str("rename-map").map(|file| rename::RenameMap::load(Path::new(&file)).unwrap_or_else(|e| exit_with(&e)));
+    let provider_options = ProviderOptions {
+        boundary: matches.opt_str("boundary").map_or(Boundary::Last, |b| parse_or_exit(&b)),
+        limits: if matches.opt_present("untrusted") { Some(Limits::UNTRUSTED) } else { None },
+        locals: matches.opt_present("locals"),
+        blocks: matches.opt_present("blocks"),
+        signatures: matches.opt_present("signatures"),
+        functions_only: matches.opt_present("functions-only"),
+        gap_context: matches.opt_present("gap-context"),
+        // A warm database is what an earlier run left, like a cache.
+        warm_databases: !matches.opt_present("deterministic"),
+    };
+    let mut style = matches.opt_str("style").map_or(Style::Default, |s| parse_or_exit(&s));
+    let mut sinks: Vec<Sink> = matches.opt_strs("output").iter().map(|o| parse_or_exit(o)).collect();
+    if sinks.is_empty() {
+        sinks.push(Sink { format, path: None });
+    }
+    let symbol_path = opt_or_env(&matches, "symbol-path", "PDB_ADDR2LINE_SYMBOL_PATH");
+    let cache = opt_or_env(&matches, "cache", "PDB_ADDR2LINE_CACHE");
+    // A cache holds whatever earlier runs put there and a timeout depends on the machine's
+    // speed, so neither can be part of a deterministic run.
+    if matches.opt_present("deterministic") {
+        for option in &["cache", "timeout"] {
+            if matches.opt_present(option) {
+                exit_with(&format!("--{} cannot be combined with --deterministic", option));
+            }
+        }
+    }
+
+    let arch: Option<Arch> = matches.opt_str("arch").map(|a| parse_or_exit(&a));
+    let base = matches.opt_str("base").map(|b| match (b.as_str(), arch) {
+        ("default", Some(arch)) => arch.default_base(),
+        ("default", None) => exit_with("--base default requires --arch"),
+        (b, _) => address::parse_address(b, arch).unwrap_or_else(|e| exit_with(&e)),
+    });
+    // The flags of GNU addr2line select its layouts, as they would there.
+    if ["addresses", "pretty-print", "inlines", "functions"].iter().any(|flag| matches.opt_present(flag)) {
+        if !matches!(style, Style::Default | Style::Binutils(_)) || (style == Style::Default && matches.opt_present("style")) {
+            exit_with("-a, -p, -i and --functions select the binutils style");
+        }
+        style = Style::Binutils(Binutils {
+            addresses: matches.opt_present("addresses"),
+            pretty: matches.opt_present("pretty-print"),
+            inlines: matches.opt_present("inlines"),
+            functions: matches.opt_present("functions"),
+            address_width: if arch == Some(Arch::X86) { 8 } else { 16 },
+            base: 0,
+        });
+    }
+    if matches.opt_present("rebase-from-trace") && (base.is_some() || matches.opt_present("auto-base")) {
+        exit_with("--rebase-from-trace cannot be combined with --base or --auto-base");
+    }
+
+    if matches.opt_present("stdio-jsonrpc") {
+        let context = rpc::Context {
+            options: provider_options,
+            arch,
+            cache: cache.as_deref(),
+            symbol_path: symbol_path.as_deref(),
+            renames: renames.as_ref(),
+        };
+        if let Err(e) = rpc::serve(&context) {
+            exit_with(&format!("error: {}", e));
+        }
+        return;
+    }
+
+    if let Some(filename) = matches.opt_str("dump-address-map") {
+        let (_, path) =
+            locate_symbols(&filename, cache.as_deref(), symbol_path.as_deref(), provider_options.limits)
+            .unwrap_or_else(|e| exit_with(&e));
+        let tables = provider::guard(&path, provider_options.limits, || addrmap::read(&path).map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| exit_with(&format!("error reading PDB: {}", e)));
+        let stdout = std::io::stdout();
+        if let Err(e) = addrmap::write(&mut stdout.lock(), format, &tables) {
+            exit_with(&format!("error writing output: {}", e));
+        }
+        return;
+    }
+
+    if let Some(db) = matches.opt_str("export-sqlite") {
+        let filename = matches.free.first().unwrap_or_else(|| exit_with("--export-sqlite needs the PDB or image to export"));
+        let (_, path) =
+            locate_symbols(filename, cache.as_deref(), symbol_path.as_deref(), provider_options.limits)
+            .unwrap_or_else(|e| exit_with(&e));
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("sym")) {
+            exit_with("only PDBs can be exported to SQLite");
+        }
+        let range = matches.opt_str("only-range").map(|range| parse_or_exit(&range));
+        if let Err(e) = provider::guard(&path, provider_options.limits, || export::export_sqlite(&path, Path::new(&db), range)) {
+            exit_with(&e);
+        }
+        return;
+    }
+    if matches.opt_present("only-range") {
+        exit_with("--only-range needs --export-sqlite");
+    }
+
+    if let Some(count) = matches.opt_str("sample") {
+        let count: usize = count.parse().unwrap_or_else(|_| exit_with(&format!("invalid --sample '{}': expected a count", count)));
+        let seed: u64 = matches.opt_str("seed").map_or(0, |seed| {
+            seed.parse().unwrap_or_else(|_| exit_with(&format!("invalid --seed '{}': expected a number", seed)))
+        });
+        let filename = matches.free.first().unwrap_or_else(|| exit_with("--sample needs the PDB or image to sample"));
+        let (_, path) =
+            locate_symbols(filename, cache.as_deref(), symbol_path.as_deref(), provider_options.limits)
+            .unwrap_or_else(|e| exit_with(&e));
+        let ranges = provider::guard(&path, provider_options.limits, || sample::executable_ranges(&path).map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| exit_with(&format!("error reading PDB: {}", e)));
+        let addresses = sample::pick(&ranges, count, seed);
+        let mut provider = provider::open(&path, &provider_options);
+        let results = provider
+            .resolve(&mut addresses.into_iter())
+            .unwrap_or_else(|e| exit_with(&format!("error reading {}: {}", provider.kind(), e)));
+        let stdout = std::io::stdout();
+        if let Err(e) = sample::write_report(&mut stdout.lock(), &results, ranges.len(), seed) {
+            exit_with(&format!("error writing output: {}", e));
+        }
+        return;
+    }
+
+    if matches.free.first().map(String::as_str) == Some("explain") {
+        let (filename, address) = match &matches.free[1..] {
+            [filename, address] => (filename, address),
+            _ => {
+                print_usage(program, &opts);
+                return;
+            }
+        };
+        let (_, path) =
+            locate_symbols(filename, cache.as_deref(), symbol_path.as_deref(), provider_options.limits)
+            .unwrap_or_else(|e| exit_with(&e));
+        let rva = address::parse_address(address, arch)
+            .and_then(|address| address::to_rva(address, base, arch))
+            .unwrap_or_else(|e| exit_with(&e));
+        let stdout = std::io::stdout();
+        let explained =
+            provider::guard(&path, provider_options.limits, || explain::explain(&mut stdout.lock(), &path, rva, provider_options.boundary));
+        if let Err(e) = explained {
+            exit_with(&format!("error reading PDB: {}", e));
+        }
+        return;
+    }
+
+    if matches.opt_present("panic-backtrace") {
+        let filename = matches
+            .opt_str("exe")
+            .or_else(|| matches.free.first().cloned())
+            .unwrap_or_else(|| exit_with("--panic-backtrace needs the PDB or image of the program"));
+        let (_, path) = locate_symbols(&filename, cache.as_deref(), symbol_path.as_deref(), provider_options.limits)
+            .unwrap_or_else(|e| exit_with(&e));
+        let mut text = String::new();
+        if let Err(e) = std::io::Read::read_to_string(&mut std::io::stdin(), &mut text) {
+            exit_with(&format!("error reading stdin: {}", e));
+        }
+        let frames: Vec<backtrace::Frame> =
+            text.lines().filter_map(backtrace::parse_frame).filter(|frame| frame.name.is_none()).collect();
+        let image_size = image_size(&path, provider_options.limits).ok().flatten().unwrap_or(u64::from(u32::MAX));
+        let mut provider = provider::open(&path, &provider_options);
+        let reading = |e: String| format!("error reading {}: {}", path.display(), e);
+        let base = match base {
+            Some(base) => base,
+            None => match backtrace::infer_base(&frames, image_size, provider.as_mut()).unwrap_or_else(|e| exit_with(&reading(e))) {
+                Some(base) => {
+                    writeln!(&mut std::io::stderr(), "note: the image was loaded at {:#x}", base).expect("stderr write");
+                    base
+                }
+                None => exit_with("cannot tell where the image was loaded from the backtrace; give its --base"),
+            },
+        };
+        // Frames of other modules lie outside the image and keep their `<unknown>`.
+        let rvas: Vec<Option<u32>> = frames
+            .iter()
+            .map(|frame| address::to_rva(frame.lookup_address(), Some(base), arch).ok().filter(|&rva| u64::from(rva) < image_size))
+            .collect();
+        let mut results = provider.resolve(&mut rvas.iter().flatten().copied()).unwrap_or_else(|e| exit_with(&reading(e))).into_iter();
+        let resolved: Vec<Option<Resolution>> = rvas.iter().map(|rva| rva.and_then(|_| results.next())).collect();
+        let stdout = std::io::stdout();
+        if let Err(e) = backtrace::write(&mut stdout.lock(), &text, &resolved) {
+            exit_with(&format!("error writing output: {}", e));
+        }
+        return;
+    }
+
+    if matches.free.first().map(String::as_str) == Some("type") {
+        let (filename, query) = match &matches.free[1..] {
+            [filename, query] => (filename, query),
+            _ => {
+                print_usage(program, &opts);
+                return;
+            }
+        };
+        let (_, path) = locate_symbols(filename, cache.as_deref(), symbol_path.as_deref(), provider_options.limits)
+            .unwrap_or_else(|e| exit_with(&e));
+        let stdout = std::io::stdout();
+        match provider::guard(&path, provider_options.limits, || types::write_types(&mut stdout.lock(), &path, query)) {
+            Ok(0) => exit_with(&format!("no class, struct, union or enum named like '{}'", query)),
+            Ok(_) => {}
+            Err(e) => exit_with(&format!("error reading {}: {}", path.display(), e)),
+        }
+        return;
+    }
+
+    if matches.free.first().map(String::as_str) == Some("compare-stacks") {
+        let (stacks, pdb) = match (&matches.free[1..], matches.opt_str("pdb")) {
+            ([a, b], Some(pdb)) => ((a, b), pdb),
+            _ => {
+                print_usage(program, &opts);
+                return;
+            }
+        };
+        let (_, path) = locate_symbols(&pdb, cache.as_deref(), symbol_path.as_deref(), provider_options.limits)
+            .unwrap_or_else(|e| exit_with(&e));
+        // Each line of a stack starts with the address of its frame, innermost first.
+        let read_stack = |file: &str| -> Vec<Resolution> {
+            let text = std::fs::read_to_string(paths::long_path(Path::new(file)))
+                .unwrap_or_else(|e| exit_with(&format!("cannot read stack '{}': {}", file, e)));
+            let addresses: Vec<u32> = text
+                .lines()
+                .filter_map(|line| line.split_whitespace().next())
+                .map(|a| {
+                    address::parse_address(a, arch)
+                        .and_then(|address| address::to_rva(address, base, arch))
+                        .unwrap_or_else(|e| exit_with(&format!("{}: {}", file, e)))
+                })
+                .collect();
+            let mut provider = provider::open(&path, &provider_options);
+            provider
+                .resolve(&mut addresses.into_iter())
+                .unwrap_or_else(|e| exit_with(&format!("error reading {}: {}", provider.kind(), e)))
+        };
+        let (a, b) = (read_stack(stacks.0), read_stack(stacks.1));
+        let stdout = std::io::stdout();
+        if let Err(e) = output::write_stack_diff(&mut stdout.lock(), (stacks.0, stacks.1), &a, &b) {
+            exit_with(&format!("error writing output: {}", e));
+        }
         return;
+    }
+
+    if let Some(dir) = matches.opt_str("each-pdb") {
+        if matches.free.is_empty() {
+            print_usage(program, &opts);
+            return;
+        }
+        let addresses: Vec<u32> = matches
+            .free
+            .iter()
+            .map(|a| {
+                address::parse_address(a, arch)
+                    .and_then(|address| address::to_rva(address, base, arch))
+                    .unwrap_or_else(|e| exit_with(&e))
+            })
+            .collect();
+        if let Err(e) = compare_pdbs(Path::new(&dir), &addresses, &provider_options) {
+            exit_with(&format!("error comparing PDBs in '{}': {}", dir, e));
+        }
+        return;
+    }
+
+    // With --loaded-modules, virtual addresses are resolved against the modules of a WinDbg
+    // `lm` listing, each with its own symbol file.
+    let loaded_modules = matches.opt_str("loaded-modules").map(|file| {
+        let text = std::fs::read_to_string(paths::long_path(Path::new(&file)))
+            .unwrap_or_else(|e| exit_with(&format!("cannot read '{}': {}", file, e)));
+        modules::parse_lm(&text)
+    });
+    if matches.opt_present("list-modules") {
+        let modules = loaded_modules.as_ref().unwrap_or_else(|| exit_with("--list-modules needs --loaded-modules"));
+        let listing = modules::list(modules, cache.as_deref(), symbol_path.as_deref(), provider_options.limits);
+        let stdout = std::io::stdout();
+        if let Err(e) = modules::write_list(&mut stdout.lock(), &listing, format, !matches.opt_present("deterministic")) {
+            exit_with(&format!("error writing output: {}", e));
+        }
+        return;
+    }
+
+    let fold_rules = matches.opt_str("fold-rules").map(|file| {
+        let text = std::fs::read_to_string(paths::long_path(Path::new(&file)))
+            .unwrap_or_else(|e| exit_with(&format!("cannot read '{}': {}", file, e)));
+        fold::parse_rules(&text).unwrap_or_else(|e| exit_with(&format!("invalid fold rules in '{}': {}", file, e)))
+    });
+
+    // With -e, as in binutils addr2line, and with --loaded-modules all positional arguments are
+    // addresses and they are read from stdin if there are none.
+    // --db names the symbol file the way -e names the image, so the same rules apply.
+    if matches.opt_present("exe") && matches.opt_present("db") {
+        exit_with("--db cannot be combined with -e");
+    }
+    let exe = matches.opt_str("exe").or_else(|| matches.opt_str("db"));
+    if exe.is_some() && loaded_modules.is_some() {
+        exit_with("--loaded-modules cannot be combined with -e");
+    }
+    let stdin_addresses: Vec<String>;
+    let (filename, addresses_str) = match exe {
+        _ if matches.free.is_empty() && (exe.is_some() || loaded_modules.is_some()) => {
+            stdin_addresses = read_stdin_words().unwrap_or_else(|e| exit_with(&format!("error reading stdin: {}", e)));
+            (exe.as_ref(), &stdin_addresses[..])
+        }
+        Some(ref exe) => (Some(exe), &matches.free[..]),
+        None if loaded_modules.is_some() => (None, &matches.free[..]),
+        None if matches.free.len() >= 2 => (Some(&matches.free[0]), &matches.free[1..]),
+        None => {
+            print_usage(program, &opts);
+            return;
+        }
     };
 
-    let mut addresses: Vec<u32> = Vec::new();
-    for address_str in addresses_str {
-        let address = u32::from_str_radix(address_str.trim_start_matches("0x"), 16).unwrap();
-        addresses.push(address);
+    // With --aggregate, each address is followed by its weight, as in `ADDRESS COUNT` lines.
+    let aggregate = if matches.opt_present("aggregate") {
+        Some(match matches.opt_str("aggregate").as_deref() {
+            None => false,
+            Some("inline") => true,
+            Some(other) => exit_with(&format!("unknown aggregation '{}', expected inline", other)),
+        })
+    } else {
+        None
+    };
+    // With --edges, arguments are `CALLER->CALLEE` pairs, resolved as two addresses each.
+    let edges = matches.opt_present("edges");
+    let split: Vec<String>;
+    let addresses_str = if edges {
+        split = addresses_str
+            .iter()
+            .flat_map(|edge| match edge.split_once("->") {
+                Some((caller, callee)) => vec![caller.to_string(), callee.to_string()],
+                None => exit_with(&format!("invalid edge '{}', expected CALLER->CALLEE", edge)),
+            })
+            .collect();
+        &split[..]
+    } else {
+        addresses_str
+    };
+
+    // With --lbr, arguments are branch records as `perf script -F brstack` prints them,
+    // `FROM/TO/FLAG/...`; the fields after the prediction flag are ignored.
+    let lbr = matches.opt_present("lbr");
+    let branches: Vec<String>;
+    let mut flags: Vec<String> = Vec::new();
+    let addresses_str = if lbr {
+        branches = addresses_str
+            .iter()
+            .flat_map(|record| {
+                let mut fields = record.split('/');
+                match (fields.next(), fields.next()) {
+                    (Some(from), Some(to)) => {
+                        flags.push(fields.next().unwrap_or("-").to_string());
+                        vec![from.to_string(), to.to_string()]
+                    }
+                    _ => exit_with(&format!("invalid branch record '{}', expected FROM/TO/FLAG", record)),
+                }
+            })
+            .collect();
+        &branches[..]
+    } else {
+        addresses_str
+    };
+
+    let weighted: Vec<String>;
+    let counts: Vec<u64>;
+    let addresses_str = if aggregate.is_some() {
+        if !addresses_str.len().is_multiple_of(2) {
+            exit_with("--aggregate expects ADDRESS COUNT pairs");
+        }
+        weighted = addresses_str.iter().step_by(2).cloned().collect();
+        counts = addresses_str
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .map(|c| c.parse().unwrap_or_else(|_| exit_with(&format!("invalid count '{}'", c))))
+            .collect();
+        &weighted[..]
+    } else {
+        counts = Vec::new();
+        addresses_str
+    };
+
+    // With --crash-signature, the addresses are a stack, innermost first, and only its signature
+    // is printed.
+    let crash_signature = if matches.opt_present("crash-signature") {
+        Some(matches.opt_str("crash-signature").map_or(5, |n| {
+            n.parse::<usize>().unwrap_or_else(|_| exit_with(&format!("invalid frame count '{}'", n)))
+        }))
+    } else {
+        None
+    };
+    if crash_signature.is_some() && (aggregate.is_some() || lbr || edges) {
+        exit_with("--crash-signature cannot be combined with --aggregate, --lbr or --edges");
+    }
+
+    // Folding merges results, which the per-address counts, branch flags and edge pairs rely on.
+    if fold_rules.is_some() && (aggregate.is_some() || lbr || edges) {
+        exit_with("--fold-rules cannot be combined with --aggregate, --lbr or --edges");
+    }
+
+    // Addresses are parsed lazily, once to guess the base and once on their way into the
+    // resolver, so huge address lists are never copied.
+    let vas = || addresses_str.iter().map(|a| address::parse_address(a, arch).unwrap_or_else(|e| exit_with(&e)));
+
+    let timeout = matches.opt_str("timeout").map(|seconds| match seconds.parse::<f64>() {
+        Ok(seconds) if seconds.is_finite() && seconds >= 0.0 => Duration::from_secs_f64(seconds),
+        _ => exit_with(&format!("invalid --timeout '{}': expected a number of seconds", seconds)),
+    });
+    let verbose = matches.opt_present("verbose");
+    let relative = matches.opt_present("relative-to-function");
+    let functions_only = matches.opt_present("functions-only");
+    let confidence = matches.opt_present("confidence");
+    if functions_only && style != Style::Default {
+        exit_with("--functions-only needs the default style");
     }
+    let mut symbol_file = None;
+    let resolved = match (&loaded_modules, filename) {
+        (Some(modules), _) => {
+            if matches.opt_present("dry-run") {
+                exit_with("--dry-run cannot be combined with --loaded-modules");
+            }
+            if timeout.is_some() {
+                exit_with("--timeout cannot be combined with --loaded-modules");
+            }
+            if matches.opt_present("rebase-from-trace") {
+                exit_with("--rebase-from-trace cannot be combined with --loaded-modules, which gives the bases");
+            }
+            if matches.opt_present("fallback-symbolizer") {
+                exit_with("--fallback-symbolizer cannot be combined with --loaded-modules");
+            }
+            let render = RenderOptions { style, module: String::new(), verbose, relative, functions_only, confidence };
+            let results = modules::resolve(modules, vas(), &provider_options, cache.as_deref(), symbol_path.as_deref());
+            results.map(|results| (render, results))
+        }
+        (None, Some(filename)) => {
+            let (input, path) =
+                locate_symbols(filename, cache.as_deref(), symbol_path.as_deref(), provider_options.limits)
+            .unwrap_or_else(|e| exit_with(&e));
 
-    match dump_pdb(&filename, addresses) {
-        Ok(_) => {}
+            let module = input.file_stem().map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+            let base = match matches.opt_str("rebase-from-trace") {
+                Some(trace) => {
+                    let inferred = provider::guard(&path, provider_options.limits, || {
+                        trace::rebase_from_trace(Path::new(&trace), &module, &path)
+                    })
+                    .unwrap_or_else(|e| exit_with(&e));
+                    writeln!(&mut std::io::stderr(), "note: inferred base {:#x} for {} from the trace", inferred, module)
+                        .expect("stderr write");
+                    Some(inferred)
+                }
+                None => base,
+            };
+            let base = base.or_else(|| {
+                let guess = image_size(&path, provider_options.limits).ok().flatten().and_then(|size| address::guess_base(vas(), size))?;
+                if matches.opt_present("auto-base") {
+                    writeln!(&mut std::io::stderr(), "note: treating addresses as virtual addresses with base {:#x}", guess)
+                        .expect("stderr write");
+                    Some(guess)
+                } else {
+                    writeln!(
+                        &mut std::io::stderr(),
+                        "note: most addresses lie outside the image; if they are virtual addresses, try --base {:#x} or --auto-base",
+                        guess
+                    )
+                    .expect("stderr write");
+                    None
+                }
+            });
+
+            // GNU addr2line echoes the addresses as they were given.
+            let style = match style {
+                Style::Binutils(flags) => Style::Binutils(Binutils { base: base.unwrap_or(0), ..flags }),
+                style => style,
+            };
+            let render = RenderOptions { style, module, verbose, relative, functions_only, confidence };
+
+            if matches.opt_present("dry-run") {
+                let addresses: Vec<u32> =
+                    vas().map(|va| address::to_rva(va, base, arch).unwrap_or_else(|e| exit_with(&e))).collect();
+                let stdout = std::io::stdout();
+                if let Err(e) = plan::write_plan(&mut stdout.lock(), &input, &path, &addresses, &provider_options) {
+                    exit_with(&e);
+                }
+                return;
+            }
+
+            symbol_file = Some(path.clone());
+            let mut addresses = vas().map(|va| address::to_rva(va, base, arch).unwrap_or_else(|e| exit_with(&e)));
+            if let Some(timeout) = timeout {
+                // Following ILT entries could take the time that is already up, so it is skipped.
+                let results = provider::resolve_with_timeout(&path, &provider_options, addresses.collect(), timeout)
+                    .map_err(|e| format!("error reading {}: {}", path.display(), e));
+                results.map(|results| (render, results))
+            } else {
+                let mut provider = provider::open(&path, &provider_options);
+                let mut results =
+                    provider.resolve(&mut addresses).map_err(|e| format!("error reading {}: {}", provider.kind(), e));
+                // Only an image has the jumps of the incremental linking table to follow and the
+                // code of hot-patch sites.
+                if let Ok(ref mut results) = results {
+                    if input != path && pe::is_pe(&input) {
+                        match std::fs::read(paths::long_path(&input)) {
+                            Ok(data) => {
+                                if let Err(e) = follow_ilt(&data, provider.as_mut(), results) {
+                                    writeln!(&mut std::io::stderr(), "warning: not following ILT entries: {}", e)
+                                        .expect("stderr write");
+                                }
+                                mark_hotpatch_sites(&data, results);
+                            }
+                            Err(e) => writeln!(&mut std::io::stderr(), "warning: cannot read '{}': {}", input.display(), e)
+                                .expect("stderr write"),
+                        }
+                    }
+                }
+                results.map(|results| (render, results))
+            }
+        }
+        (None, None) => unreachable!("a file is named unless --loaded-modules is given"),
+    };
+    match resolved {
+        Ok((render, mut results)) => {
+            if let Some(command) = matches.opt_str("fallback-symbolizer") {
+                let filled = fallback::Fallback::spawn(&command).and_then(|mut fallback| fallback.fill(&mut results));
+                if let Err(e) = filled {
+                    writeln!(&mut std::io::stderr(), "warning: {}", e).expect("stderr write");
+                }
+            }
+            if let Some(log) = matches.opt_str("audit-log") {
+                let limits = provider_options.limits;
+                if let Err(e) = audit::append(Path::new(&log), symbol_file.as_deref(), &results, limits) {
+                    writeln!(&mut std::io::stderr(), "error: {}", e).expect("stderr write");
+                }
+            }
+            if matches.opt_present("rust-pretty") {
+                rust::prettify(&mut results);
+            }
+            if let Some(ref renames) = renames {
+                renames.apply(&mut results);
+            }
+            system::classify(&mut results);
+            if matches.opt_present("hide-system-frames") {
+                system::hide(&mut results);
+            }
+            // Shortened paths are only for display; structured output, the HTML report and commands
+            // get full paths.
+            let shortened;
+            let displayed = if matches.opt_present("basenames-only-unique") {
+                let mut copy = results.clone();
+                output::shorten_files(&mut copy);
+                shortened = copy;
+                &shortened
+            } else {
+                &results
+            };
+            // Folded stacks and collapsed names are only for reading; JSON keeps the full results.
+            let collapsed;
+            let shown = if fold_rules.is_some() || matches.opt_present("collapse-templates") {
+                let mut copy = match fold_rules {
+                    Some(ref rules) => fold::fold(displayed, rules),
+                    None => displayed.clone(),
+                };
+                if matches.opt_present("collapse-templates") {
+                    output::collapse_templates(&mut copy);
+                }
+                collapsed = copy;
+                &collapsed
+            } else {
+                displayed
+            };
+            if let Some(depth) = crash_signature {
+                // Public symbols alone mark modules shipped without private symbols, usually the OS.
+                let system = |frame: &output::Frame| {
+                    frame.source == output::Source::PublicSymbol
+                        || frame.system
+                        || fold_rules.as_ref().is_some_and(|rules| fold::group(rules, &frame.function).is_some())
+                };
+                let stdout = std::io::stdout();
+                if let Err(e) = output::write_crash_signature(&mut stdout.lock(), &results, depth, &system) {
+                    writeln!(&mut std::io::stderr(), "error writing output: {}", e).expect("stderr write");
+                }
+            } else if lbr {
+                let stdout = std::io::stdout();
+                if let Err(e) = output::write_branches(&mut stdout.lock(), shown, &flags) {
+                    writeln!(&mut std::io::stderr(), "error writing output: {}", e).expect("stderr write");
+                }
+            } else if edges {
+                let stdout = std::io::stdout();
+                if let Err(e) = output::write_edges(&mut stdout.lock(), shown) {
+                    writeln!(&mut std::io::stderr(), "error writing output: {}", e).expect("stderr write");
+                }
+            } else if let Some(inline) = aggregate {
+                let stdout = std::io::stdout();
+                if let Err(e) = output::write_aggregate(&mut stdout.lock(), shown, &counts, inline) {
+                    writeln!(&mut std::io::stderr(), "error writing output: {}", e).expect("stderr write");
+                }
+            } else {
+                for sink in &sinks {
+                    let results = if sink.format == output::Format::Text { shown } else { &results };
+                    if let Err(e) = sink.write(&render, results) {
+                        writeln!(&mut std::io::stderr(), "error writing output: {}", e).expect("stderr write");
+                    }
+                }
+            }
+            if let Some(command) = matches.opt_str("exec-per-frame") {
+                if let Err(e) = exec::run_per_frame(&command, &results) {
+                    writeln!(&mut std::io::stderr(), "error: {}", e).expect("stderr write");
+                }
+            }
+            if let Some(dir) = matches.opt_str("html-report") {
+                if let Err(e) = html::write_report(Path::new(&dir), &results) {
+                    writeln!(&mut std::io::stderr(), "error writing HTML report: {}", e).expect("stderr write");
+                }
+            }
+        }
         Err(e) => {
-            writeln!(&mut std::io::stderr(), "error dumping PDB: {}", e).expect("stderr write");
+            writeln!(&mut std::io::stderr(), "{}", e).expect("stderr write");
         }
     }
 }
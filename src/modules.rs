@@ -0,0 +1,302 @@
+//! `--loaded-modules`: resolving virtual addresses against the modules of a WinDbg `lm` listing,
+//! as kernel dumps need where a stack spans ntoskrnl, the HAL and drivers.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use pdb::{FallibleIterator, PDB};
+
+use crate::output::{json_string, Format, Resolution};
+use crate::provider::{self, Limits, ProviderOptions};
+use crate::store;
+use crate::{load_publics, locate_symbols, paths};
+
+/// A module as `lm` lists it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoadedModule {
+    pub name: String,
+    pub start: u64,
+    pub end: u64,
+    /// The symbol or image file path printed after the symbol state, if any.
+    pub path: Option<String>,
+}
+
+/// Parses the output of `lm`, `lmf` or `lm l`.
+///
+/// Each module line reads `START END NAME [(SYMBOL STATE)] [PATH]`, with addresses in WinDbg's
+/// `fffff803`12400000` form. Headers, prompts and the lines `lmv` adds are skipped, and so is
+/// the `Unloaded modules:` list, whose modules have no code mapped anymore.
+pub fn parse_lm(text: &str) -> Vec<LoadedModule> {
+    let mut modules = Vec::new();
+    for line in text.lines() {
+        if line.trim_start().starts_with("Unloaded modules") {
+            break;
+        }
+        let mut fields = line.split_whitespace();
+        let (start, end, name) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(start), Some(end), Some(name)) => (start, end, name),
+            _ => continue,
+        };
+        let (start, end) = match (parse_hex(start), parse_hex(end)) {
+            (Some(start), Some(end)) if start < end => (start, end),
+            _ => continue,
+        };
+        let rest = fields.collect::<Vec<_>>().join(" ");
+        let rest = match rest.strip_prefix('(') {
+            Some(state) => state.split_once(')').map_or("", |(_, path)| path).trim(),
+            None => rest.trim(),
+        };
+        modules.push(LoadedModule {
+            name: name.to_string(),
+            start,
+            end,
+            path: if rest.is_empty() { None } else { Some(rest.to_string()) },
+        });
+    }
+    modules.sort_by_key(|module| module.start);
+    modules
+}
+
+fn parse_hex(s: &str) -> Option<u64> {
+    let digits: String = s.trim_start_matches("0x").chars().filter(|&c| c != '`').collect();
+    u64::from_str_radix(&digits, 16).ok()
+}
+
+/// The PDB names the kernel is built with, depending on processor features and architecture.
+/// `lm` lists it as `nt` whatever the file is called.
+const KERNEL_PDBS: [&str; 5] = ["ntkrnlmp.pdb", "ntoskrnl.pdb", "ntkrla57.pdb", "ntkrnlpa.pdb", "ntkrpamp.pdb"];
+
+/// The symbol files to try for `module`, most specific first.
+fn candidates(module: &LoadedModule) -> Vec<String> {
+    let mut candidates: Vec<String> = module.path.iter().cloned().collect();
+    if module.name.eq_ignore_ascii_case("nt") {
+        candidates.extend(KERNEL_PDBS.iter().map(|name| name.to_string()));
+    } else {
+        candidates.push(format!("{}.pdb", module.name));
+    }
+    candidates
+}
+
+/// Resolves the virtual addresses `vas` against the modules they lie in.
+///
+/// Every module is read once for all of its addresses. Each result names its module and holds
+/// the address relative to the module's base. Modules without a symbol file or with one that
+/// cannot be read are reported on stderr and their addresses left unresolved; an address outside
+/// every module is an error.
+pub fn resolve(
+    modules: &[LoadedModule],
+    vas: impl Iterator<Item = u64>,
+    options: &ProviderOptions,
+    cache: Option<&str>,
+    symbol_path: Option<&str>,
+) -> Result<Vec<Resolution>, String> {
+    let mut results = Vec::new();
+    let mut by_module: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for va in vas {
+        let index = modules[..modules.partition_point(|module| module.start <= va)]
+            .len()
+            .checked_sub(1)
+            .filter(|&index| va < modules[index].end)
+            .ok_or_else(|| format!("address {:#x} is not within any loaded module", va))?;
+        let module = &modules[index];
+        let rva = u32::try_from(va - module.start)
+            .map_err(|_| format!("address {:#x} is not within 4 GiB of module '{}'", va, module.name))?;
+        let mut result = Resolution::new(rva, Vec::new());
+        result.module = Some(module.name.clone());
+        by_module.entry(index).or_default().push(results.len());
+        results.push(result);
+    }
+
+    for (module, positions) in by_module {
+        let module = &modules[module];
+        let path =
+            candidates(module).iter().find_map(|candidate| locate_symbols(candidate, cache, symbol_path, options.limits).ok());
+        let (_, path) = match path {
+            Some(path) => path,
+            None => {
+                warn(&format!("no symbols found for module '{}'", module.name));
+                continue;
+            }
+        };
+        let mut rvas = positions.iter().map(|&position| results[position].address);
+        match provider::open(&path, options).resolve(&mut rvas) {
+            Ok(resolved) => {
+                for (position, mut resolution) in positions.into_iter().zip(resolved) {
+                    resolution.module = results[position].module.take();
+                    results[position] = resolution;
+                }
+            }
+            Err(e) => warn(&format!("cannot read symbols of module '{}' from '{}': {}", module.name, path.display(), e)),
+        }
+    }
+    Ok(results)
+}
+
+/// What `--list-modules` tells of the symbol file of a module.
+pub struct SymbolFile {
+    pub path: String,
+    /// Where it was found: `local` for the path `lm` printed, the image's directory or the path
+    /// the linker recorded, `cache` for the --cache directory and `symbol-path` for the others.
+    pub origin: &'static str,
+    pub debug_id: String,
+    pub has_lines: bool,
+    pub has_publics: bool,
+    pub has_srcsrv: bool,
+    /// How long reading the module list, line programs and publics took.
+    pub load_time: Duration,
+}
+
+/// Finds and inspects the symbol file of each module within `limits`, or says why there is none.
+pub fn list<'m>(
+    modules: &'m [LoadedModule],
+    cache: Option<&str>,
+    symbol_path: Option<&str>,
+    limits: Option<Limits>,
+) -> Vec<(&'m LoadedModule, Result<SymbolFile, String>)> {
+    modules
+        .iter()
+        .map(|module| {
+            let path = candidates(module).iter().find_map(|candidate| locate_symbols(candidate, cache, symbol_path, limits).ok());
+            let symbols = match path {
+                Some((_, path)) => provider::guard(&path, limits, || {
+                    let max_name = limits.map_or(usize::MAX, |limits| limits.max_string_length);
+                    inspect(&path, cache, symbol_path, max_name)
+                        .map_err(|e| format!("cannot read '{}': {}", path.display(), e))
+                }),
+                None => Err("no symbols found".to_string()),
+            };
+            (module, symbols)
+        })
+        .collect()
+}
+
+fn inspect(path: &Path, cache: Option<&str>, symbol_path: Option<&str>, max_name: usize) -> pdb::Result<SymbolFile> {
+    let debug_id = store::debug_id(path)?;
+    let started = Instant::now();
+    let mut pdb = PDB::open(fs::File::open(paths::long_path(path))?)?;
+    let dbi = pdb.debug_information()?;
+    let mut has_lines = false;
+    let mut modules = dbi.modules()?;
+    while let Some(module) = modules.next()? {
+        if let Some(info) = pdb.module_info(&module)? {
+            if info.line_program()?.lines().next()?.is_some() {
+                has_lines = true;
+                break;
+            }
+        }
+    }
+    let address_map = pdb.address_map()?;
+    let has_publics = !load_publics(&mut pdb, &address_map, max_name)?.is_empty();
+    let has_srcsrv = pdb.named_stream(b"srcsrv").is_ok();
+
+    let within = |dir: &str| !dir.is_empty() && path.starts_with(dir);
+    let origin = if cache.is_some_and(within) {
+        "cache"
+    } else if symbol_path.is_some_and(|dirs| dirs.split(';').any(within)) {
+        "symbol-path"
+    } else {
+        "local"
+    };
+    Ok(SymbolFile {
+        path: path.display().to_string(),
+        origin,
+        debug_id,
+        has_lines,
+        has_publics,
+        has_srcsrv,
+        load_time: started.elapsed(),
+    })
+}
+
+/// Writes the `listing` of `list` as a table, or as a JSON array with one object per module.
+/// The load times are left out without `timings`, as they differ from run to run.
+pub fn write_list(
+    out: &mut dyn Write,
+    listing: &[(&LoadedModule, Result<SymbolFile, String>)],
+    format: Format,
+    timings: bool,
+) -> io::Result<()> {
+    if format == Format::Json {
+        let entries: Vec<String> = listing
+            .iter()
+            .map(|(module, symbols)| {
+                let symbols = match symbols {
+                    Ok(file) => format!(
+                        "\"symbol_file\":{},\"origin\":{},\"debug_id\":{},\"has_lines\":{},\"has_publics\":{},\"has_srcsrv\":{}{}",
+                        json_string(&file.path),
+                        json_string(file.origin),
+                        json_string(&file.debug_id),
+                        file.has_lines,
+                        file.has_publics,
+                        file.has_srcsrv,
+                        if timings { format!(",\"load_ms\":{}", file.load_time.as_millis()) } else { String::new() }
+                    ),
+                    Err(e) => format!("\"symbol_file\":null,\"error\":{}", json_string(e)),
+                };
+                format!(
+                    "{{\"name\":{},\"start\":\"{:#x}\",\"end\":\"{:#x}\",{}}}",
+                    json_string(&module.name),
+                    module.start,
+                    module.end,
+                    symbols
+                )
+            })
+            .collect();
+        return writeln!(out, "[{}]", entries.join(","));
+    }
+    for (module, symbols) in listing {
+        match symbols {
+            Ok(file) => {
+                let has = [("lines", file.has_lines), ("publics", file.has_publics), ("srcsrv", file.has_srcsrv)];
+                let has: Vec<&str> = has.iter().filter(|&&(_, has)| has).map(|&(name, _)| name).collect();
+                write!(out, "{:16} {} {} ({}) [{}]", module.name, file.debug_id, file.path, file.origin, has.join(", "))?;
+                if timings {
+                    write!(out, " {} ms", file.load_time.as_millis())?;
+                }
+                writeln!(out)?;
+            }
+            Err(e) => writeln!(out, "{:16} {}", module.name, e)?,
+        }
+    }
+    Ok(())
+}
+
+fn warn(message: &str) {
+    writeln!(&mut io::stderr(), "warning: {}", message).expect("stderr write");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_lm, LoadedModule};
+
+    #[test]
+    fn lm_listing() {
+        let text = "\
+0: kd> lm
+start             end                 module name
+fffff803`4a000000 fffff803`4a0a7000   hal        (deferred)
+fffff803`4a200000 fffff803`4b246000   nt         (pdb symbols)          c:\\sym\\ntkrnlmp.pdb\\0123\\ntkrnlmp.pdb
+fffff803`4c000000 fffff803`4c010000   mydrv      \\SystemRoot\\System32\\drivers\\mydrv.sys
+
+Unloaded modules:
+fffff803`4d000000 fffff803`4d010000   old.sys
+";
+        let modules = parse_lm(text);
+        assert_eq!(modules.len(), 3);
+        assert_eq!(
+            modules[1],
+            LoadedModule {
+                name: "nt".to_string(),
+                start: 0xfffff803_4a200000,
+                end: 0xfffff803_4b246000,
+                path: Some("c:\\sym\\ntkrnlmp.pdb\\0123\\ntkrnlmp.pdb".to_string()),
+            }
+        );
+        assert_eq!(modules[0].path, None);
+        assert_eq!(modules[2].path.as_deref(), Some("\\SystemRoot\\System32\\drivers\\mydrv.sys"));
+    }
+}
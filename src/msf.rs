@@ -0,0 +1,293 @@
+//! The `streams` subcommand: the raw streams of the multi-stream file (MSF) container a PDB is,
+//! with their sizes and what they hold, for debugging malformed PDBs.
+
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use pdb::PDB;
+
+use crate::paths;
+
+const MAGIC: &[u8] = b"Microsoft C/C++ MSF 7.00\r\n\x1aDS\0\0\0";
+
+/// The names of the streams with fixed numbers.
+const FIXED: [&str; 5] = ["old directory", "PDB information", "TPI (types)", "DBI (debug information)", "IPI (ids)"];
+
+/// The streams the optional debug header of the DBI stream lists, in its order.
+const DEBUG_HEADER: [&str; 11] = [
+    "FPO data",
+    "exception data",
+    "fixup data",
+    "OMAP to source",
+    "OMAP from source",
+    "section headers",
+    "token RID map",
+    "xdata",
+    "pdata",
+    "new FPO data",
+    "original section headers",
+];
+
+/// A PDB read as a plain MSF file, without interpreting its streams.
+pub struct Msf {
+    data: Vec<u8>,
+    block_size: usize,
+    /// The size and blocks of each stream, or `None` for nil streams.
+    streams: Vec<Option<(u32, Vec<u32>)>>,
+}
+
+impl Msf {
+    /// Reads the MSF file at `path` and its stream directory.
+    pub fn open(path: &Path) -> Result<Msf, String> {
+        let data = fs::read(paths::long_path(path)).map_err(|e| format!("cannot read '{}': {}", path.display(), e))?;
+        Msf::parse(data)
+    }
+
+    fn parse(data: Vec<u8>) -> Result<Msf, String> {
+        if !data.starts_with(MAGIC) {
+            return Err("not an MSF 7.00 file".to_string());
+        }
+        let field = |offset: usize| u32_at(&data, offset).ok_or("truncated superblock");
+        let block_size = field(32)? as usize;
+        if !matches!(block_size, 512 | 1024 | 2048 | 4096 | 8192 | 16384 | 32768) {
+            return Err(format!("invalid block size {}", block_size));
+        }
+        let directory_size = field(44)? as usize;
+        let block_map = field(52)? as usize;
+        let msf = Msf { data, block_size, streams: Vec::new() };
+
+        // The block map lists the blocks of the directory, which lists the blocks of each stream.
+        let directory_blocks: Vec<u32> = (0..directory_size.div_ceil(block_size))
+            .map(|i| u32_at(&msf.data, block_map * block_size + 4 * i).ok_or("truncated block map"))
+            .collect::<Result<_, _>>()?;
+        let directory = msf.gather(&directory_blocks, directory_size)?;
+        let word = |i: usize| u32_at(&directory, 4 * i).ok_or("truncated stream directory");
+        let count = word(0)? as usize;
+        let mut next = 1 + count;
+        let mut streams = Vec::with_capacity(count.min(directory.len() / 4));
+        for i in 0..count {
+            let size = word(1 + i)?;
+            if size == u32::MAX {
+                streams.push(None);
+                continue;
+            }
+            let blocks = (size as usize).div_ceil(block_size);
+            let list = (next..next + blocks).map(word).collect::<Result<Vec<u32>, _>>()?;
+            next += blocks;
+            streams.push(Some((size, list)));
+        }
+        Ok(Msf { streams, ..msf })
+    }
+
+    /// The number of streams, nil streams included.
+    pub fn stream_count(&self) -> usize {
+        self.streams.len()
+    }
+
+    /// The size of stream `index`, or `None` for a nil or missing stream.
+    pub fn stream_size(&self, index: usize) -> Option<u32> {
+        self.streams.get(index)?.as_ref().map(|&(size, _)| size)
+    }
+
+    /// The contents of stream `index`, or `None` for a nil or missing stream.
+    pub fn read(&self, index: usize) -> Result<Option<Vec<u8>>, String> {
+        match self.streams.get(index) {
+            Some(Some((size, blocks))) => self.gather(blocks, *size as usize).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    fn gather(&self, blocks: &[u32], size: usize) -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::with_capacity(size);
+        for &block in blocks {
+            let start = block as usize * self.block_size;
+            let chunk = self.data.get(start..start + self.block_size).ok_or_else(|| format!("block {} is past the end of the file", block))?;
+            bytes.extend_from_slice(chunk);
+        }
+        bytes.truncate(size);
+        Ok(bytes)
+    }
+
+    /// What the streams hold, as far as the fixed streams, the named streams of the PDB
+    /// information stream and the DBI stream tell.
+    pub fn stream_names(&self, path: &Path) -> BTreeMap<usize, String> {
+        let mut names: BTreeMap<usize, String> = FIXED.iter().enumerate().map(|(i, name)| (i, name.to_string())).collect();
+        if let Ok(Some(dbi)) = self.read(3) {
+            dbi_stream_names(&dbi, &mut names);
+        }
+        for (name, index) in named_streams(path) {
+            names.insert(index, format!("named \"{}\"", name));
+        }
+        names
+    }
+}
+
+/// The sizes of the streams of the MSF file at `path`, nil streams left out, or `None` if it is
+/// not an MSF file. Only the superblock and the stream directory are read, and directories
+/// larger than `max_directory_size` bytes are rejected before they are.
+pub fn stream_sizes(path: &Path, max_directory_size: u64) -> Result<Option<Vec<u32>>, String> {
+    let mut file = fs::File::open(paths::long_path(path)).map_err(|e| format!("cannot read '{}': {}", path.display(), e))?;
+    let mut superblock = [0; 56];
+    if file.read_exact(&mut superblock).is_err() || !superblock.starts_with(MAGIC) {
+        return Ok(None);
+    }
+    let field = |offset: usize| u64::from(u32_at(&superblock, offset).unwrap_or(0));
+    let block_size = field(32);
+    if !matches!(block_size, 512 | 1024 | 2048 | 4096 | 8192 | 16384 | 32768) {
+        return Err(format!("invalid block size {}", block_size));
+    }
+    let directory_size = field(44);
+    if directory_size > max_directory_size {
+        return Err(format!("stream directory is larger than the limit of {} bytes", max_directory_size));
+    }
+    let mut read_at = |offset: u64, size: u64| -> Result<Vec<u8>, String> {
+        let mut data = vec![0; size as usize];
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_exact(&mut data))
+            .map_err(|_| format!("offset {:#x} is past the end of the file", offset))?;
+        Ok(data)
+    };
+
+    let directory_blocks = directory_size.div_ceil(block_size);
+    let block_map = read_at(field(52) * block_size, 4 * directory_blocks)?;
+    let mut directory = Vec::new();
+    for i in 0..directory_blocks as usize {
+        let block = u32_at(&block_map, 4 * i).ok_or("truncated block map")?;
+        directory.extend(read_at(u64::from(block) * block_size, block_size)?);
+    }
+    directory.truncate(directory_size as usize);
+    let count = u32_at(&directory, 0).ok_or("truncated stream directory")? as usize;
+    (1..=count)
+        .map(|i| u32_at(&directory, 4 * i).ok_or_else(|| "truncated stream directory".to_string()))
+        .filter(|size| size.as_ref().map_or(true, |&size| size != u32::MAX))
+        .collect::<Result<Vec<u32>, String>>()
+        .map(Some)
+}
+
+/// The named streams of the PDB at `path`, such as `srcsrv` or streams build pipelines add, with
+/// their numbers.
+pub fn named_streams(path: &Path) -> Vec<(String, usize)> {
+    // The name map is a hash table; the pdb crate reads it.
+    let pdb = fs::File::open(paths::long_path(path)).ok().and_then(|file| PDB::open(file).ok());
+    let info = match pdb.map(|mut pdb| pdb.pdb_information()) {
+        Some(Ok(info)) => info,
+        _ => return Vec::new(),
+    };
+    match info.stream_names() {
+        Ok(streams) => streams.into_iter().map(|stream| (stream.name.to_string().into_owned(), usize::from(stream.stream_id.0))).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// A module record of the DBI stream: the module's stream and the sizes of its parts.
+pub struct ModuleRecord {
+    pub name: String,
+    pub stream: u16,
+    /// The size of the symbols, including the signature before them.
+    pub symbols_size: u32,
+    pub c11_lines_size: u32,
+    pub c13_lines_size: u32,
+}
+
+/// The module records of the DBI stream `dbi`, in module order.
+pub fn module_records(dbi: &[u8]) -> Vec<ModuleRecord> {
+    let size = |offset: usize| u32_at(dbi, offset).map_or(0, |size| size as usize);
+    let modules_end = (64 + size(24)).min(dbi.len());
+
+    // Each module record is 64 bytes of fields, the module's stream at 34 followed by the sizes
+    // of its parts, then its module and object file names, padded to 4 bytes.
+    let mut records = Vec::new();
+    let mut offset = 64;
+    while offset + 64 <= modules_end {
+        let strings = &dbi[offset + 64..modules_end];
+        let mut parts = strings.splitn(3, |&b| b == 0);
+        let module = parts.next().unwrap_or_default();
+        let object = parts.next().unwrap_or_default();
+        records.push(ModuleRecord {
+            name: String::from_utf8_lossy(module).into_owned(),
+            stream: u16_at(dbi, offset + 34).unwrap_or(u16::MAX),
+            symbols_size: u32_at(dbi, offset + 36).unwrap_or(0),
+            c11_lines_size: u32_at(dbi, offset + 40).unwrap_or(0),
+            c13_lines_size: u32_at(dbi, offset + 44).unwrap_or(0),
+        });
+        offset += 64 + module.len() + 1 + object.len() + 1;
+        offset = (offset + 3) & !3;
+    }
+    records
+}
+
+/// Names the streams the DBI header, the module list and the debug header refer to.
+fn dbi_stream_names(dbi: &[u8], names: &mut BTreeMap<usize, String>) {
+    let mut name = |stream: Option<u16>, name: String| {
+        if let Some(stream) = stream.filter(|&stream| stream != u16::MAX) {
+            names.insert(usize::from(stream), name);
+        }
+    };
+    name(u16_at(dbi, 12), "global symbol hashes".to_string());
+    name(u16_at(dbi, 16), "public symbol hashes".to_string());
+    name(u16_at(dbi, 20), "symbol records".to_string());
+    for module in module_records(dbi) {
+        name(Some(module.stream), format!("module {}", module.name));
+    }
+    let size = |offset: usize| u32_at(dbi, offset).map_or(0, |size| size as usize);
+
+    // The debug header follows the other substreams, the EC substream last of them.
+    let debug_header = 64 + size(24) + size(28) + size(32) + size(36) + size(40) + size(52);
+    for (i, what) in DEBUG_HEADER.iter().enumerate().take(size(48) / 2) {
+        name(u16_at(dbi, debug_header + 2 * i), what.to_string());
+    }
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+/// Writes one line per stream: its number, size in bytes (`nil` for nil streams) and name.
+pub fn write_streams(out: &mut dyn Write, msf: &Msf, names: &BTreeMap<usize, String>) -> io::Result<()> {
+    for index in 0..msf.stream_count() {
+        let size = msf.stream_size(index).map_or_else(|| "nil".to_string(), |size| size.to_string());
+        writeln!(out, "{:5} {:>10} {}", index, size, names.get(&index).map_or("", String::as_str))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{stream_sizes, Msf, MAGIC};
+
+    #[test]
+    fn streams() {
+        // Block 3 holds stream 2, block 4 the directory and block 5 the block map.
+        const BLOCK: usize = 512;
+        let mut data = vec![0u8; 6 * BLOCK];
+        data[..MAGIC.len()].copy_from_slice(MAGIC);
+        let mut put = |offset: usize, value: u32| data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        put(32, BLOCK as u32);
+        put(44, 20);
+        put(52, 5);
+        put(5 * BLOCK, 4);
+        for (i, &word) in [3, 0, u32::MAX, 5, 3].iter().enumerate() {
+            put(4 * BLOCK + 4 * i, word);
+        }
+        data[3 * BLOCK..3 * BLOCK + 5].copy_from_slice(b"hello");
+
+        let path = std::env::temp_dir().join(format!("pdb-addr2line-msf-{}.pdb", std::process::id()));
+        std::fs::write(&path, &data).unwrap();
+        assert_eq!(stream_sizes(&path, 20), Ok(Some(vec![0, 5])));
+        assert!(stream_sizes(&path, 16).is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        let msf = Msf::parse(data).unwrap();
+        assert_eq!(msf.stream_count(), 3);
+        assert_eq!(msf.stream_size(1), None);
+        assert_eq!(msf.read(2).unwrap().as_deref(), Some(&b"hello"[..]));
+        assert!(Msf::parse(b"not a PDB".to_vec()).is_err());
+    }
+}
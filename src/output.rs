@@ -0,0 +1,978 @@
+//! Rendering of resolved frames in the supported output formats.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::confidence;
+use crate::locals::Local;
+use crate::protobuf;
+use crate::repo::SourceRepo;
+
+/// Version of the JSON output schema, emitted as `schema_version`.
+///
+/// Bump the minor version when adding optional fields and the major version for any change that
+/// can break existing parsers, and keep `schema/output.schema.json` in sync.
+pub const SCHEMA_VERSION: &str = "1.20.0";
+
+/// The JSON Schema describing the JSON output, printed by `--schema`.
+pub const SCHEMA: &str = include_str!("../schema/output.schema.json");
+
+/// A single symbolized frame for a queried address.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    /// Name of the function (or inlined function) containing the address.
+    pub function: String,
+    /// File name and path.
+    pub file: String,
+    /// Absolute line number starting at 1. Zero means no line number.
+    pub line: u64,
+    /// Column number starting at 1, if the line information records columns.
+    pub column: Option<u32>,
+    /// Offset of the address from the start of the function, if the function start is known.
+    pub function_offset: Option<u32>,
+    /// Offset of the address from the start of the line record it was resolved with.
+    pub line_offset: Option<u32>,
+    /// Whether this frame was produced by an inline site.
+    pub inline: bool,
+    /// How the frame was resolved.
+    pub source: Source,
+    /// Where the file lives in its source repository, if the PDB is source indexed.
+    pub repo: Option<SourceRepo>,
+    /// Other functions identical COMDAT folding merged into this one, sharing its code.
+    pub aliases: Vec<String>,
+    /// The function's full signature, if requested with `--signatures` and its type is known.
+    pub signature: Option<String>,
+    /// The source language of the compiland the frame comes from, if it records one.
+    pub language: Option<&'static str>,
+    /// Whether the frame comes from the C runtime or the C++ standard library.
+    pub system: bool,
+    /// The other locations of line records covering the address, as optimized code can have
+    /// several; `file` and `line` are the deterministic primary choice among them.
+    pub candidates: Vec<(String, u64)>,
+    /// Whether the address lies in the function's prologue, before its debug start.
+    pub prologue: bool,
+}
+
+impl Frame {
+    /// The signature of the function if known, otherwise its name.
+    pub fn display_name(&self) -> &str {
+        self.signature.as_deref().unwrap_or(&self.function)
+    }
+}
+
+/// The kind of debug information a frame was resolved from, most reliable first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Source {
+    /// A line record of the containing procedure or inline site.
+    LineInfo,
+    /// A procedure record without a line record covering the address.
+    ProcedureOnly,
+    /// The closest preceding public symbol, with no procedure covering the address.
+    PublicSymbol,
+    /// Padding after the end of the named procedure, before the next symbol.
+    Padding,
+    /// The reply of the `--fallback-symbolizer` to an address the symbol file did not resolve.
+    Fallback,
+}
+
+impl Source {
+    /// The name used for this source in JSON and verbose text output.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Source::LineInfo => "line-info",
+            Source::ProcedureOnly => "procedure-only",
+            Source::PublicSymbol => "public-symbol",
+            Source::Padding => "padding",
+            Source::Fallback => "fallback",
+        }
+    }
+}
+
+/// All frames found for one queried address, outermost frame first.
+#[derive(Clone, Debug)]
+pub struct Resolution {
+    /// The queried address relative to the image base.
+    pub address: u32,
+    /// The loaded module containing the address, if it was found in a module list.
+    pub module: Option<String>,
+    /// The function the address jumps to if it is an incremental linking table entry, whose
+    /// frames are given instead of the entry's.
+    pub ilt_target: Option<u32>,
+    /// The entry of the hot-patchable function whose patch site holds the address, if the image
+    /// was given.
+    pub hotpatch_entry: Option<u32>,
+    /// Whether `--timeout` expired before the address was resolved, leaving it without frames.
+    pub timed_out: bool,
+    pub frames: Vec<Frame>,
+    /// Variables in scope at the address, if requested with `--locals`.
+    pub locals: Vec<Local>,
+    /// Lexical blocks containing the address, outermost first, if requested with `--blocks`.
+    pub blocks: Vec<Block>,
+    /// `__annotation` strings recorded within `ANNOTATION_DISTANCE` bytes of the address.
+    pub annotations: Vec<Annotation>,
+    /// The lines around the address if it is in a gap of its function's line information and
+    /// `--gap-context` asked for them.
+    pub gap: Option<Gap>,
+}
+
+impl Resolution {
+    pub fn new(address: u32, frames: Vec<Frame>) -> Self {
+        Resolution {
+            address,
+            module: None,
+            ilt_target: None,
+            hotpatch_entry: None,
+            timed_out: false,
+            frames,
+            locals: Vec::new(),
+            blocks: Vec::new(),
+            annotations: Vec::new(),
+            gap: None,
+        }
+    }
+}
+
+/// The nearest line records with a real line around an address in a gap of the line
+/// information, each as file, line and distance in bytes from the address.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gap {
+    pub before: Option<(String, u64, u64)>,
+    pub after: Option<(String, u64, u64)>,
+}
+
+/// A lexical scope (`S_BLOCK32`) within a function.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+    /// First address of the block relative to the image base.
+    pub start: u32,
+    pub len: u32,
+    /// The block's name, usually empty.
+    pub name: String,
+}
+
+/// The strings of an `__annotation` call (`S_ANNOTATION`), which drivers and telemetry code use
+/// to label checkpoints.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Annotation {
+    /// Address of the annotation relative to the image base.
+    pub address: u32,
+    pub strings: Vec<String>,
+}
+
+/// How far from a queried address annotations are still reported, in bytes either way, which
+/// covers the `int 2c` an annotation compiles to and the instructions around it.
+pub const ANNOTATION_DISTANCE: u32 = 16;
+
+/// The output formats understood by `--format`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+    /// The `Results` message of schema/results.proto.
+    Protobuf,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "protobuf" => Ok(Format::Protobuf),
+            _ => Err(format!("unknown output format '{}' (expected text, json or protobuf)", s)),
+        }
+    }
+}
+
+/// A destination for results, given as `FORMAT=PATH` to `--output`.
+#[derive(Clone, Debug)]
+pub struct Sink {
+    pub format: Format,
+    /// The file to write to, or `None` for stdout (`-`).
+    pub path: Option<PathBuf>,
+}
+
+impl FromStr for Sink {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (format, path) = match s.find('=') {
+            Some(pos) => (&s[..pos], &s[pos + 1..]),
+            None => return Err(format!("invalid output '{}' (expected FORMAT=PATH)", s)),
+        };
+        let path = match path {
+            "-" => None,
+            "" => return Err(format!("invalid output '{}' (missing path)", s)),
+            path => Some(PathBuf::from(path)),
+        };
+        Ok(Sink { format: format.parse()?, path })
+    }
+}
+
+impl Sink {
+    /// Writes `results` to this sink, creating or truncating the output file.
+    pub fn write(&self, options: &RenderOptions, results: &[Resolution]) -> io::Result<()> {
+        match self.path {
+            Some(ref path) => {
+                let mut file = io::BufWriter::new(File::create(path)?);
+                write_results(&mut file, self.format, options, results)?;
+                file.flush()
+            }
+            None => {
+                let stdout = io::stdout();
+                let mut stdout = stdout.lock();
+                write_results(&mut stdout, self.format, options, results)
+            }
+        }
+    }
+}
+
+/// The layouts of the text format understood by `--style`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Style {
+    /// `0x1234 function (file:line)`, one line per frame.
+    Default,
+    /// Numbered frames like WinDbg's `kL`: `00 module!function+0x12 [file @ line]`.
+    Windbg,
+    /// Compiler diagnostics that editors and CI annotations recognize:
+    /// `file:line:col: note: 0x1234 in function`.
+    Gcc,
+    /// The layouts of GNU `addr2line`, chosen by its `-a`, `-p`, `-i` and `--functions` flags.
+    Binutils(Binutils),
+}
+
+/// The flags of GNU `addr2line` that shape its output.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Binutils {
+    /// `-a`: print each address before its frames.
+    pub addresses: bool,
+    /// `-p`: print each frame on one line, `function at file:line`.
+    pub pretty: bool,
+    /// `-i`: print the functions the innermost one was inlined into, too.
+    pub inlines: bool,
+    /// `-f`: print function names.
+    pub functions: bool,
+    /// Hex digits of printed addresses, 8 for 32-bit images and 16 otherwise.
+    pub address_width: usize,
+    /// Image base added to the queried addresses to echo them as given.
+    pub base: u64,
+}
+
+impl FromStr for Style {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Style::Default),
+            "windbg" => Ok(Style::Windbg),
+            "gcc" => Ok(Style::Gcc),
+            "binutils" => Ok(Style::Binutils(Binutils { address_width: 16, ..Binutils::default() })),
+            _ => Err(format!("unknown output style '{}' (expected default, windbg, gcc or binutils)", s)),
+        }
+    }
+}
+
+/// Settings that affect how results are rendered, independent of the format.
+#[derive(Clone, Debug)]
+pub struct RenderOptions {
+    pub style: Style,
+    /// Module name used to qualify function names, usually the PDB file stem.
+    pub module: String,
+    /// Whether to append the resolution source of each frame to text output.
+    pub verbose: bool,
+    /// Whether to show the offsets from the start of the function and of the line record.
+    pub relative: bool,
+    /// Whether to leave out files and lines, which `--functions-only` does not read.
+    pub functions_only: bool,
+    /// Whether to show the confidence score of each frame.
+    pub confidence: bool,
+}
+
+pub fn write_results(
+    out: &mut dyn Write,
+    format: Format,
+    options: &RenderOptions,
+    results: &[Resolution],
+) -> io::Result<()> {
+    match (format, options.style) {
+        (Format::Text, Style::Default) => write_text(out, options, results),
+        (Format::Text, Style::Windbg) => write_windbg(out, &options.module, results),
+        (Format::Text, Style::Gcc) => write_gcc(out, results),
+        (Format::Text, Style::Binutils(flags)) => write_binutils(out, &flags, results),
+        (Format::Json, _) => write_json(out, options, results),
+        (Format::Protobuf, _) => protobuf::write_results(out, options, results),
+    }
+}
+
+fn write_text(out: &mut dyn Write, options: &RenderOptions, results: &[Resolution]) -> io::Result<()> {
+    for result in results {
+        if result.timed_out {
+            writeln!(out, "{:#x} timeout", result.address)?;
+        }
+        for (i, frame) in result.frames.iter().enumerate() {
+            if frame.source == Source::Padding {
+                write!(out, "{:#x} padding after {}", result.address, frame.function)?;
+            } else {
+                write!(out, "{:#x} {}", result.address, frame.display_name())?;
+                if let Some(offset) = frame.function_offset.filter(|_| options.relative) {
+                    write!(out, "+{:#x}", offset)?;
+                }
+                if !frame.aliases.is_empty() {
+                    write!(out, " [also: {}]", frame.aliases.join(", "))?;
+                }
+                if !options.functions_only {
+                    write!(out, " ({}:{}", file_or_unknown(frame), frame.line)?;
+                    if let Some(offset) = frame.line_offset.filter(|_| options.relative) {
+                        write!(out, "+{:#x}", offset)?;
+                    }
+                    write!(out, ")")?;
+                }
+            }
+            if let Some(target) = result.ilt_target.filter(|_| i == 0) {
+                write!(out, " via ILT to {:#x}", target)?;
+            }
+            if let Some(entry) = result.hotpatch_entry.filter(|_| i == 0) {
+                write!(out, " [hotpatch entry {:#x}]", entry)?;
+            }
+            if frame.system {
+                write!(out, " [system]")?;
+            }
+            if options.confidence {
+                write!(out, " [confidence {:.1}]", confidence::score(frame))?;
+            }
+            if options.verbose && !frame.candidates.is_empty() {
+                let candidates: Vec<String> = frame.candidates.iter().map(|(file, line)| format!("{}:{}", file, line)).collect();
+                write!(out, " [also at {}]", candidates.join(", "))?;
+            }
+            if options.verbose {
+                match frame.language {
+                    Some(language) => write!(out, " [{}, {}]", frame.source.as_str(), language)?,
+                    None => write!(out, " [{}]", frame.source.as_str())?,
+                }
+            }
+            writeln!(out)?;
+        }
+        for block in &result.blocks {
+            write!(out, "    block {:#x}-{:#x}", block.start, u64::from(block.start) + u64::from(block.len))?;
+            if !block.name.is_empty() {
+                write!(out, " {}", block.name)?;
+            }
+            writeln!(out)?;
+        }
+        for local in &result.locals {
+            writeln!(out, "    local {}: {}", local.name, local.location)?;
+        }
+        for annotation in &result.annotations {
+            writeln!(out, "    annotation {:#x}: {}", annotation.address, annotation.strings.join(", "))?;
+        }
+        if let Some(gap) = &result.gap {
+            let before = gap.before.as_ref().map(|(file, line, distance)| format!("{}:{} (-{:#x})", file, line, distance));
+            let after = gap.after.as_ref().map(|(file, line, distance)| format!("{}:{} (+{:#x})", file, line, distance));
+            match (before, after) {
+                (Some(before), Some(after)) => writeln!(out, "    between {} and {}", before, after)?,
+                (Some(before), None) => writeln!(out, "    after {}", before)?,
+                (None, Some(after)) => writeln!(out, "    before {}", after)?,
+                (None, None) => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes all frames as one numbered stack, innermost frame first, the way `kL` shows them.
+///
+/// The queried addresses are treated as consecutive stack entries, so frame numbers keep counting
+/// across addresses and inline frames get their own number.
+fn write_windbg(out: &mut dyn Write, module: &str, results: &[Resolution]) -> io::Result<()> {
+    let mut number = 0;
+    for result in results {
+        let module = result.module.as_deref().unwrap_or(module);
+        for frame in result.frames.iter().rev() {
+            write!(out, "{:02x} {}!{}", number, module, frame.function)?;
+            if let Some(offset) = frame.function_offset.filter(|&offset| offset != 0) {
+                write!(out, "+{:#x}", offset)?;
+            }
+            writeln!(out, " [{} @ {}]", file_or_unknown(frame), frame.line)?;
+            number += 1;
+        }
+    }
+    Ok(())
+}
+
+/// Writes one diagnostic note per frame, innermost first like a compiler's "in function" chain.
+///
+/// Frames without a recorded column get column 1, which keeps the location in the
+/// `file:line:col` form parsers expect. Frames without a file are attributed to `??:0:0`.
+fn write_gcc(out: &mut dyn Write, results: &[Resolution]) -> io::Result<()> {
+    for result in results {
+        for frame in result.frames.iter().rev() {
+            let (file, line, column) = match frame.file.as_str() {
+                "" => ("??", 0, 0),
+                file => (file, frame.line, frame.column.unwrap_or(1)),
+            };
+            let kind = if frame.inline { " (inlined)" } else { "" };
+            writeln!(out, "{}:{}:{}: note: {:#x} in {}{}", file, line, column, result.address, frame.display_name(), kind)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the results exactly as GNU `addr2line` does with the given flags.
+///
+/// Without `-p`, the address, the function and the location each take a line; with it they share
+/// one, as `0x0000000000001234: function at file:line`. Without `-i` only the innermost frame is
+/// printed; with it the frames it was inlined into follow, each marked ` (inlined by) ` with
+/// `-p`. Unknown functions print as `??`, unknown locations as `??:0` and unknown lines as `?`.
+fn write_binutils(out: &mut dyn Write, flags: &Binutils, results: &[Resolution]) -> io::Result<()> {
+    for result in results {
+        if flags.addresses {
+            let address = flags.base.wrapping_add(u64::from(result.address));
+            write!(out, "0x{:0w$x}", address, w = flags.address_width)?;
+            if flags.pretty {
+                write!(out, ": ")?;
+            } else {
+                writeln!(out)?;
+            }
+        }
+        let frames: Vec<&Frame> = result.frames.iter().rev().filter(|frame| frame.source != Source::Padding).collect();
+        if frames.is_empty() {
+            if flags.functions {
+                write!(out, "{}", if flags.pretty { "?? " } else { "??\n" })?;
+            }
+            writeln!(out, "??:0")?;
+            continue;
+        }
+        let count = if flags.inlines { frames.len() } else { 1 };
+        for (i, frame) in frames.iter().take(count).enumerate() {
+            if i > 0 && flags.pretty {
+                write!(out, " (inlined by) ")?;
+            }
+            if flags.functions {
+                write!(out, "{}", frame.function)?;
+                if flags.pretty {
+                    write!(out, " at ")?;
+                } else {
+                    writeln!(out)?;
+                }
+            }
+            let file = if frame.file.is_empty() { "??" } else { frame.file.as_str() };
+            match frame.line {
+                0 => writeln!(out, "{}:?", file)?,
+                line => writeln!(out, "{}:{}", file, line)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_json(out: &mut dyn Write, options: &RenderOptions, results: &[Resolution]) -> io::Result<()> {
+    write!(out, "{{\"schema_version\":{},\"results\":[", json_string(SCHEMA_VERSION))?;
+    for (i, result) in results.iter().enumerate() {
+        if i > 0 {
+            write!(out, ",")?;
+        }
+        write!(out, "{{\"address\":\"{:#x}\",", result.address)?;
+        if let Some(ref module) = result.module {
+            write!(out, "\"module\":{},", json_string(module))?;
+        }
+        if let Some(target) = result.ilt_target {
+            write!(out, "\"ilt_target\":\"{:#x}\",", target)?;
+        }
+        if let Some(entry) = result.hotpatch_entry {
+            write!(out, "\"hotpatch_entry\":\"{:#x}\",", entry)?;
+        }
+        if result.timed_out {
+            write!(out, "\"timeout\":true,")?;
+        }
+        write!(out, "\"frames\":[")?;
+        for (j, frame) in result.frames.iter().enumerate() {
+            if j > 0 {
+                write!(out, ",")?;
+            }
+            write!(out, "{{\"function\":{},", json_string(&frame.function))?;
+            if !options.functions_only {
+                write!(out, "\"file\":{},\"line\":{},", json_string(&frame.file), frame.line)?;
+            }
+            write!(out, "\"inline\":{},\"source\":{}", frame.inline, json_string(frame.source.as_str()))?;
+            if options.confidence {
+                write!(out, ",\"confidence\":{}", confidence::score(frame))?;
+            }
+            if let Some(ref repo) = frame.repo {
+                write!(
+                    out,
+                    ",\"repo_url\":{},\"revision\":{},\"repo_relative_path\":{}",
+                    json_string(&repo.repo_url),
+                    json_string(&repo.revision),
+                    json_string(&repo.repo_relative_path)
+                )?;
+            }
+            if !frame.aliases.is_empty() {
+                let aliases: Vec<String> = frame.aliases.iter().map(|alias| json_string(alias)).collect();
+                write!(out, ",\"aliases\":[{}]", aliases.join(","))?;
+            }
+            if !frame.candidates.is_empty() && !options.functions_only {
+                let candidates: Vec<String> = frame
+                    .candidates
+                    .iter()
+                    .map(|(file, line)| format!("{{\"file\":{},\"line\":{}}}", json_string(file), line))
+                    .collect();
+                write!(out, ",\"candidates\":[{}]", candidates.join(","))?;
+            }
+            if let Some(language) = frame.language {
+                write!(out, ",\"language\":{}", json_string(language))?;
+            }
+            if frame.system {
+                write!(out, ",\"system\":true")?;
+            }
+            if let Some(ref signature) = frame.signature {
+                write!(out, ",\"signature\":{}", json_string(signature))?;
+            }
+            if options.relative {
+                if let Some(offset) = frame.function_offset {
+                    write!(out, ",\"function_offset\":\"{:#x}\"", offset)?;
+                }
+                if let Some(offset) = frame.line_offset {
+                    write!(out, ",\"line_offset\":\"{:#x}\"", offset)?;
+                }
+            }
+            write!(out, "}}")?;
+        }
+        write!(out, "]")?;
+        if !result.blocks.is_empty() {
+            write!(out, ",\"blocks\":[")?;
+            for (j, block) in result.blocks.iter().enumerate() {
+                if j > 0 {
+                    write!(out, ",")?;
+                }
+                write!(
+                    out,
+                    "{{\"start\":\"{:#x}\",\"end\":\"{:#x}\",\"name\":{}}}",
+                    block.start,
+                    u64::from(block.start) + u64::from(block.len),
+                    json_string(&block.name)
+                )?;
+            }
+            write!(out, "]")?;
+        }
+        if !result.locals.is_empty() {
+            write!(out, ",\"locals\":[")?;
+            for (j, local) in result.locals.iter().enumerate() {
+                if j > 0 {
+                    write!(out, ",")?;
+                }
+                write!(out, "{{\"name\":{},\"location\":{}}}", json_string(&local.name), json_string(&local.location))?;
+            }
+            write!(out, "]")?;
+        }
+        if !result.annotations.is_empty() {
+            write!(out, ",\"annotations\":[")?;
+            for (j, annotation) in result.annotations.iter().enumerate() {
+                if j > 0 {
+                    write!(out, ",")?;
+                }
+                let strings: Vec<String> = annotation.strings.iter().map(|s| json_string(s)).collect();
+                write!(out, "{{\"address\":\"{:#x}\",\"strings\":[{}]}}", annotation.address, strings.join(","))?;
+            }
+            write!(out, "]")?;
+        }
+        if let Some(gap) = &result.gap {
+            write!(out, ",\"gap\":{{")?;
+            let sides = [("before", &gap.before), ("after", &gap.after)];
+            for (j, (side, (file, line, distance))) in
+                sides.iter().filter_map(|&(side, neighbour)| Some((side, neighbour.as_ref()?))).enumerate()
+            {
+                if j > 0 {
+                    write!(out, ",")?;
+                }
+                write!(out, "\"{}\":{{\"file\":{},\"line\":{},\"distance\":{}}}", side, json_string(file), line, distance)?;
+            }
+            write!(out, "}}")?;
+        }
+        write!(out, "}}")?;
+    }
+    writeln!(out, "]}}")
+}
+
+/// Writes a table of the innermost frame each build resolves each address to.
+///
+/// `builds` holds the name of each PDB with its results for `addresses`, or the error that
+/// prevented resolving against it.
+pub fn write_comparison(
+    out: &mut dyn Write,
+    addresses: &[u32],
+    builds: &[(String, Result<Vec<Resolution>, String>)],
+) -> io::Result<()> {
+    let mut rows = Vec::new();
+    for (i, &address) in addresses.iter().enumerate() {
+        for (name, results) in builds {
+            let symbol = match results {
+                Ok(results) => match results[i].frames.last() {
+                    Some(frame) if frame.source == Source::Padding => format!("padding after {}", frame.function),
+                    Some(frame) => format!("{} ({}:{})", frame.function, file_or_unknown(frame), frame.line),
+                    None => "-".to_string(),
+                },
+                Err(e) => format!("error: {}", e),
+            };
+            rows.push((format!("{:#x}", address), name.as_str(), symbol));
+        }
+    }
+
+    let address_width = rows.iter().map(|row| row.0.len()).max().unwrap_or(0).max("ADDRESS".len());
+    let name_width = rows.iter().map(|row| row.1.len()).max().unwrap_or(0).max("PDB".len());
+    writeln!(out, "{:aw$}  {:nw$}  SYMBOL", "ADDRESS", "PDB", aw = address_width, nw = name_width)?;
+    for (address, name, symbol) in rows {
+        writeln!(out, "{:aw$}  {:nw$}  {}", address, name, symbol, aw = address_width, nw = name_width)?;
+    }
+    Ok(())
+}
+
+/// Writes a table of the total weight per function, heaviest first, with `counts[i]` the weight
+/// of `results[i]`.
+///
+/// Addresses are attributed to their outermost frame, the function the code was compiled into,
+/// or with `inline` to their innermost frame. Unresolved addresses are counted as `??`.
+pub fn write_aggregate(out: &mut dyn Write, results: &[Resolution], counts: &[u64], inline: bool) -> io::Result<()> {
+    let mut totals: HashMap<String, u64> = HashMap::new();
+    for (result, &count) in results.iter().zip(counts) {
+        let frame = if inline { result.frames.last() } else { result.frames.first() };
+        let function = match frame {
+            Some(frame) if frame.source == Source::Padding => format!("padding after {}", frame.function),
+            Some(frame) => frame.function.clone(),
+            None => "??".to_string(),
+        };
+        *totals.entry(function).or_default() += count;
+    }
+    let mut rows: Vec<(String, u64)> = totals.into_iter().collect();
+    rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let total: u64 = rows.iter().map(|row| row.1).sum();
+    let count_width = rows.iter().map(|row| row.1.to_string().len()).max().unwrap_or(0).max("COUNT".len());
+    writeln!(out, "{:>cw$}  {:>7}  FUNCTION", "COUNT", "PERCENT", cw = count_width)?;
+    for (function, count) in rows {
+        let percent = if total == 0 { 0.0 } else { count as f64 * 100.0 / total as f64 };
+        writeln!(out, "{:>cw$}  {:>6.2}%  {}", count, percent, function, cw = count_width)?;
+    }
+    Ok(())
+}
+
+/// Writes the crash signature of the stack `results`, innermost address first: the function names
+/// of the first `depth` frames, innermost inline frame first, with template arguments collapsed
+/// and joined with ` | `.
+///
+/// Frames `system` accepts and addresses without symbols are left out, so the signature names
+/// the user's code and stays stable across OS updates.
+pub fn write_crash_signature(
+    out: &mut dyn Write,
+    results: &[Resolution],
+    depth: usize,
+    system: &dyn Fn(&Frame) -> bool,
+) -> io::Result<()> {
+    let names: Vec<String> = results
+        .iter()
+        .flat_map(|result| result.frames.iter().rev())
+        .filter(|frame| !system(frame))
+        .take(depth)
+        .map(|frame| collapse_template_arguments(&frame.function))
+        .collect();
+    writeln!(out, "{}", names.join(" | "))
+}
+
+/// The function names of a stack of `results`, innermost frame first, with inlined functions
+/// as frames of their own and unresolved addresses as themselves.
+fn stack_frames(results: &[Resolution]) -> Vec<String> {
+    results
+        .iter()
+        .flat_map(|result| match result.frames.len() {
+            0 => vec![format!("{:#x}", result.address)],
+            _ => result.frames.iter().rev().map(|frame| frame.function.clone()).collect(),
+        })
+        .collect()
+}
+
+/// The lengths of the common prefix and common suffix of `a` and `b`, which never overlap.
+fn common_ends<T: PartialEq>(a: &[T], b: &[T]) -> (usize, usize) {
+    let prefix = a.iter().zip(b).take_while(|(a, b)| a == b).count();
+    let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    (prefix, suffix)
+}
+
+/// Writes the stacks `a` and `b` side by side, aligned on their common top and bottom frames,
+/// with a summary of where they diverge.
+///
+/// Each row is marked `=` for a frame both stacks share, `!` where they differ and `<` or `>`
+/// where only the first or second stack has a frame.
+pub fn write_stack_diff(
+    out: &mut dyn Write,
+    names: (&str, &str),
+    a: &[Resolution],
+    b: &[Resolution],
+) -> io::Result<()> {
+    let (a, b) = (stack_frames(a), stack_frames(b));
+    let (prefix, suffix) = common_ends(&a, &b);
+    let (middle_a, middle_b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut rows: Vec<(char, &str, &str)> = a[..prefix].iter().map(|frame| ('=', frame.as_str(), frame.as_str())).collect();
+    for i in 0..middle_a.len().max(middle_b.len()) {
+        rows.push(match (middle_a.get(i), middle_b.get(i)) {
+            (Some(a), Some(b)) => ('!', a.as_str(), b.as_str()),
+            (Some(a), None) => ('<', a.as_str(), ""),
+            (None, Some(b)) => ('>', "", b.as_str()),
+            (None, None) => unreachable!("the row is within the longer middle"),
+        });
+    }
+    rows.extend(a[a.len() - suffix..].iter().map(|frame| ('=', frame.as_str(), frame.as_str())));
+
+    let width = rows.iter().map(|row| row.1.len()).max().unwrap_or(0).max(names.0.len());
+    writeln!(out, "  {:w$}  {}", names.0, names.1, w = width)?;
+    for (marker, a, b) in rows {
+        writeln!(out, "{} {:w$}  {}", marker, a, b, w = width)?;
+    }
+    if middle_a.is_empty() && middle_b.is_empty() {
+        writeln!(out, "the stacks are identical ({} frames)", a.len())
+    } else {
+        writeln!(
+            out,
+            "{} common top frames, {} common bottom frames, diverging in {} vs {} frames",
+            prefix,
+            suffix,
+            middle_a.len(),
+            middle_b.len()
+        )
+    }
+}
+
+/// Writes one `caller (file:line) -> callee` line per call edge, where `results` holds the
+/// resolved caller and callee address of each edge in turn.
+pub fn write_edges(out: &mut dyn Write, results: &[Resolution]) -> io::Result<()> {
+    for edge in results.chunks_exact(2) {
+        let caller = match edge[0].frames.last() {
+            Some(frame) => format!("{} ({}:{})", frame.function, file_or_unknown(frame), frame.line),
+            None => format!("{:#x}", edge[0].address),
+        };
+        let callee = match edge[1].frames.last() {
+            Some(frame) => frame.function.clone(),
+            None => format!("{:#x}", edge[1].address),
+        };
+        writeln!(out, "{} -> {}", caller, callee)?;
+    }
+    Ok(())
+}
+
+/// Writes one `FROM function (file:line) -> TO function (file:line) FLAG` line per branch, where
+/// `results` holds the resolved source and target of each branch in turn and `flags` the
+/// prediction flag of each branch as recorded (`M` mispredicted, `P` predicted, `-` unknown).
+pub fn write_branches(out: &mut dyn Write, results: &[Resolution], flags: &[String]) -> io::Result<()> {
+    let location = |result: &Resolution| match result.frames.last() {
+        Some(frame) => format!("{:#x} {} ({}:{})", result.address, frame.function, file_or_unknown(frame), frame.line),
+        None => format!("{:#x} ??", result.address),
+    };
+    for (branch, flag) in results.chunks_exact(2).zip(flags) {
+        writeln!(out, "{} -> {} {}", location(&branch[0]), location(&branch[1]), flag)?;
+    }
+    Ok(())
+}
+
+/// Shortens the file of every frame to the fewest trailing path components that still tell it
+/// apart from the other files in `results`, like editor tab titles do.
+///
+/// Components are compared case-insensitively, as Windows paths are; files that differ only in
+/// case keep their full path.
+pub fn shorten_files(results: &mut [Resolution]) {
+    let mut files: Vec<String> = results.iter().flat_map(|r| r.frames.iter().map(|f| f.file.clone())).collect();
+    files.sort();
+    files.dedup();
+    let components: Vec<Vec<String>> =
+        files.iter().map(|file| file.rsplit(['\\', '/']).map(str::to_lowercase).collect()).collect();
+    let mut short = HashMap::new();
+    for (i, file) in files.iter().enumerate() {
+        let mine = &components[i];
+        let unique = |count: usize| {
+            components.iter().enumerate().all(|(j, theirs)| j == i || theirs.len() < count || theirs[..count] != mine[..count])
+        };
+        let count = (1..=mine.len()).find(|&count| unique(count)).unwrap_or(mine.len());
+        // The suffix starts after the separator preceding the `count`th component from the end.
+        let start = file.rmatch_indices(['\\', '/']).nth(count - 1).map_or(0, |(index, _)| index + 1);
+        short.insert(file.clone(), file[start..].to_string());
+    }
+    for frame in results.iter_mut().flat_map(|r| r.frames.iter_mut()) {
+        if let Some(file) = short.get(&frame.file) {
+            frame.file = file.clone();
+        }
+    }
+}
+
+/// Folds the template arguments in the function names of `results` to `<...>`, as
+/// `--collapse-templates` shows them.
+pub fn collapse_templates(results: &mut [Resolution]) {
+    for frame in results.iter_mut().flat_map(|r| r.frames.iter_mut()) {
+        frame.function = collapse_template_arguments(&frame.function);
+        if let Some(ref mut signature) = frame.signature {
+            *signature = collapse_template_arguments(signature);
+        }
+    }
+}
+
+/// Folds every outermost template argument list in `name` to `<...>`, so
+/// `std::vector<int,std::allocator<int> >::push_back` becomes `std::vector<...>::push_back`.
+///
+/// Only a `<` following a name opens an argument list; the angle brackets of `operator<<` and
+/// the like, and of MSVC's `<lambda_1>` names, are kept.
+fn collapse_template_arguments(name: &str) -> String {
+    const OPERATORS: [&str; 11] = ["<=>", "<<=", "<<", "<=", "<", ">>=", ">>", ">=", ">", "->*", "->"];
+    let mut out = String::with_capacity(name.len());
+    let mut rest = name;
+    let mut after_operator = false;
+    while let Some(c) = rest.chars().next() {
+        if out.ends_with("operator") {
+            if let Some(operator) = OPERATORS.iter().find(|&operator| rest.starts_with(operator)) {
+                out.push_str(operator);
+                rest = &rest[operator.len()..];
+                after_operator = true;
+                continue;
+            }
+        }
+        let opens_arguments = after_operator || out.ends_with(|p: char| p.is_alphanumeric() || p == '_' || p == '>');
+        after_operator = false;
+        if c == '<' && opens_arguments {
+            let mut depth = 0;
+            let end = rest.char_indices().find_map(|(i, c)| {
+                match c {
+                    '<' => depth += 1,
+                    '>' => depth -= 1,
+                    _ => {}
+                }
+                if depth == 0 {
+                    Some(i + 1)
+                } else {
+                    None
+                }
+            });
+            match end {
+                Some(end) => {
+                    out.push_str("<...>");
+                    rest = &rest[end..];
+                    continue;
+                }
+                // Unbalanced, as in a truncated name: keep the rest as it is.
+                None => break,
+            }
+        }
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The file name to show in text output, `??` if the frame has no line information.
+fn file_or_unknown(frame: &Frame) -> &str {
+    if frame.file.is_empty() {
+        "??"
+    } else {
+        &frame.file
+    }
+}
+
+/// Quotes and escapes `s` as a JSON string literal.
+pub fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(file: &str) -> Frame {
+        Frame {
+            function: "f".to_string(),
+            file: file.to_string(),
+            line: 1,
+            column: None,
+            function_offset: None,
+            line_offset: None,
+            inline: false,
+            source: Source::LineInfo,
+            repo: None,
+            aliases: Vec::new(),
+            signature: None,
+            language: None,
+            system: false,
+            candidates: Vec::new(),
+            prologue: false,
+        }
+    }
+
+    #[test]
+    fn shortest_unique_suffixes() {
+        let files = [r"C:\src\app\main.cpp", r"C:\src\lib\util.cpp", r"C:\src\app\util.cpp", "/usr/include/vector", ""];
+        let mut results = vec![Resolution::new(0, files.iter().map(|file| frame(file)).collect())];
+        shorten_files(&mut results);
+        let short: Vec<&str> = results[0].frames.iter().map(|frame| frame.file.as_str()).collect();
+        assert_eq!(short, vec!["main.cpp", r"lib\util.cpp", r"app\util.cpp", "vector", ""]);
+    }
+
+    #[test]
+    fn collapsed_template_arguments() {
+        assert_eq!(
+            collapse_template_arguments("std::vector<int,std::allocator<int> >::push_back"),
+            "std::vector<...>::push_back"
+        );
+        assert_eq!(collapse_template_arguments("std::operator<<<std::char_traits<char> >"), "std::operator<<<...>");
+        assert_eq!(collapse_template_arguments("`anonymous namespace'::<lambda_1>::operator()"), "`anonymous namespace'::<lambda_1>::operator()");
+        assert_eq!(collapse_template_arguments("Map<K,V>::Find<Key>"), "Map<...>::Find<...>");
+    }
+
+    #[test]
+    fn binutils_layouts() {
+        let mut inlined = frame("a.h");
+        inlined.function = "inl".to_string();
+        inlined.line = 3;
+        inlined.inline = true;
+        let mut outer = frame("a.c");
+        outer.function = "main".to_string();
+        outer.line = 10;
+        let results = vec![Resolution::new(0x1004, vec![outer, inlined]), Resolution::new(0x2000, Vec::new())];
+        let render = |addresses, pretty, inlines, functions| {
+            let flags = Binutils { addresses, pretty, inlines, functions, address_width: 16, base: 0x1_4000_0000 };
+            let mut out = Vec::new();
+            write_binutils(&mut out, &flags, &results).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        // As GNU addr2line prints them, innermost frame first.
+        assert_eq!(render(true, false, false, false), "0x0000000140001004\na.h:3\n0x0000000140002000\n??:0\n");
+        assert_eq!(render(true, true, false, false), "0x0000000140001004: a.h:3\n0x0000000140002000: ??:0\n");
+        assert_eq!(render(true, false, true, false), "0x0000000140001004\na.h:3\na.c:10\n0x0000000140002000\n??:0\n");
+        assert_eq!(render(false, true, true, false), "a.h:3\n (inlined by) a.c:10\n??:0\n");
+        assert_eq!(
+            render(true, true, true, true),
+            "0x0000000140001004: inl at a.h:3\n (inlined by) main at a.c:10\n0x0000000140002000: ?? ??:0\n"
+        );
+    }
+
+    #[test]
+    fn common_stack_ends() {
+        assert_eq!(common_ends(&["crash", "a", "main"], &["crash", "b", "c", "main"]), (1, 1));
+        assert_eq!(common_ends(&["f", "main"], &["f", "main"]), (2, 0));
+        // A frame repeated at both ends of a shorter stack is not counted twice.
+        assert_eq!(common_ends(&["f"], &["f", "f"]), (1, 0));
+    }
+}
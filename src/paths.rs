@@ -0,0 +1,78 @@
+//! Path handling for PDBs on symbol shares with long or UNC paths.
+
+use std::path::{Path, PathBuf};
+
+/// Returns a form of `path` that the file system APIs accept beyond `MAX_PATH` (260 characters).
+///
+/// On Windows, absolute paths become verbatim `\\?\` paths and UNC paths `\\?\UNC\` paths;
+/// relative paths are made absolute first. Elsewhere the path is returned unchanged.
+pub fn long_path(path: &Path) -> PathBuf {
+    if cfg!(windows) {
+        let absolute = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            match std::env::current_dir() {
+                Ok(dir) => dir.join(path),
+                Err(_) => return path.to_path_buf(),
+            }
+        };
+        match absolute.to_str() {
+            Some(absolute) => PathBuf::from(verbatim(absolute)),
+            None => absolute,
+        }
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Converts an absolute Windows path to its verbatim form.
+///
+/// Verbatim paths skip the normalization Windows otherwise applies, so separators are made
+/// uniform and `.` and `..` components are resolved here. Paths that already are verbatim or
+/// device paths, or that are not absolute, are returned unchanged.
+fn verbatim(path: &str) -> String {
+    if path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+        return path.to_string();
+    }
+    let path = path.replace('/', "\\");
+    let (prefix, rest) = if let Some(unc) = path.strip_prefix(r"\\") {
+        (r"\\?\UNC\", unc)
+    } else if path.len() >= 3 && path.as_bytes()[1] == b':' && path.as_bytes()[2] == b'\\' {
+        (r"\\?\", &path[..])
+    } else {
+        return path;
+    };
+
+    let mut components: Vec<&str> = Vec::new();
+    for component in rest.split('\\') {
+        match component {
+            "" | "." => {}
+            // Never pop the drive or the UNC server and share.
+            ".." if components.len() > if prefix.ends_with(r"UNC\") { 2 } else { 1 } => {
+                components.pop();
+            }
+            ".." => {}
+            component => components.push(component),
+        }
+    }
+    format!("{}{}", prefix, components.join("\\"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::verbatim;
+
+    #[test]
+    fn drive_and_unc_paths() {
+        assert_eq!(verbatim(r"C:\symbols\app.pdb"), r"\\?\C:\symbols\app.pdb");
+        assert_eq!(verbatim(r"\\server\share\app.pdb"), r"\\?\UNC\server\share\app.pdb");
+        assert_eq!(verbatim(r"\\?\C:\app.pdb"), r"\\?\C:\app.pdb");
+    }
+
+    #[test]
+    fn components_are_normalized() {
+        assert_eq!(verbatim("C:/a/./b/../app.pdb"), r"\\?\C:\a\app.pdb");
+        assert_eq!(verbatim(r"C:\..\app.pdb"), r"\\?\C:\app.pdb");
+        assert_eq!(verbatim(r"\\server\share\..\..\app.pdb"), r"\\?\UNC\server\share\app.pdb");
+    }
+}
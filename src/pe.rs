@@ -0,0 +1,215 @@
+//! Just enough PE parsing to find the PDB an executable was linked with and to follow its
+//! incremental linking thunks.
+
+use std::convert::TryInto;
+use std::fs;
+use std::path::Path;
+
+use crate::paths;
+
+/// The CodeView (`RSDS`) debug record of an image, naming its PDB.
+#[derive(Clone, Debug)]
+pub struct CodeView {
+    /// The PDB's GUID in the byte order it is stored in.
+    pub guid: [u8; 16],
+    pub age: u32,
+    /// The PDB path as written by the linker, often absolute to the build machine.
+    pub pdb_path: String,
+}
+
+impl CodeView {
+    /// The identifier symbol stores file the matching PDB under, see `store::debug_id`.
+    pub fn debug_id(&self) -> String {
+        let g = &self.guid;
+        let data1 = u32::from_le_bytes([g[0], g[1], g[2], g[3]]);
+        let data2 = u16::from_le_bytes([g[4], g[5]]);
+        let data3 = u16::from_le_bytes([g[6], g[7]]);
+        let rest: String = g[8..].iter().map(|b| format!("{:02X}", b)).collect();
+        format!("{:08X}{:04X}{:04X}{}{:X}", data1, data2, data3, rest, self.age)
+    }
+
+    /// The file name of the PDB, without the directories of the build machine.
+    pub fn pdb_name(&self) -> &str {
+        self.pdb_path.rsplit(['\\', '/']).next().unwrap_or(&self.pdb_path)
+    }
+}
+
+/// Returns whether the file at `path` starts like a PE image (`MZ`).
+pub fn is_pe(path: &Path) -> bool {
+    use std::io::Read;
+    let mut magic = [0u8; 2];
+    fs::File::open(paths::long_path(path)).and_then(|mut file| file.read_exact(&mut magic)).is_ok() && &magic == b"MZ"
+}
+
+/// Reads the CodeView record from the debug directory of the image at `path`.
+pub fn read_codeview(path: &Path) -> Result<CodeView, String> {
+    let data = fs::read(paths::long_path(path)).map_err(|e| e.to_string())?;
+    parse_codeview(&data).ok_or_else(|| "no CodeView debug record (linked without /DEBUG?)".to_string())
+}
+
+/// Returns the target of the `jmp rel32` instruction at `rva` in the image `data`, the form the
+/// entries of the incremental linking table (ILT) take, or `None` if there is no such jump.
+pub fn jump_target(data: &[u8], rva: u32) -> Option<u32> {
+    let offset = file_offset(data, rva)?;
+    let code = data.get(offset..offset + 5)?;
+    if code[0] != 0xe9 {
+        return None;
+    }
+    let displacement = i32::from_le_bytes(code[1..5].try_into().ok()?);
+    rva.checked_add(5)?.checked_add_signed(displacement)
+}
+
+/// Returns the entry of the hot-patchable function whose patch site contains `rva` in the image
+/// `data`, if any.
+///
+/// A patch site is a two-byte first instruction, which a patch replaces with a short jump back,
+/// and the five bytes of padding before the function (`/FUNCTIONPADMIN`), where the patch puts a
+/// long jump. The first instruction is recognized as `mov edi, edi` (`8b ff`), which `/hotpatch`
+/// puts at the start of x86 functions, or as the two-byte `xchg ax, ax` (`66 90`); x64 compilers
+/// just make sure the first instruction is long enough, which the bytes alone cannot show.
+pub fn hotpatch_entry(data: &[u8], rva: u32) -> Option<u32> {
+    const PADDING: u32 = 5;
+    (rva.saturating_sub(1)..=rva.checked_add(PADDING)?).find(|&entry| {
+        if entry < PADDING || rva >= entry + 2 {
+            return false;
+        }
+        let offset = match file_offset(data, entry - PADDING) {
+            Some(offset) => offset,
+            None => return false,
+        };
+        match data.get(offset..offset + PADDING as usize + 2) {
+            Some(code) => {
+                let (padding, prologue) = code.split_at(PADDING as usize);
+                (padding.iter().all(|&b| b == 0xcc) || padding.iter().all(|&b| b == 0x90))
+                    && (prologue == [0x8b, 0xff] || prologue == [0x66, 0x90])
+            }
+            None => false,
+        }
+    })
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+/// Returns the offset of the optional header and of its data directories.
+fn optional_header(data: &[u8]) -> Option<(usize, usize)> {
+    let pe = u32_at(data, 0x3c)? as usize;
+    if data.get(pe..pe + 4)? != b"PE\0\0" {
+        return None;
+    }
+    let optional = pe + 24;
+    let directories = match u16_at(data, optional)? {
+        0x10b => optional + 96,
+        0x20b => optional + 112,
+        _ => return None,
+    };
+    Some((optional, directories))
+}
+
+/// Maps `rva` to a file offset through the section table.
+fn file_offset(data: &[u8], rva: u32) -> Option<usize> {
+    let (optional, _) = optional_header(data)?;
+    let pe = optional - 24;
+    let sections = u16_at(data, pe + 6)? as usize;
+    let table = optional + u16_at(data, pe + 20)? as usize;
+    (0..sections).find_map(|i| {
+        let header = table + i * 40;
+        let virtual_size = u32_at(data, header + 8)?;
+        let virtual_address = u32_at(data, header + 12)?;
+        let raw_pointer = u32_at(data, header + 20)?;
+        if rva >= virtual_address && rva - virtual_address < virtual_size {
+            Some(raw_pointer.checked_add(rva - virtual_address)? as usize)
+        } else {
+            None
+        }
+    })
+}
+
+fn parse_codeview(data: &[u8]) -> Option<CodeView> {
+    let u32_at = |offset: usize| u32_at(data, offset);
+
+    let (_, directories) = optional_header(data)?;
+    const DEBUG_DIRECTORY: usize = 6;
+    let debug_rva = u32_at(directories + DEBUG_DIRECTORY * 8)?;
+    let debug_size = u32_at(directories + DEBUG_DIRECTORY * 8 + 4)? as usize;
+    let debug = file_offset(data, debug_rva)?;
+
+    const IMAGE_DEBUG_TYPE_CODEVIEW: u32 = 2;
+    (0..debug_size / 28).find_map(|i| {
+        let entry = debug + i * 28;
+        if u32_at(entry + 12)? != IMAGE_DEBUG_TYPE_CODEVIEW {
+            return None;
+        }
+        let size = u32_at(entry + 16)? as usize;
+        let record = data.get(u32_at(entry + 24)? as usize..)?.get(..size)?;
+        if record.get(..4)? != b"RSDS" {
+            return None;
+        }
+        let path = record.get(24..)?;
+        let path = &path[..path.iter().position(|&b| b == 0).unwrap_or(path.len())];
+        Some(CodeView {
+            guid: record[4..20].try_into().ok()?,
+            age: u32::from_le_bytes(record[20..24].try_into().ok()?),
+            pdb_path: String::from_utf8_lossy(path).into_owned(),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{file_offset, parse_codeview};
+
+    /// A PE32+ image with one section mapping RVA 0x1000 to file offset 0x200, which holds the
+    /// debug directory and the CodeView record of `app.pdb`.
+    fn image() -> Vec<u8> {
+        let mut data = vec![0u8; 0x300];
+        let mut put = |offset: usize, bytes: &[u8]| data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        put(0, b"MZ");
+        put(0x3c, &0x40u32.to_le_bytes());
+        put(0x40, b"PE\0\0");
+        put(0x40 + 6, &1u16.to_le_bytes());
+        put(0x40 + 20, &0xf0u16.to_le_bytes());
+        put(0x58, &0x20bu16.to_le_bytes());
+        // The debug data directory, the seventh.
+        put(0xc8 + 6 * 8, &0x1000u32.to_le_bytes());
+        put(0xc8 + 6 * 8 + 4, &28u32.to_le_bytes());
+        // The section header.
+        put(0x148 + 8, &0x100u32.to_le_bytes());
+        put(0x148 + 12, &0x1000u32.to_le_bytes());
+        put(0x148 + 20, &0x200u32.to_le_bytes());
+        // The debug directory entry and the CodeView record it points to.
+        put(0x200 + 12, &2u32.to_le_bytes());
+        put(0x200 + 16, &40u32.to_le_bytes());
+        put(0x200 + 24, &0x220u32.to_le_bytes());
+        put(0x220, b"RSDS");
+        put(0x224, &[0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff, 0x00]);
+        put(0x234, &3u32.to_le_bytes());
+        put(0x238, b"C:\\out\\app.pdb\0");
+        data
+    }
+
+    #[test]
+    fn codeview_record() {
+        let codeview = parse_codeview(&image()).unwrap();
+        assert_eq!(codeview.pdb_path, r"C:\out\app.pdb");
+        assert_eq!(codeview.pdb_name(), "app.pdb");
+        assert_eq!(codeview.debug_id(), "443322116655887799AABBCCDDEEFF003");
+        assert!(parse_codeview(b"MZ").is_none());
+    }
+
+    #[test]
+    fn file_offsets() {
+        let mut data = image();
+        assert_eq!(file_offset(&data, 0x1010), Some(0x210));
+        assert_eq!(file_offset(&data, 0x1100), None);
+        assert_eq!(file_offset(&data, 0xfff), None);
+        // A raw data pointer near the end of the address space does not wrap around.
+        data[0x148 + 20..0x148 + 24].copy_from_slice(&0xffff_fff0u32.to_le_bytes());
+        assert_eq!(file_offset(&data, 0x1020), None);
+    }
+}
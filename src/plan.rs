@@ -0,0 +1,81 @@
+//! `--dry-run`: what resolving the addresses would read, without resolving them.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use pdb::{FallibleIterator, PDB};
+
+use crate::paths;
+use crate::provider::{self, ProviderOptions};
+use crate::resolve;
+use crate::sqlite;
+use crate::warm;
+
+/// Writes which symbol files resolving `addresses` would open, which modules of a PDB contain
+/// the addresses and how much would be read.
+///
+/// `input` is the file named on the command line and `path` the symbol file located for it. A
+/// PDB is planned to be read from its warm database where `provider::open` would read that.
+pub fn write_plan(
+    out: &mut dyn Write,
+    input: &Path,
+    path: &Path,
+    addresses: &[u32],
+    options: &ProviderOptions,
+) -> Result<(), String> {
+    let error = |e: std::io::Error| e.to_string();
+    if input != path {
+        writeln!(out, "image: {}", input.display()).map_err(error)?;
+    }
+    let size = fs::metadata(paths::long_path(path)).map_err(|e| format!("cannot open '{}': {}", path.display(), e))?.len();
+    writeln!(out, "symbol file: {} ({} bytes)", path.display(), size).map_err(error)?;
+    let is_sym = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("sym"));
+    let is_database = sqlite::is_database(path);
+    let sym = path.with_extension("sym");
+    if !is_sym && !is_database && paths::long_path(&sym).is_file() {
+        writeln!(out, "merged with: {}", sym.display()).map_err(error)?;
+    }
+    let warm = !is_sym && !is_database && provider::warm_database(path, options).is_some();
+    if warm {
+        writeln!(out, "read from: {} (written by warm)", warm::database_path(path).display()).map_err(error)?;
+    }
+    writeln!(out, "addresses: {}", addresses.len()).map_err(error)?;
+    if is_sym || is_database || warm {
+        let parsed = if warm { "database" } else { "symbol file" };
+        writeln!(out, "estimate: the whole {} is parsed", parsed).map_err(error)?;
+        return Ok(());
+    }
+
+    let (total, read) =
+        provider::guard(path, options.limits, || modules_read(path, addresses).map_err(|e| e.to_string()))?;
+    match read {
+        Some(read) => {
+            writeln!(out, "modules: {} of {} are read for the queried addresses", read.len(), total).map_err(error)?;
+            for name in &read {
+                writeln!(out, "  {}", name).map_err(error)?;
+            }
+            writeln!(out, "estimate: the symbols and line programs of {} modules are read", read.len()).map_err(error)?;
+        }
+        None => {
+            writeln!(out, "modules: {} (no section contributions to match addresses with)", total).map_err(error)?;
+            writeln!(out, "estimate: the symbols and line programs of all {} modules are read", total).map_err(error)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the number of modules in the PDB and the names of those resolving `addresses` reads,
+/// or `None` for those if the section contributions cannot be read.
+fn modules_read(path: &Path, addresses: &[u32]) -> pdb::Result<(usize, Option<BTreeSet<String>>)> {
+    let mut pdb = PDB::open(fs::File::open(paths::long_path(path))?)?;
+    let address_map = pdb.address_map()?;
+    let dbi = pdb.debug_information()?;
+    let modules: Vec<String> = dbi.modules()?.map(|module| Ok(module.module_name().into_owned())).collect()?;
+
+    let queries = resolve::sort_queries(addresses.iter().copied());
+    let read = crate::modules_to_read(&dbi, &address_map, &queries)
+        .map(|read| read.iter().filter_map(|&index| modules.get(index).cloned()).collect());
+    Ok((modules.len(), read))
+}
@@ -0,0 +1,236 @@
+//! `--format protobuf`: the results as one `Results` message of schema/results.proto, for
+//! pipelines processing more stacks than parsing JSON keeps up with, and the address map and
+//! capabilities as `AddressMap` and `Capabilities` messages.
+//!
+//! Only the wire format the messages need is written: varints, doubles and length-delimited
+//! strings and messages.
+
+use std::io::{self, Write};
+
+use crate::addrmap::{AddressTables, Section};
+use crate::capabilities;
+use crate::confidence;
+use crate::output::{RenderOptions, Resolution, SCHEMA_VERSION};
+
+/// The wire types of the field keys written.
+const VARINT: u64 = 0;
+const FIXED64: u64 = 1;
+const LENGTH_DELIMITED: u64 = 2;
+
+/// An encoded message under construction.
+#[derive(Default)]
+struct Message(Vec<u8>);
+
+impl Message {
+    fn raw_varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+
+    fn key(&mut self, field: u64, wire_type: u64) {
+        self.raw_varint(field << 3 | wire_type);
+    }
+
+    fn varint(&mut self, field: u64, value: u64) {
+        self.key(field, VARINT);
+        self.raw_varint(value);
+    }
+
+    /// Writes `value` unless it is false, the default proto3 leaves out.
+    fn flag(&mut self, field: u64, value: bool) {
+        if value {
+            self.varint(field, 1);
+        }
+    }
+
+    fn double(&mut self, field: u64, value: f64) {
+        self.key(field, FIXED64);
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn bytes(&mut self, field: u64, bytes: &[u8]) {
+        self.key(field, LENGTH_DELIMITED);
+        self.raw_varint(bytes.len() as u64);
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn string(&mut self, field: u64, value: &str) {
+        self.bytes(field, value.as_bytes());
+    }
+
+    fn message(&mut self, field: u64, message: Message) {
+        self.bytes(field, &message.0);
+    }
+}
+
+/// Writes `results` as a `Results` message, with the same fields as the JSON output.
+pub fn write_results(out: &mut dyn Write, options: &RenderOptions, results: &[Resolution]) -> io::Result<()> {
+    let mut message = Message::default();
+    message.string(1, SCHEMA_VERSION);
+    for result in results {
+        message.message(2, encode_result(options, result));
+    }
+    out.write_all(&message.0)
+}
+
+fn encode_result(options: &RenderOptions, result: &Resolution) -> Message {
+    let mut message = Message::default();
+    message.varint(1, result.address.into());
+    if let Some(ref module) = result.module {
+        message.string(2, module);
+    }
+    if let Some(target) = result.ilt_target {
+        message.varint(3, target.into());
+    }
+    if let Some(entry) = result.hotpatch_entry {
+        message.varint(4, entry.into());
+    }
+    message.flag(5, result.timed_out);
+    for frame in &result.frames {
+        let mut encoded = Message::default();
+        encoded.string(1, &frame.function);
+        if !options.functions_only {
+            encoded.string(2, &frame.file);
+            encoded.varint(3, frame.line);
+        }
+        encoded.flag(4, frame.inline);
+        encoded.string(5, frame.source.as_str());
+        if options.confidence {
+            encoded.double(6, confidence::score(frame));
+        }
+        if let Some(ref repo) = frame.repo {
+            encoded.string(7, &repo.repo_url);
+            encoded.string(8, &repo.revision);
+            encoded.string(9, &repo.repo_relative_path);
+        }
+        for alias in &frame.aliases {
+            encoded.string(10, alias);
+        }
+        for (file, line) in frame.candidates.iter().filter(|_| !options.functions_only) {
+            let mut location = Message::default();
+            location.string(1, file);
+            location.varint(2, *line);
+            encoded.message(11, location);
+        }
+        if let Some(language) = frame.language {
+            encoded.string(12, language);
+        }
+        encoded.flag(13, frame.system);
+        if let Some(ref signature) = frame.signature {
+            encoded.string(14, signature);
+        }
+        if options.relative {
+            if let Some(offset) = frame.function_offset {
+                encoded.varint(15, offset.into());
+            }
+            if let Some(offset) = frame.line_offset {
+                encoded.varint(16, offset.into());
+            }
+        }
+        message.message(6, encoded);
+    }
+    for block in &result.blocks {
+        let mut encoded = Message::default();
+        encoded.varint(1, block.start.into());
+        encoded.varint(2, u64::from(block.start) + u64::from(block.len));
+        encoded.string(3, &block.name);
+        message.message(7, encoded);
+    }
+    for local in &result.locals {
+        let mut encoded = Message::default();
+        encoded.string(1, &local.name);
+        encoded.string(2, &local.location);
+        message.message(8, encoded);
+    }
+    for annotation in &result.annotations {
+        let mut encoded = Message::default();
+        encoded.varint(1, annotation.address.into());
+        for string in &annotation.strings {
+            encoded.string(2, string);
+        }
+        message.message(9, encoded);
+    }
+    if let Some(ref gap) = result.gap {
+        let mut encoded = Message::default();
+        for (field, side) in [(1, &gap.before), (2, &gap.after)] {
+            if let Some((file, line, distance)) = side {
+                let mut neighbour = Message::default();
+                neighbour.string(1, file);
+                neighbour.varint(2, *line);
+                neighbour.varint(3, *distance);
+                encoded.message(field, neighbour);
+            }
+        }
+        message.message(10, encoded);
+    }
+    message
+}
+
+/// Writes the address translation tables of `--dump-address-map` as an `AddressMap` message.
+pub fn write_address_map(out: &mut dyn Write, tables: &AddressTables) -> io::Result<()> {
+    let section = |section: &Section| {
+        let mut message = Message::default();
+        message.string(1, &section.name);
+        message.varint(2, section.rva.into());
+        message.varint(3, section.size.into());
+        message
+    };
+    let mut message = Message::default();
+    for (field, sections) in [(1, &tables.sections[..]), (2, tables.original_sections.as_deref().unwrap_or_default())] {
+        for s in sections {
+            message.message(field, section(s));
+        }
+    }
+    for (field, records) in [(3, &tables.omap_to_source), (4, &tables.omap_from_source)] {
+        for &(from, to) in records {
+            let mut record = Message::default();
+            record.varint(1, from.into());
+            record.varint(2, to.into());
+            message.message(field, record);
+        }
+    }
+    out.write_all(&message.0)
+}
+
+/// Writes what the `capabilities` subcommand reports as a `Capabilities` message.
+pub fn write_capabilities(out: &mut dyn Write) -> io::Result<()> {
+    // Map entries are messages of the key and the value.
+    let flags = |message: &mut Message, field, flags: &[(&str, bool)]| {
+        for &(name, on) in flags {
+            let mut entry = Message::default();
+            entry.string(1, name);
+            entry.flag(2, on);
+            message.message(field, entry);
+        }
+    };
+    let mut message = Message::default();
+    message.string(1, capabilities::VERSION);
+    message.string(2, capabilities::GIT_HASH);
+    flags(&mut message, 3, capabilities::FEATURES);
+    for version in capabilities::SCHEMA_VERSIONS {
+        message.string(4, version);
+    }
+    flags(&mut message, 5, capabilities::ABILITIES);
+    for method in capabilities::RPC_METHODS {
+        message.string(6, method);
+    }
+    out.write_all(&message.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Message;
+
+    #[test]
+    fn wire_format() {
+        let mut message = Message::default();
+        message.varint(1, 300);
+        message.string(2, "hi");
+        message.flag(3, false);
+        message.flag(4, true);
+        assert_eq!(message.0, [0x08, 0xac, 0x02, 0x12, 0x02, b'h', b'i', 0x20, 0x01]);
+    }
+}
@@ -0,0 +1,318 @@
+//! The interface between symbol file formats and address resolution.
+
+use std::fs;
+use std::io::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::breakpad::BreakpadProvider;
+use crate::db::DbProvider;
+use crate::msf;
+use crate::output::{Frame, Resolution, Source};
+use crate::paths;
+use crate::resolve::Boundary;
+use crate::sqlite;
+use crate::PdbProvider;
+use crate::warm;
+
+/// A source of symbols for one module that can resolve addresses relative to its image base.
+pub trait SymbolProvider {
+    /// Short name of the symbol format, used in messages.
+    fn kind(&self) -> &'static str;
+
+    /// Resolves each address in `addresses`, returning one result per address in the same order.
+    ///
+    /// The addresses are consumed as they are needed, so callers can parse them lazily.
+    fn resolve(&mut self, addresses: &mut dyn Iterator<Item = u32>) -> Result<Vec<Resolution>, String>;
+}
+
+/// Settings shared by all providers.
+#[derive(Clone, Copy, Debug)]
+pub struct ProviderOptions {
+    pub boundary: Boundary,
+    /// Resource limits for symbol files from untrusted sources, see `GuardedProvider`.
+    pub limits: Option<Limits>,
+    /// Whether to collect the local variables in scope at each address.
+    pub locals: bool,
+    /// Whether to collect the lexical blocks containing each address.
+    pub blocks: bool,
+    /// Whether to rebuild function signatures from the type records.
+    pub signatures: bool,
+    /// Whether to skip reading the line programs of procedures, for function names alone.
+    pub functions_only: bool,
+    /// Whether to find the lines around addresses in gaps of the line information.
+    pub gap_context: bool,
+    /// Whether PDBs may be read from the databases `warm` wrote next to them, see
+    /// `warm_database`.
+    pub warm_databases: bool,
+}
+
+/// Bounds on the resources a symbol file may make the resolver use.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// Largest symbol file accepted, in bytes.
+    pub max_file_size: u64,
+    /// Largest stream of a PDB accepted, in bytes, as its stream directory records it.
+    pub max_stream_size: u64,
+    /// Largest sum of the sizes of the streams of a PDB accepted, in bytes. Streams can share
+    /// blocks, so this bounds the memory reading them takes where the file size does not.
+    pub max_total_stream_size: u64,
+    /// Most addresses resolved in one run.
+    pub max_addresses: usize,
+    /// Deepest nesting of symbol scopes followed within a module.
+    pub max_scope_depth: usize,
+    /// Longest function or file name accepted; PDBs with longer ones are rejected as the names
+    /// are read.
+    pub max_string_length: usize,
+}
+
+impl Limits {
+    /// The limits applied by `--untrusted`, generous for real PDBs of large applications.
+    pub const UNTRUSTED: Limits = Limits {
+        max_file_size: 4 << 30,
+        max_stream_size: 1 << 30,
+        max_total_stream_size: 8 << 30,
+        max_addresses: 1 << 20,
+        max_scope_depth: 256,
+        max_string_length: 4096,
+    };
+}
+
+/// Opens the symbol file at `path` with the provider matching its format.
+///
+/// SQLite databases written by `--export-sqlite` are read as such, files with a `.sym` extension
+/// as Breakpad symbols and everything else as a PDB, or from its database as `warm_database`
+/// decides. A PDB with a `.sym` file of the same name next to it is merged with it, see
+/// `MergedProvider`.
+pub fn open(path: &Path, options: &ProviderOptions) -> Box<dyn SymbolProvider> {
+    let provider = open_unguarded(path, options);
+    match options.limits {
+        Some(limits) => Box::new(GuardedProvider { inner: provider, path: path.to_path_buf(), limits }),
+        None => provider,
+    }
+}
+
+fn open_unguarded(path: &Path, options: &ProviderOptions) -> Box<dyn SymbolProvider> {
+    let path = path.to_path_buf();
+    let options = *options;
+    if sqlite::is_database(&path) {
+        return Box::new(DbProvider::new(path, options));
+    }
+    if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("sym")) {
+        return Box::new(BreakpadProvider { path, options });
+    }
+    let sym = path.with_extension("sym");
+    let pdb: Box<dyn SymbolProvider> = match warm_database(&path, &options) {
+        Some(database) => Box::new(database),
+        None => Box::new(PdbProvider::new(path, options)),
+    };
+    if paths::long_path(&sym).is_file() {
+        merge(pdb, open_unguarded(&sym, &options))
+    } else {
+        pdb
+    }
+}
+
+/// The database `warm` wrote for the PDB at `pdb`, already read, if the PDB is to be read from
+/// it: `options` allow it and need nothing only the PDB has, and the database is current, of
+/// the schema version this build writes and readable. Otherwise the PDB is read itself.
+///
+/// The database answers as the PDB would, but holds no locals, blocks, types or gaps, and its
+/// names and scopes are not bounded by `Limits`.
+pub fn warm_database(pdb: &Path, options: &ProviderOptions) -> Option<DbProvider> {
+    let needs_pdb =
+        options.locals || options.blocks || options.signatures || options.gap_context || options.limits.is_some();
+    let database = warm::database_path(pdb);
+    if !options.warm_databases || needs_pdb || !warm::is_current(pdb) || !sqlite::is_database(&database) {
+        return None;
+    }
+    DbProvider::warm(database, *options).ok()
+}
+
+/// Enforces `Limits` around another provider and turns its panics into errors.
+///
+/// Together with the scope depth and name length limits the PDB reader applies itself, this
+/// lets a service symbolize user-uploaded files without a malformed one taking it down.
+pub struct GuardedProvider {
+    inner: Box<dyn SymbolProvider>,
+    path: PathBuf,
+    limits: Limits,
+}
+
+impl SymbolProvider for GuardedProvider {
+    fn kind(&self) -> &'static str {
+        self.inner.kind()
+    }
+
+    fn resolve(&mut self, addresses: &mut dyn Iterator<Item = u32>) -> Result<Vec<Resolution>, String> {
+        let addresses: Vec<u32> = addresses.take(self.limits.max_addresses + 1).collect();
+        if addresses.len() > self.limits.max_addresses {
+            return Err(format!("more than {} addresses", self.limits.max_addresses));
+        }
+        let inner = &mut self.inner;
+        guard(&self.path, Some(self.limits), || inner.resolve(&mut addresses.iter().copied()))
+    }
+}
+
+/// Runs `read` on the symbol file at `path` within `limits`, if any: files larger than they
+/// allow, or PDBs whose stream directory records larger streams, are rejected before `read`
+/// runs, and its panics are turned into errors.
+///
+/// Everything that parses a symbol file given on the command line goes through this, not only
+/// resolving, as a malformed file can take down any reader.
+pub fn guard<T>(path: &Path, limits: Option<Limits>, read: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+    let limits = match limits {
+        Some(limits) => limits,
+        None => return read(),
+    };
+    let size = fs::metadata(paths::long_path(path)).map_err(|e| e.to_string())?.len();
+    if size > limits.max_file_size {
+        return Err(format!("file is larger than the limit of {} bytes", limits.max_file_size));
+    }
+    if let Some(sizes) = msf::stream_sizes(path, limits.max_stream_size)? {
+        if sizes.iter().any(|&size| u64::from(size) > limits.max_stream_size) {
+            return Err(format!("stream is larger than the limit of {} bytes", limits.max_stream_size));
+        }
+        if sizes.iter().map(|&size| u64::from(size)).sum::<u64>() > limits.max_total_stream_size {
+            return Err(format!("streams are larger than the limit of {} bytes in total", limits.max_total_stream_size));
+        }
+    }
+
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let outcome = panic::catch_unwind(AssertUnwindSafe(read));
+    panic::set_hook(hook);
+    outcome.map_err(|_| "malformed symbol file".to_string())?
+}
+
+/// How many addresses `resolve_with_timeout` resolves at a time.
+const TIMEOUT_BATCH: usize = 1024;
+
+/// Resolves `addresses` against the symbol file at `path` in batches until `timeout` expires.
+///
+/// The batches are resolved on a separate thread, so even a batch that never finishes cannot
+/// hold up the results of those before it. They share one provider, so a PDB is opened and its
+/// tables read once, not for each batch. Addresses whose batch had not been resolved in time
+/// are returned without frames and marked `timed_out`. `Limits::max_addresses` applies to all
+/// batches together.
+pub fn resolve_with_timeout(
+    path: &Path,
+    options: &ProviderOptions,
+    addresses: Vec<u32>,
+    timeout: Duration,
+) -> Result<Vec<Resolution>, String> {
+    if let Some(limits) = options.limits {
+        if addresses.len() > limits.max_addresses {
+            return Err(format!("more than {} addresses", limits.max_addresses));
+        }
+    }
+    let deadline = Instant::now() + timeout;
+    let (sender, receiver) = mpsc::channel();
+    let batches: Vec<Vec<u32>> = addresses.chunks(TIMEOUT_BATCH).map(<[u32]>::to_vec).collect();
+    let (path, options) = (path.to_path_buf(), *options);
+    // The thread is left running if the deadline passes; the process exits without waiting.
+    thread::spawn(move || {
+        let mut provider = open(&path, &options);
+        for batch in batches {
+            let results = provider.resolve(&mut batch.into_iter());
+            if sender.send(results).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut results = Vec::with_capacity(addresses.len());
+    while results.len() < addresses.len() {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match receiver.recv_timeout(remaining) {
+            Ok(batch) => results.extend(batch?),
+            Err(_) => break,
+        }
+    }
+    let resolved = results.len();
+    if resolved < addresses.len() {
+        writeln!(
+            &mut io::stderr(),
+            "warning: timed out after {:?} with {} of {} addresses resolved",
+            timeout,
+            resolved,
+            addresses.len()
+        )
+        .expect("stderr write");
+    }
+    results.extend(addresses[resolved..].iter().map(|&address| {
+        let mut result = Resolution::new(address, Vec::new());
+        result.timed_out = true;
+        result
+    }));
+    Ok(results)
+}
+
+/// Combines two providers for the same module, preferring `primary`.
+pub fn merge(primary: Box<dyn SymbolProvider>, secondary: Box<dyn SymbolProvider>) -> Box<dyn SymbolProvider> {
+    Box::new(MergedProvider { primary, secondary })
+}
+
+/// Resolves with two providers for the same module and merges their answers.
+///
+/// The secondary provider fills in addresses the primary one resolves less precisely, as ranked
+/// by `Source`. Where both find line information but disagree about the location, the primary
+/// answer is kept and the conflict is reported on stderr.
+pub struct MergedProvider {
+    primary: Box<dyn SymbolProvider>,
+    secondary: Box<dyn SymbolProvider>,
+}
+
+impl SymbolProvider for MergedProvider {
+    fn kind(&self) -> &'static str {
+        self.primary.kind()
+    }
+
+    fn resolve(&mut self, addresses: &mut dyn Iterator<Item = u32>) -> Result<Vec<Resolution>, String> {
+        let mut results = self.primary.resolve(addresses)?;
+        let fallback = match self.secondary.resolve(&mut results.iter().map(|result| result.address)) {
+            Ok(fallback) => fallback,
+            Err(e) => {
+                writeln!(&mut io::stderr(), "warning: ignoring {}: {}", self.secondary.kind(), e).expect("stderr write");
+                return Ok(results);
+            }
+        };
+
+        for (result, other) in results.iter_mut().zip(fallback) {
+            if rank(&other) < rank(result) {
+                result.frames = other.frames;
+                continue;
+            }
+            if let (Some(ours), Some(theirs)) = (line_info(result), line_info(&other)) {
+                if !ours.file.eq_ignore_ascii_case(&theirs.file) || ours.line != theirs.line {
+                    writeln!(
+                        &mut io::stderr(),
+                        "warning: {:#x}: {} has {}:{}, {} has {}:{}",
+                        result.address,
+                        self.primary.kind(),
+                        ours.file,
+                        ours.line,
+                        self.secondary.kind(),
+                        theirs.file,
+                        theirs.line
+                    )
+                    .expect("stderr write");
+                }
+            }
+        }
+        Ok(results)
+    }
+}
+
+/// Orders results by their most reliable frame; unresolved addresses rank last.
+fn rank(result: &Resolution) -> usize {
+    result.frames.iter().map(|frame| frame.source as usize).min().unwrap_or(usize::MAX)
+}
+
+/// The innermost frame of `result` if it was resolved from a line record.
+fn line_info(result: &Resolution) -> Option<&Frame> {
+    result.frames.last().filter(|frame| frame.source == Source::LineInfo)
+}
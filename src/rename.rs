@@ -0,0 +1,105 @@
+//! `--rename-map`: restoring the original names of symbols shipped obfuscated, from a JSON
+//! object mapping each obfuscated name to its original.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::json::{self, Value};
+use crate::output::Resolution;
+use crate::paths;
+
+/// Obfuscated names with their originals, and the reverse.
+pub struct RenameMap {
+    originals: HashMap<String, String>,
+    obfuscated: HashMap<String, String>,
+}
+
+impl RenameMap {
+    /// Reads the map from the JSON file at `path`.
+    pub fn load(path: &Path) -> Result<RenameMap, String> {
+        let text = fs::read_to_string(paths::long_path(path))
+            .map_err(|e| format!("cannot read rename map '{}': {}", path.display(), e))?;
+        RenameMap::parse(&text).map_err(|e| format!("invalid rename map '{}': {}", path.display(), e))
+    }
+
+    /// Parses a JSON object like `{"a1b2": "Player", "c3d4": "update"}`.
+    pub fn parse(text: &str) -> Result<RenameMap, String> {
+        let pairs = match json::parse(text)? {
+            Value::Object(pairs) => pairs,
+            _ => return Err("expected an object of obfuscated to original names".to_string()),
+        };
+        let mut map = RenameMap { originals: HashMap::new(), obfuscated: HashMap::new() };
+        for (obfuscated, original) in pairs {
+            let original = match original {
+                Value::String(original) => original,
+                _ => return Err(format!("the original name of '{}' is not a string", obfuscated)),
+            };
+            map.obfuscated.insert(original.clone(), obfuscated.clone());
+            map.originals.insert(obfuscated, original);
+        }
+        Ok(map)
+    }
+
+    /// The original of the obfuscated `name`.
+    pub fn original(&self, name: &str) -> String {
+        translate(&self.originals, name)
+    }
+
+    /// The obfuscated form of the original `name`, for searching the symbol file.
+    pub fn obfuscate(&self, name: &str) -> String {
+        translate(&self.obfuscated, name)
+    }
+
+    /// Restores the original names of all frames of `results`.
+    pub fn apply(&self, results: &mut [Resolution]) {
+        for frame in results.iter_mut().flat_map(|result| result.frames.iter_mut()) {
+            frame.function = self.original(&frame.function);
+            for alias in &mut frame.aliases {
+                *alias = self.original(alias);
+            }
+            if let Some(ref mut signature) = frame.signature {
+                *signature = self.original(signature);
+            }
+        }
+    }
+}
+
+/// Maps `name` as a whole if `map` has it, and otherwise each identifier in it, so the segments
+/// of qualified names and the types of signatures are mapped too.
+fn translate(map: &HashMap<String, String>, name: &str) -> String {
+    if let Some(mapped) = map.get(name) {
+        return mapped.clone();
+    }
+    let mut out = String::with_capacity(name.len());
+    let mut rest = name;
+    while !rest.is_empty() {
+        let identifier = rest.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$')).unwrap_or(rest.len());
+        if identifier == 0 {
+            let c = rest.chars().next().expect("rest is not empty");
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        } else {
+            let token = &rest[..identifier];
+            out.push_str(map.get(token).map_or(token, String::as_str));
+            rest = &rest[identifier..];
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RenameMap;
+
+    #[test]
+    fn renames_both_ways() {
+        let map = RenameMap::parse(r#"{"a1": "Player", "b2": "update", "c3::d4": "Game::run"}"#).unwrap();
+        assert_eq!(map.original("a1::b2"), "Player::update");
+        assert_eq!(map.original("void __cdecl a1::b2(a1 *)"), "void __cdecl Player::update(Player *)");
+        assert_eq!(map.original("c3::d4"), "Game::run");
+        assert_eq!(map.original("main"), "main");
+        assert_eq!(map.obfuscate("Player::update"), "a1::b2");
+        assert!(RenameMap::parse(r#"{"a1": 1}"#).is_err());
+    }
+}
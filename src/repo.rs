@@ -0,0 +1,254 @@
+//! Source repository locations of source files, from a PDB's SourceLink or srcsrv stream.
+//!
+//! SourceLink (the `sourcelink` named stream) maps path patterns to URLs of the raw file at the
+//! built revision. srcsrv (the `srcsrv` stream written by the Debugging Tools' indexing scripts)
+//! lists each file with variables that the `SRCSRVTRG` template expands into such a URL. Either
+//! way the URL is then split into repository, revision and path for the hosts whose URL layout
+//! is known.
+
+use std::collections::HashMap;
+
+use pdb::PDB;
+
+use crate::json::{self, Value};
+
+/// Where a source file lives in its repository.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceRepo {
+    /// URL of the repository's web page, e.g. `https://github.com/org/project`.
+    pub repo_url: String,
+    /// The commit the file was built from.
+    pub revision: String,
+    /// Path of the file within the repository, with `/` separators.
+    pub repo_relative_path: String,
+}
+
+/// The source file to URL mappings of one PDB.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    /// SourceLink `documents` as (path pattern, URL pattern), most specific first.
+    documents: Vec<(String, String)>,
+    /// srcsrv URLs by lower case local path.
+    srcsrv: HashMap<String, String>,
+}
+
+impl SourceMap {
+    /// Reads the SourceLink and srcsrv streams of `pdb`, returning `None` if it has neither.
+    pub fn load<'s, S: pdb::Source<'s> + 's>(pdb: &mut PDB<'s, S>) -> Option<SourceMap> {
+        let mut read = |name: &[u8]| {
+            let stream = pdb.named_stream(name).ok()?;
+            Some(String::from_utf8_lossy(stream.as_slice()).into_owned())
+        };
+        let sourcelink = read(b"sourcelink");
+        let srcsrv = read(b"srcsrv");
+        if sourcelink.is_none() && srcsrv.is_none() {
+            return None;
+        }
+        let mut map = SourceMap::default();
+        if let Some(json) = sourcelink {
+            map.documents = parse_sourcelink(&json);
+        }
+        if let Some(text) = srcsrv {
+            map.srcsrv = parse_srcsrv(&text);
+        }
+        Some(map)
+    }
+
+    /// Returns the repository location of the source file at the local path `file`.
+    pub fn lookup(&self, file: &str) -> Option<SourceRepo> {
+        let url = self.url(file)?;
+        split_url(&url)
+    }
+
+    fn url(&self, file: &str) -> Option<String> {
+        for (pattern, url) in &self.documents {
+            match pattern.strip_suffix('*') {
+                Some(prefix) => {
+                    let matches = file.len() >= prefix.len()
+                        && file.is_char_boundary(prefix.len())
+                        && file[..prefix.len()].eq_ignore_ascii_case(prefix);
+                    if matches {
+                        return Some(url.replacen('*', &file[prefix.len()..].replace('\\', "/"), 1));
+                    }
+                }
+                None if pattern.eq_ignore_ascii_case(file) => return Some(url.clone()),
+                None => {}
+            }
+        }
+        self.srcsrv.get(&file.to_lowercase()).cloned()
+    }
+}
+
+/// Parses the `documents` object of a SourceLink file. Longer patterns come first, as the
+/// SourceLink specification has the most specific pattern win.
+fn parse_sourcelink(text: &str) -> Vec<(String, String)> {
+    let members = match json::parse(text.trim_start_matches('\u{feff}')) {
+        Ok(sourcelink) => match sourcelink.get("documents") {
+            Some(Value::Object(members)) => members.clone(),
+            _ => return Vec::new(),
+        },
+        Err(_) => return Vec::new(),
+    };
+    let mut documents: Vec<(String, String)> =
+        members.into_iter().filter_map(|(pattern, url)| Some((pattern, url.as_str()?.to_string()))).collect();
+    documents.sort_by_key(|document| std::cmp::Reverse(document.0.len()));
+    documents
+}
+
+/// Parses a srcsrv stream into the URL of each listed file, expanding `SRCSRVTRG` with the
+/// stream's variables and the file's `*`-separated fields as `%var1%`, `%var2%` and so on.
+fn parse_srcsrv(text: &str) -> HashMap<String, String> {
+    let mut variables: HashMap<String, String> = HashMap::new();
+    let mut files = HashMap::new();
+    let mut section = "";
+    for line in text.lines() {
+        let line = line.trim_end();
+        if let Some(header) = line.strip_prefix("SRCSRV:") {
+            section = header.split_whitespace().next().unwrap_or("");
+            continue;
+        }
+        match section {
+            "ini" | "variables" => {
+                if let Some((name, value)) = line.split_once('=') {
+                    variables.insert(name.trim().to_lowercase(), value.to_string());
+                }
+            }
+            "source" => {
+                let fields: Vec<&str> = line.split('*').collect();
+                let target = match variables.get("srcsrvtrg") {
+                    Some(target) if fields.len() > 1 => target,
+                    _ => continue,
+                };
+                let mut file_variables = variables.clone();
+                for (i, field) in fields.iter().enumerate() {
+                    file_variables.insert(format!("var{}", i + 1), field.to_string());
+                }
+                let url = expand(target, &file_variables, 0);
+                if url.starts_with("http://") || url.starts_with("https://") {
+                    files.insert(fields[0].to_lowercase(), url);
+                }
+            }
+            _ => {}
+        }
+    }
+    files
+}
+
+/// Replaces `%name%` references with the variables' values, recursively. Unknown references and
+/// srcsrv functions such as `%fnbksl%(...)` are left as they are.
+fn expand(template: &str, variables: &HashMap<String, String>, depth: usize) -> String {
+    let mut expanded = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('%') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('%') {
+            Some(end) => match variables.get(&after[..end].to_lowercase()) {
+                // Variables refer to each other a few levels deep; deeper means a cycle.
+                Some(value) if depth < 8 => {
+                    expanded.push_str(&expand(value, variables, depth + 1));
+                    rest = &after[end + 1..];
+                }
+                _ => {
+                    expanded.push_str(&rest[start..start + end + 2]);
+                    rest = &after[end + 1..];
+                }
+            },
+            None => {
+                expanded.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Splits a raw file URL of a known host into repository, revision and path.
+fn split_url(url: &str) -> Option<SourceRepo> {
+    let (scheme, rest) = url.split_once("://")?;
+    let (host, path) = rest.split_once('/')?;
+    let repo = |repo_url: String, revision: &str, file: &str| {
+        Some(SourceRepo {
+            repo_url,
+            revision: revision.to_string(),
+            repo_relative_path: file.trim_start_matches('/').to_string(),
+        })
+    };
+    match host {
+        // https://raw.githubusercontent.com/ORG/REPO/REV/PATH
+        "raw.githubusercontent.com" => {
+            let mut parts = path.splitn(4, '/');
+            let (org, name, revision, file) = (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+            repo(format!("{}://github.com/{}/{}", scheme, org, name), revision, file)
+        }
+        // https://dev.azure.com/ORG/PROJECT/_apis/git/repositories/REPO/items?...&version=REV&path=PATH
+        "dev.azure.com" => {
+            let (location, query) = path.split_once('?')?;
+            let (project, name) = location.split_once("/_apis/git/repositories/")?;
+            let name = name.trim_end_matches("/items");
+            let parameter = |key: &str| {
+                query.split('&').find_map(|pair| pair.split_once('=').filter(|(k, _)| k.eq_ignore_ascii_case(key)))
+            };
+            repo(format!("{}://{}/{}/_git/{}", scheme, host, project, name), parameter("version")?.1, parameter("path")?.1)
+        }
+        // https://HOST/GROUP/REPO/-/raw/REV/PATH, as served by GitLab and Gitea-like hosts.
+        _ => {
+            let (project, file) = path.split_once("/-/raw/")?;
+            let (revision, file) = file.split_once('/')?;
+            repo(format!("{}://{}/{}", scheme, host, project), revision, file)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sourcelink_documents() {
+        let map = SourceMap {
+            documents: parse_sourcelink(
+                r#"{"documents": {"C:\\src\\*": "https://raw.githubusercontent.com/org/app/0123abc/*",
+                   "C:\\src\\vendor\\zlib\\*": "https://gitlab.com/libs/zlib/-/raw/v1.3/*"}}"#,
+            ),
+            srcsrv: HashMap::new(),
+        };
+        assert_eq!(
+            map.lookup(r"c:\src\core\main.cpp"),
+            Some(SourceRepo {
+                repo_url: "https://github.com/org/app".to_string(),
+                revision: "0123abc".to_string(),
+                repo_relative_path: "core/main.cpp".to_string(),
+            })
+        );
+        let zlib = map.lookup(r"C:\src\vendor\zlib\inflate.c").unwrap();
+        assert_eq!(zlib.repo_url, "https://gitlab.com/libs/zlib");
+        assert_eq!(zlib.revision, "v1.3");
+        assert_eq!(map.lookup(r"D:\other\file.c"), None);
+    }
+
+    #[test]
+    fn srcsrv_files() {
+        let text = "SRCSRV: ini ------\r\nVERSION=2\r\nSRCSRV: variables ------\r\n\
+                    REPO=https://raw.githubusercontent.com/org/app\r\n\
+                    SRCSRVTRG=%REPO%/%var2%/%var3%\r\nSRCSRV: source files ------\r\n\
+                    C:\\src\\main.cpp*89ab*src/main.cpp\r\nSRCSRV: end ------\r\n";
+        let map = SourceMap { documents: Vec::new(), srcsrv: parse_srcsrv(text) };
+        let repo = map.lookup(r"C:\SRC\main.cpp").unwrap();
+        assert_eq!(repo.repo_url, "https://github.com/org/app");
+        assert_eq!(repo.revision, "89ab");
+        assert_eq!(repo.repo_relative_path, "src/main.cpp");
+    }
+
+    #[test]
+    fn azure_devops_urls() {
+        let repo = split_url(
+            "https://dev.azure.com/org/proj/_apis/git/repositories/app/items?api-version=1.0&versionType=commit&version=f00d&path=/lib/a.cpp",
+        )
+        .unwrap();
+        assert_eq!(repo.repo_url, "https://dev.azure.com/org/proj/_git/app");
+        assert_eq!(repo.revision, "f00d");
+        assert_eq!(repo.repo_relative_path, "lib/a.cpp");
+    }
+}
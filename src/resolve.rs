@@ -0,0 +1,509 @@
+//! Address matching over in-memory tables, kept free of PDB parsing so it can be tested directly.
+
+use std::str::FromStr;
+
+use crate::demangle::{self, Style};
+use crate::output::{Frame, Resolution, Source};
+
+/// Which line record an address on a record boundary belongs to, chosen with `--boundary`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Boundary {
+    /// The record ending at the address, as coverage tools count a boundary.
+    First,
+    /// The record starting at the address, which is what a crashing instruction belongs to.
+    Last,
+}
+
+impl FromStr for Boundary {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "first" => Ok(Boundary::First),
+            "last" => Ok(Boundary::Last),
+            _ => Err(format!("unknown boundary '{}' (expected first or last)", s)),
+        }
+    }
+}
+
+/// Returns whether `target` lies in the half-open range `[start, start + len)`.
+///
+/// Ranges reaching past `u64::MAX` are handled without overflow and empty ranges contain nothing.
+pub fn in_range(start: u64, len: u64, target: u64) -> bool {
+    target >= start && target - start < len
+}
+
+/// Sorts query addresses for `in_sorted_range`, keeping each address's position in the query.
+pub fn sort_queries(addresses: impl IntoIterator<Item = u32>) -> Vec<(u32, usize)> {
+    let mut sorted: Vec<(u32, usize)> = addresses.into_iter().zip(0..).collect();
+    sorted.sort_unstable();
+    sorted
+}
+
+/// Returns the query positions of the addresses in `[start, start + len)`.
+///
+/// `sorted` comes from `sort_queries`, so finding the matches is a binary search instead of a
+/// scan over all queries, which matters when millions of addresses meet thousands of ranges.
+pub fn in_sorted_range(sorted: &[(u32, usize)], start: u64, len: u64) -> impl Iterator<Item = usize> + '_ {
+    let first = sorted.partition_point(|&(address, _)| u64::from(address) < start);
+    sorted[first..]
+        .iter()
+        .take_while(move |&&(address, _)| in_range(start, len, u64::from(address)))
+        .map(|&(_, index)| index)
+}
+
+/// Picks the line record covering `target` from the start addresses of a procedure's records.
+///
+/// This is the record with the greatest start address not above `target`. When several records
+/// start at the same address the last one wins, so zero-length records never shadow the record
+/// that follows them. Returns `None` if `target` precedes every record.
+///
+/// With `Boundary::First`, an address where a record starts is attributed to the preceding record
+/// instead, unless no record precedes it.
+pub fn select_line(starts: &[u64], target: u64, boundary: Boundary) -> Option<usize> {
+    let last_before = |limit: u64, inclusive: bool| {
+        let mut best: Option<usize> = None;
+        for (index, &start) in starts.iter().enumerate() {
+            let before = if inclusive { start <= limit } else { start < limit };
+            if before && best.is_none_or(|best| starts[best] <= start) {
+                best = Some(index);
+            }
+        }
+        best
+    };
+    let selected = last_before(target, true)?;
+    if boundary == Boundary::First && starts[selected] == target {
+        return last_before(target, false).or(Some(selected));
+    }
+    Some(selected)
+}
+
+/// The line records other than `selected` that also claim `target`, from the start addresses
+/// and sizes of a procedure's records: those starting where `selected` does and those whose known
+/// size covers `target`.
+///
+/// Instruction scheduling in optimized code can leave several records claiming one address; the
+/// resolver reports `selected` and these as the alternatives.
+pub fn covering_lines(records: &[(u64, Option<u64>)], target: u64, selected: usize) -> Vec<usize> {
+    let primary = records[selected].0;
+    records
+        .iter()
+        .enumerate()
+        .filter(|&(index, &(start, size))| {
+            index != selected && (start == primary || (start <= target && target - start < size.unwrap_or(0)))
+        })
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// The distinct locations of the records `covering_lines` finds, leaving out the location of
+/// `selected` itself. `location` gives the file and line of a record by index.
+pub fn line_candidates(
+    records: &[(u64, Option<u64>)],
+    target: u64,
+    selected: usize,
+    location: impl Fn(usize) -> (String, u64),
+) -> Vec<(String, u64)> {
+    let primary = location(selected);
+    let mut candidates: Vec<(String, u64)> = Vec::new();
+    for other in covering_lines(records, target, selected) {
+        let other = location(other);
+        if other != primary && !candidates.contains(&other) {
+            candidates.push(other);
+        }
+    }
+    candidates
+}
+
+/// Line numbers compilers give code that belongs to no source line.
+pub const HIDDEN_LINES: [u64; 2] = [0xfeefee, 0xf00f00];
+
+/// For an address in a gap of a procedure's line information, the nearest records with a real
+/// line before and after it, from the start addresses and lines of the procedure's records.
+///
+/// An address is in a gap when no record starts at or before it, or when the `selected` record
+/// is one of line 0 or a hidden line, as compiler-generated glue code is. Returns `None`
+/// outside gaps and when there are no real lines at all.
+pub fn gap_neighbours(records: &[(u64, u64)], target: u64, selected: Option<usize>) -> Option<(Option<usize>, Option<usize>)> {
+    let real = |line: u64| line != 0 && !HIDDEN_LINES.contains(&line);
+    if selected.is_some_and(|index| real(records[index].1)) {
+        return None;
+    }
+    let before = records.iter().rposition(|&(start, line)| start <= target && real(line));
+    let after = records.iter().position(|&(start, line)| start > target && real(line));
+    if before.is_none() && after.is_none() {
+        return None;
+    }
+    Some((before, after))
+}
+
+/// Gives addresses that no procedure covers a frame from the symbols around them.
+///
+/// An address past the end of the preceding procedure is padding between functions (alignment
+/// or incremental linking pads), unless a public symbol starts in between, in which case it
+/// belongs to code without debug information. Publics carry no size, so attributing an address
+/// to the closest preceding one is only a guess.
+pub fn resolve_unmatched(procedures: &[(u32, u32, String)], publics: &[(u32, String)], results: &mut [Resolution]) {
+    for result in results.iter_mut().filter(|result| result.frames.is_empty()) {
+        let target = result.address;
+        let public = publics[..publics.partition_point(|&(rva, _)| rva <= target)].last();
+        let procedure = procedures[..procedures.partition_point(|&(start, _, _)| start <= target)].last();
+
+        if let Some((start, len, name)) = procedure {
+            let end = u64::from(*start) + u64::from(*len);
+            if public.is_none_or(|&(rva, _)| u64::from(rva) < end) {
+                result.frames.push(Frame {
+                    function: name.clone(),
+                    file: String::new(),
+                    line: 0,
+                    column: None,
+                    function_offset: Some(target - start),
+                    line_offset: None,
+                    inline: false,
+                    source: Source::Padding,
+                    repo: None,
+                    aliases: Vec::new(),
+                    signature: None,
+                    language: None,
+                    system: false,
+                    candidates: Vec::new(),
+                    prologue: false,
+                });
+                continue;
+            }
+        }
+        if let Some((rva, name)) = public {
+            result.frames.push(Frame {
+                function: generated_name(name).unwrap_or_else(|| demangle(name)),
+                file: String::new(),
+                line: 0,
+                column: None,
+                function_offset: Some(target - rva),
+                line_offset: None,
+                inline: false,
+                source: Source::PublicSymbol,
+                repo: None,
+                aliases: Vec::new(),
+                signature: None,
+                language: None,
+                system: false,
+                candidates: Vec::new(),
+                prologue: false,
+            });
+        }
+    }
+}
+
+/// Reports functions that identical COMDAT folding (`/OPT:ICF`) merged as aliases of one frame.
+///
+/// Folded functions share one address range, so each procedure record of the fold set yields a
+/// frame for the same address; all but the first are dropped, with their inline frames, and
+/// their names recorded as aliases. Publics starting where the frame's function starts name the
+/// folded functions that kept no procedure record.
+pub fn merge_folded(publics: &[(u32, String)], results: &mut [Resolution]) {
+    for result in results.iter_mut() {
+        let address = result.address;
+        let start_of = |frame: &Frame| match (frame.inline, frame.source, frame.function_offset) {
+            (false, Source::LineInfo, Some(offset))
+            | (false, Source::ProcedureOnly, Some(offset))
+            | (false, Source::PublicSymbol, Some(offset)) => address.checked_sub(offset),
+            _ => None,
+        };
+
+        let mut frames: Vec<Frame> = Vec::with_capacity(result.frames.len());
+        let mut primary: Option<(usize, u32)> = None;
+        let mut skipping = false;
+        for frame in result.frames.drain(..) {
+            match start_of(&frame) {
+                Some(start) => match primary {
+                    Some((index, primary_start)) if primary_start == start => {
+                        if frame.function != frames[index].function && !frames[index].aliases.contains(&frame.function) {
+                            frames[index].aliases.push(frame.function);
+                        }
+                        skipping = true;
+                    }
+                    _ => {
+                        primary = Some((frames.len(), start));
+                        skipping = false;
+                        frames.push(frame);
+                    }
+                },
+                None if frame.inline && skipping => {}
+                None => frames.push(frame),
+            }
+        }
+
+        if let Some((index, start)) = primary {
+            let first = publics.partition_point(|&(rva, _)| rva < start);
+            for (_, name) in publics[first..].iter().take_while(|&&(rva, _)| rva == start) {
+                let name = generated_name(name).unwrap_or_else(|| demangle_name_only(name));
+                let frame = &mut frames[index];
+                // Names that could not be undecorated cannot be compared with procedure names.
+                if name.starts_with('?') || undecorated(&name) == undecorated(&frame.function) {
+                    continue;
+                }
+                if !frame.aliases.contains(&name) {
+                    frame.aliases.push(name);
+                }
+            }
+        }
+        result.frames = frames;
+    }
+}
+
+/// Strips the C decoration of x86 publics, `_name` and `_name@8`, for comparison with
+/// procedure names.
+fn undecorated(name: &str) -> &str {
+    let name = name.strip_prefix('_').unwrap_or(name);
+    match name.rsplit_once('@') {
+        Some((base, size)) if !size.is_empty() && size.bytes().all(|b| b.is_ascii_digit()) => base,
+        _ => name,
+    }
+}
+
+/// Names code the compiler split out of a function after that function, with a tag saying what
+/// the code is, or returns `None` for ordinary functions.
+pub fn generated_name(name: &str) -> Option<String> {
+    funclet_name(name).or_else(|| coroutine_name(name))
+}
+
+/// The funclet name prefixes MSVC uses, with the kind of code each funclet holds.
+///
+/// On x64 funclets are named like `?catch$0@?0?PARENT@4HA`, on x86 like `__catch$PARENT$0`.
+const FUNCLETS: [(&str, &str); 7] = [
+    ("?catch$", "catch block"),
+    ("?fin$", "finally block"),
+    ("?filt$", "exception filter"),
+    ("?dtor$", "unwind funclet"),
+    ("__catch$", "catch block"),
+    ("__unwindfunclet$", "unwind funclet"),
+    ("__ehhandler$", "exception handler"),
+];
+
+/// Names an exception handling funclet after the function it belongs to, as
+/// `parent (catch block)`, or returns `None` if `name` is not a funclet.
+fn funclet_name(name: &str) -> Option<String> {
+    let &(prefix, kind) = FUNCLETS.iter().find(|(prefix, _)| name.starts_with(prefix))?;
+    let rest = &name[prefix.len()..];
+    let parent = if prefix.starts_with('?') {
+        // `N@?0?PARENT@4HA`
+        let start = rest.find("@?0?")? + 4;
+        rest[start..].strip_suffix("@4HA")?
+    } else {
+        // `PARENT$N`, or just `PARENT` for handlers.
+        match rest.rfind('$') {
+            Some(end) if rest[end + 1..].bytes().all(|b| b.is_ascii_digit()) => &rest[..end],
+            _ => rest,
+        }
+    };
+    if parent.is_empty() {
+        return None;
+    }
+    Some(format!("{} ({})", demangle_name_only(parent), kind))
+}
+
+/// The markers MSVC inserts into the names of the parts of a C++20 coroutine, with their tags.
+const COROUTINE_PARTS: [(&str, &str); 3] =
+    [("$_ResumeCoro$", "coroutine resume"), ("$_DestroyCoro$", "coroutine destroy"), ("$_InitCoro$", "coroutine init")];
+
+/// Names a coroutine part like `task$_ResumeCoro$1`, or its decorated form
+/// `?task$_ResumeCoro$1@@YAXXZ`, as `task (coroutine resume)`.
+fn coroutine_name(name: &str) -> Option<String> {
+    let &(marker, tag) = COROUTINE_PARTS.iter().find(|(marker, _)| name.contains(marker))?;
+    let start = name.find(marker)?;
+    let rest = &name[start + marker.len()..];
+    let end = rest.bytes().position(|b| !b.is_ascii_digit()).unwrap_or(rest.len());
+    let original = format!("{}{}", &name[..start], &rest[end..]);
+    let original = if original.starts_with('?') { demangle_name_only(&original) } else { original };
+    Some(format!("{} ({})", original, tag))
+}
+
+/// Demangles a symbol name, returning it unchanged if it is not mangled.
+fn demangle(name: &str) -> String {
+    demangle::demangle(name, Style::Full)
+}
+
+/// Demangles a symbol name to just its qualified name, as procedure records name functions.
+fn demangle_name_only(name: &str) -> String {
+    demangle::demangle(name, Style::NameOnly)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    #[cfg(feature = "demangle")]
+    fn funclets_name_their_parent() {
+        assert_eq!(funclet_name("?catch$0@?0??main@@YAHXZ@4HA").as_deref(), Some("main (catch block)"));
+        assert_eq!(funclet_name("?dtor$3@?0??run@Job@@QEAAXXZ@4HA").as_deref(), Some("Job::run (unwind funclet)"));
+        assert_eq!(funclet_name("__catch$?main@@YAHXZ$0").as_deref(), Some("main (catch block)"));
+        assert_eq!(funclet_name("__ehhandler$?main@@YAHXZ").as_deref(), Some("main (exception handler)"));
+        assert_eq!(funclet_name("?main@@YAHXZ"), None);
+        assert_eq!(funclet_name("?catch$0"), None);
+    }
+
+    #[test]
+    fn coroutine_parts_name_the_coroutine() {
+        assert_eq!(generated_name("fetch$_ResumeCoro$1").as_deref(), Some("fetch (coroutine resume)"));
+        assert_eq!(generated_name("Net::fetch$_DestroyCoro$2").as_deref(), Some("Net::fetch (coroutine destroy)"));
+        assert_eq!(generated_name("fetch"), None);
+    }
+
+    #[test]
+    #[cfg(feature = "demangle")]
+    fn decorated_coroutine_parts() {
+        assert_eq!(generated_name("?fetch$_ResumeCoro$1@@YAXXZ").as_deref(), Some("fetch (coroutine resume)"));
+    }
+
+    fn frame(function: &str, function_offset: Option<u32>, inline: bool) -> Frame {
+        Frame {
+            function: function.to_string(),
+            file: String::new(),
+            line: 0,
+            column: None,
+            function_offset,
+            line_offset: None,
+            inline,
+            source: Source::LineInfo,
+            repo: None,
+            aliases: Vec::new(),
+            signature: None,
+            language: None,
+            system: false,
+            candidates: Vec::new(),
+            prologue: false,
+        }
+    }
+
+    #[test]
+    fn folded_procedures_become_aliases() {
+        let mut results = vec![Resolution::new(
+            0x1010,
+            vec![
+                frame("FuncA", Some(0x10), false),
+                frame("inlined", None, true),
+                frame("FuncB", Some(0x10), false),
+                frame("inlined", None, true),
+            ],
+        )];
+        let publics = vec![(0x1000, "_FuncA".to_string()), (0x1000, "FuncC".to_string()), (0x1010, "Next".to_string())];
+        merge_folded(&publics, &mut results);
+        let frames = &results[0].frames;
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].aliases, vec!["FuncB".to_string(), "FuncC".to_string()]);
+        assert_eq!(frames[1].function, "inlined");
+    }
+
+    #[test]
+    fn range_edges() {
+        assert!(in_range(0x1000, 0x10, 0x1000));
+        assert!(in_range(0x1000, 0x10, 0x100f));
+        assert!(!in_range(0x1000, 0x10, 0x1010));
+        assert!(!in_range(0x1000, 0x10, 0xfff));
+        assert!(!in_range(0x1000, 0, 0x1000));
+        assert!(in_range(u64::MAX - 1, 0x10, u64::MAX));
+    }
+
+    #[test]
+    fn zero_length_record_is_skipped() {
+        // The records at 0x10 are a zero-length record followed by the real one.
+        assert_eq!(select_line(&[0x0, 0x10, 0x10, 0x20], 0x10, Boundary::Last), Some(2));
+        assert_eq!(select_line(&[0x0, 0x10, 0x10, 0x20], 0x1f, Boundary::Last), Some(2));
+        assert_eq!(select_line(&[0x0, 0x10, 0x10, 0x20], 0x20, Boundary::Last), Some(3));
+    }
+
+    #[test]
+    fn address_before_first_record() {
+        assert_eq!(select_line(&[0x10, 0x20], 0xf, Boundary::Last), None);
+        assert_eq!(select_line(&[], 0x10, Boundary::Last), None);
+    }
+
+    #[test]
+    fn first_boundary_prefers_ending_record() {
+        assert_eq!(select_line(&[0x0, 0x10, 0x20], 0x10, Boundary::First), Some(0));
+        assert_eq!(select_line(&[0x0, 0x10, 0x20], 0x11, Boundary::First), Some(1));
+        // Nothing ends at the first record's start.
+        assert_eq!(select_line(&[0x0, 0x10, 0x20], 0x0, Boundary::First), Some(0));
+    }
+
+    #[test]
+    fn records_claiming_the_same_address() {
+        let records = [(0x0, None), (0x8, None), (0x8, None), (0x4, Some(0x10)), (0x10, Some(4))];
+        assert_eq!(covering_lines(&records, 0xa, 2), vec![1, 3]);
+        assert_eq!(covering_lines(&records, 0x2, 0), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn lines_around_gaps() {
+        let records = [(0x4, 40), (0x10, 0xfeefee), (0x18, 0), (0x20, 45)];
+        assert_eq!(gap_neighbours(&records, 0x12, Some(1)), Some((Some(0), Some(3))));
+        assert_eq!(gap_neighbours(&records, 0x1a, Some(2)), Some((Some(0), Some(3))));
+        assert_eq!(gap_neighbours(&records, 0x0, None), Some((None, Some(0))));
+        assert_eq!(gap_neighbours(&records, 0x8, Some(0)), None);
+    }
+
+    proptest! {
+        #[test]
+        fn in_range_matches_wide_arithmetic(start: u64, len: u64, target: u64) {
+            let expected = u128::from(start) <= u128::from(target)
+                && u128::from(target) < u128::from(start) + u128::from(len);
+            prop_assert_eq!(in_range(start, len, target), expected);
+        }
+
+        #[test]
+        fn sorted_range_matches_scan(
+            addresses in proptest::collection::vec(0u32..0x100, 0..64),
+            start in 0u64..0x110,
+            len in 0u64..0x40,
+        ) {
+            let sorted = sort_queries(addresses.iter().copied());
+            let mut found: Vec<usize> = in_sorted_range(&sorted, start, len).collect();
+            found.sort_unstable();
+            let expected: Vec<usize> = (0..addresses.len())
+                .filter(|&i| in_range(start, len, u64::from(addresses[i])))
+                .collect();
+            prop_assert_eq!(found, expected);
+        }
+
+        #[test]
+        fn selected_line_starts_at_or_before_target(
+            mut starts in proptest::collection::vec(0u64..0x1000, 0..32),
+            target in 0u64..0x1100,
+        ) {
+            starts.sort_unstable();
+            match select_line(&starts, target, Boundary::Last) {
+                Some(index) => {
+                    prop_assert!(starts[index] <= target);
+                    // No later record also starts at or before the target.
+                    prop_assert!(starts[index + 1..].iter().all(|&start| start > target));
+                }
+                None => prop_assert!(starts.iter().all(|&start| start > target)),
+            }
+        }
+
+        #[test]
+        fn selection_ignores_record_order(
+            starts in proptest::collection::vec(0u64..0x1000, 1..32),
+            target in 0u64..0x1100,
+        ) {
+            let mut sorted = starts.clone();
+            sorted.sort_unstable();
+            let pick = |starts: &[u64]| select_line(starts, target, Boundary::Last).map(|index| starts[index]);
+            prop_assert_eq!(pick(&starts), pick(&sorted));
+        }
+
+        #[test]
+        fn boundary_belongs_to_starting_record(
+            mut starts in proptest::collection::vec(0u64..0x1000, 2..32),
+            pick in any::<proptest::sample::Index>(),
+        ) {
+            starts.sort_unstable();
+            starts.dedup();
+            let index = pick.index(starts.len());
+            prop_assert_eq!(select_line(&starts, starts[index], Boundary::Last), Some(index));
+            prop_assert_eq!(select_line(&starts, starts[index], Boundary::First), Some(index.saturating_sub(1)));
+        }
+    }
+}
@@ -0,0 +1,341 @@
+//! `--stdio-jsonrpc`: JSON-RPC 2.0 over stdin and stdout, for editors and debug adapters that run
+//! the tool as a child process.
+//!
+//! Messages are either one JSON document per line or framed with `Content-Length` headers as in
+//! the Language Server Protocol; each response uses the framing of its request. The methods are:
+//!
+//! - `capabilities`: the version, features and abilities of this build, for clients to check
+//!   before relying on newer methods or fields. Sending it first is optional.
+//! - `resolve` `{"file", "addresses": [...], "base"?}`: the document `--format json` writes.
+//! - `findSymbol` `{"file", "name"}`: procedures and publics whose name contains `name`.
+//! - `listModules` `{"file"}`: the modules (object files) of the PDB.
+//! - `loadedSources` `{"file"}`: the source files of the PDB, shaped like the body of a Debug
+//!   Adapter Protocol `loadedSources` response so post-mortem viewers can pass it on unchanged.
+//! - `sourceLookup` `{"file", "path"}`: where the source file `path` lives in its repository,
+//!   from the PDB's SourceLink or srcsrv stream, or `null`.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
+
+use pdb::{FallibleIterator, SymbolData, PDB};
+
+use crate::address::{self, Arch};
+use crate::capabilities;
+use crate::demangle;
+use crate::json::{self, Value};
+use crate::output::{self, json_string, Format, RenderOptions, Style};
+use crate::provider::{self, ProviderOptions};
+use crate::rename::RenameMap;
+use crate::repo::SourceMap;
+use crate::system;
+use crate::{load_publics, locate_symbols, paths};
+
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+const SERVER_ERROR: i32 = -32000;
+
+/// Largest `Content-Length` accepted, in bytes. Longer messages are skipped and answered with an
+/// error rather than read into a buffer of whatever size the header claims.
+const MAX_MESSAGE_SIZE: usize = 64 << 20;
+
+/// Where symbol files named in requests are looked for, and how they are read.
+pub struct Context<'a> {
+    pub options: ProviderOptions,
+    pub arch: Option<Arch>,
+    pub cache: Option<&'a str>,
+    pub symbol_path: Option<&'a str>,
+    /// Original names of obfuscated symbols, from `--rename-map`.
+    pub renames: Option<&'a RenameMap>,
+}
+
+/// Answers requests from stdin until it is closed.
+pub fn serve(context: &Context) -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let (message, framed) = match content_length(&line) {
+            Some(length) => {
+                // Skip the remaining headers up to the blank line.
+                loop {
+                    line.clear();
+                    if input.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                        break;
+                    }
+                }
+                if length > MAX_MESSAGE_SIZE {
+                    io::copy(&mut (&mut input).take(length as u64), &mut io::sink())?;
+                    (Err(format!("message longer than the limit of {} bytes", MAX_MESSAGE_SIZE)), true)
+                } else {
+                    let mut body = vec![0; length];
+                    input.read_exact(&mut body)?;
+                    (Ok(String::from_utf8_lossy(&body).into_owned()), true)
+                }
+            }
+            None if line.trim().is_empty() => continue,
+            None => (Ok(line.clone()), false),
+        };
+
+        let response = match message {
+            Ok(message) => match handle(context, &message) {
+                Some(response) => response,
+                None => continue,
+            },
+            Err(e) => error(&Value::Null, INVALID_REQUEST, &e),
+        };
+        if framed {
+            write!(out, "Content-Length: {}\r\n\r\n{}", response.len(), response)?;
+        } else {
+            writeln!(out, "{}", response)?;
+        }
+        out.flush()?;
+    }
+}
+
+fn content_length(line: &str) -> Option<usize> {
+    let (name, value) = line.split_once(':')?;
+    if name.trim().eq_ignore_ascii_case("content-length") {
+        value.trim().parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Returns the response to one message, or `None` for notifications.
+fn handle(context: &Context, message: &str) -> Option<String> {
+    let request = match json::parse(message) {
+        Ok(request) => request,
+        Err(e) => return Some(error(&Value::Null, PARSE_ERROR, &e)),
+    };
+    let id = request.get("id").cloned();
+    let method = request.get("method").and_then(Value::as_str);
+    let (id, method) = match (id, method) {
+        (Some(id), Some(method)) => (id, method),
+        (None, Some(_)) => return None,
+        (id, None) => return Some(error(&id.unwrap_or(Value::Null), INVALID_REQUEST, "missing method")),
+    };
+    let params = request.get("params").cloned().unwrap_or(Value::Object(Vec::new()));
+    let result = match method {
+        "capabilities" => Ok(capabilities::to_json()),
+        "resolve" => resolve(context, &params),
+        "findSymbol" => find_symbol(context, &params),
+        "listModules" => list_modules(context, &params),
+        "loadedSources" => loaded_sources(context, &params),
+        "sourceLookup" => source_lookup(context, &params),
+        _ => Err((METHOD_NOT_FOUND, format!("unknown method '{}'", method))),
+    };
+    Some(match result {
+        Ok(result) => format!("{{\"jsonrpc\":\"2.0\",\"id\":{},\"result\":{}}}", id, result),
+        Err((code, message)) => error(&id, code, &message),
+    })
+}
+
+fn error(id: &Value, code: i32, message: &str) -> String {
+    format!("{{\"jsonrpc\":\"2.0\",\"id\":{},\"error\":{{\"code\":{},\"message\":{}}}}}", id, code, json_string(message))
+}
+
+type MethodResult = Result<String, (i32, String)>;
+
+/// The symbol file for the `file` parameter.
+fn symbol_file(context: &Context, params: &Value) -> Result<std::path::PathBuf, (i32, String)> {
+    let file = params.get("file").and_then(Value::as_str).ok_or((INVALID_PARAMS, "missing 'file'".to_string()))?;
+    let (_, path) = locate_symbols(file, context.cache, context.symbol_path, context.options.limits).map_err(|e| (SERVER_ERROR, e))?;
+    Ok(path)
+}
+
+/// Reads the symbol file at `path` with `read` within the limits of `context`.
+fn guarded<T>(context: &Context, path: &Path, read: impl FnOnce() -> pdb::Result<T>) -> Result<T, (i32, String)> {
+    provider::guard(path, context.options.limits, || read().map_err(|e| e.to_string())).map_err(|e| (SERVER_ERROR, e))
+}
+
+/// Reads an address given as a number or as a string in any form the command line accepts.
+fn address_param(value: &Value, arch: Option<Arch>) -> Result<u64, (i32, String)> {
+    match value {
+        Value::Number(n) if *n >= 0.0 && n.fract() == 0.0 && *n <= u64::MAX as f64 => Ok(*n as u64),
+        Value::String(s) => address::parse_address(s, arch).map_err(|e| (INVALID_PARAMS, e)),
+        _ => Err((INVALID_PARAMS, format!("invalid address {}", value))),
+    }
+}
+
+fn resolve(context: &Context, params: &Value) -> MethodResult {
+    let path = symbol_file(context, params)?;
+    let base = params.get("base").map(|base| address_param(base, context.arch)).transpose()?;
+    let addresses = match params.get("addresses") {
+        Some(Value::Array(addresses)) => addresses,
+        _ => return Err((INVALID_PARAMS, "missing 'addresses' array".to_string())),
+    };
+    let rvas = addresses
+        .iter()
+        .map(|a| {
+            address_param(a, context.arch)
+                .and_then(|a| address::to_rva(a, base, context.arch).map_err(|e| (INVALID_PARAMS, e)))
+        })
+        .collect::<Result<Vec<u32>, _>>()?;
+
+    let mut results = provider::open(&path, &context.options)
+        .resolve(&mut rvas.into_iter())
+        .map_err(|e| (SERVER_ERROR, e))?;
+    if let Some(renames) = context.renames {
+        renames.apply(&mut results);
+    }
+    system::classify(&mut results);
+    let render = RenderOptions { style: Style::Default, module: String::new(), verbose: false, relative: false, functions_only: false, confidence: false };
+    let mut json = Vec::new();
+    output::write_results(&mut json, Format::Json, &render, &results).map_err(|e| (SERVER_ERROR, e.to_string()))?;
+    Ok(String::from_utf8_lossy(&json).trim_end().to_string())
+}
+
+fn find_symbol(context: &Context, params: &Value) -> MethodResult {
+    let path = symbol_file(context, params)?;
+    let name = params.get("name").and_then(Value::as_str).ok_or((INVALID_PARAMS, "missing 'name'".to_string()))?;
+    // With a rename map, original names are searched for by their obfuscated form.
+    let query = context.renames.map_or_else(|| name.to_string(), |renames| renames.obfuscate(name));
+    let max_name = context.options.limits.map_or(usize::MAX, |limits| limits.max_string_length);
+    let mut symbols = guarded(context, &path, || find_symbols(&path, &query, max_name))?;
+    if let Some(renames) = context.renames {
+        for symbol in &mut symbols {
+            symbol.name = renames.original(&symbol.name);
+        }
+    }
+    let items: Vec<String> = symbols
+        .iter()
+        .map(|symbol| {
+            let size = symbol.size.map_or_else(|| "null".to_string(), |size| size.to_string());
+            format!(
+                "{{\"name\":{},\"kind\":\"{}\",\"address\":\"{:#x}\",\"size\":{}}}",
+                json_string(&symbol.name),
+                symbol.kind,
+                symbol.rva,
+                size
+            )
+        })
+        .collect();
+    Ok(format!("[{}]", items.join(",")))
+}
+
+/// A procedure or public symbol found by `findSymbol`.
+struct FoundSymbol {
+    kind: &'static str,
+    name: String,
+    rva: u32,
+    size: Option<u32>,
+}
+
+/// Returns the procedures and publics whose name contains `query`, rejecting public names longer
+/// than `max_name` bytes.
+fn find_symbols(path: &Path, query: &str, max_name: usize) -> pdb::Result<Vec<FoundSymbol>> {
+    let mut pdb = PDB::open(fs::File::open(paths::long_path(path))?)?;
+    let address_map = pdb.address_map()?;
+    let dbi = pdb.debug_information()?;
+    let mut found = Vec::new();
+    let mut modules = dbi.modules()?;
+    while let Some(module) = modules.next()? {
+        let info = match pdb.module_info(&module)? {
+            Some(info) => info,
+            None => continue,
+        };
+        let mut symbols = info.symbols()?;
+        while let Some(symbol) = symbols.next()? {
+            if let Ok(SymbolData::Procedure(proc)) = symbol.parse() {
+                let name = proc.name.to_string();
+                if name.contains(query) {
+                    if let Some(rva) = proc.offset.to_rva(&address_map) {
+                        found.push(FoundSymbol { kind: "procedure", name: name.into_owned(), rva: rva.0, size: Some(proc.len) });
+                    }
+                }
+            }
+        }
+    }
+    for (rva, name) in load_publics(&mut pdb, &address_map, max_name)? {
+        // Publics are found and named by their demangled names, as the resolver shows them.
+        let demangled = demangle::demangle(&name, demangle::Style::Full);
+        if name.contains(query) || demangle::demangle(&name, demangle::Style::NameOnly).contains(query) {
+            found.push(FoundSymbol { kind: "public", name: demangled, rva, size: None });
+        }
+    }
+    Ok(found)
+}
+
+fn list_modules(context: &Context, params: &Value) -> MethodResult {
+    let path = symbol_file(context, params)?;
+    let modules = || -> pdb::Result<Vec<String>> {
+        let mut pdb = PDB::open(fs::File::open(paths::long_path(&path))?)?;
+        let dbi = pdb.debug_information()?;
+        let modules = dbi.modules()?;
+        modules
+            .map(|module| {
+                Ok(format!(
+                    "{{\"name\":{},\"object_file\":{}}}",
+                    json_string(&module.module_name()),
+                    json_string(&module.object_file_name())
+                ))
+            })
+            .collect()
+    };
+    let modules = guarded(context, &path, modules)?;
+    Ok(format!("[{}]", modules.join(",")))
+}
+
+fn loaded_sources(context: &Context, params: &Value) -> MethodResult {
+    let path = symbol_file(context, params)?;
+    let files = guarded(context, &path, || source_files(&path))?;
+    let sources: Vec<String> = files
+        .iter()
+        .map(|file| {
+            let name = file.rsplit(['\\', '/']).next().unwrap_or(file);
+            format!("{{\"name\":{},\"path\":{}}}", json_string(name), json_string(file))
+        })
+        .collect();
+    Ok(format!("{{\"sources\":[{}]}}", sources.join(",")))
+}
+
+/// Returns the distinct source file names of all modules' line programs.
+fn source_files(path: &Path) -> pdb::Result<BTreeSet<String>> {
+    let mut pdb = PDB::open(fs::File::open(paths::long_path(path))?)?;
+    let string_table = pdb.string_table()?;
+    let dbi = pdb.debug_information()?;
+    let mut files = BTreeSet::new();
+    let mut modules = dbi.modules()?;
+    while let Some(module) = modules.next()? {
+        let info = match pdb.module_info(&module)? {
+            Some(info) => info,
+            None => continue,
+        };
+        let program = info.line_program()?;
+        let mut module_files = program.files();
+        while let Some(file) = module_files.next()? {
+            files.insert(file.name.to_string_lossy(&string_table)?.into_owned());
+        }
+    }
+    Ok(files)
+}
+
+fn source_lookup(context: &Context, params: &Value) -> MethodResult {
+    let path = symbol_file(context, params)?;
+    let file = params.get("path").and_then(Value::as_str).ok_or((INVALID_PARAMS, "missing 'path'".to_string()))?;
+    let source_map = || -> pdb::Result<Option<SourceMap>> {
+        let mut pdb = PDB::open(fs::File::open(paths::long_path(&path))?)?;
+        Ok(SourceMap::load(&mut pdb))
+    };
+    let source_map = guarded(context, &path, source_map)?;
+    Ok(match source_map.and_then(|map| map.lookup(file)) {
+        Some(repo) => format!(
+            "{{\"path\":{},\"repo_url\":{},\"revision\":{},\"repo_relative_path\":{}}}",
+            json_string(file),
+            json_string(&repo.repo_url),
+            json_string(&repo.revision),
+            json_string(&repo.repo_relative_path)
+        ),
+        None => "null".to_string(),
+    })
+}
@@ -0,0 +1,171 @@
+//! `--rust-pretty`: Rust function names cleaned up to read like the backtraces of Rust on Linux.
+
+use crate::output::Resolution;
+
+/// Cleans up the function names of the frames that can come from Rust code: those of compilands
+/// recorded as Rust and those whose language is unknown, such as public symbols. Frames of C,
+/// C++ and other compilands are left alone.
+pub fn prettify(results: &mut [Resolution]) {
+    for frame in results.iter_mut().flat_map(|r| r.frames.iter_mut()) {
+        if frame.language.is_some_and(|language| language != "rust") {
+            continue;
+        }
+        frame.function = pretty(&frame.function);
+        for alias in &mut frame.aliases {
+            *alias = pretty(alias);
+        }
+    }
+}
+
+/// Cleans up one name: demangles legacy `_ZN...E` symbols, drops the `::h0123456789abcdef` hash
+/// suffix, spells closures `{{closure}}` and removes the generic arguments of the path segments,
+/// so `alloc::vec::Vec<u8,alloc::alloc::Global>::push::h0123456789abcdef` becomes
+/// `alloc::vec::Vec::push`. Types in a qualified path like `<T as Trait>::f` keep their arguments.
+pub fn pretty(name: &str) -> String {
+    let demangled = demangle_legacy(name);
+    let name = demangled.as_deref().unwrap_or(name);
+    let name = strip_hash(name);
+    strip_generics(&closures(name))
+}
+
+/// Demangles a legacy Rust symbol without its hash, or returns `None` if `name` is not one.
+pub fn demangle(name: &str) -> Option<String> {
+    demangle_legacy(name).map(|demangled| strip_hash(&demangled).to_string())
+}
+
+/// Demangles a legacy (`-C symbol-mangling-version=legacy`) Rust symbol, or returns `None` if
+/// `name` is not one.
+fn demangle_legacy(name: &str) -> Option<String> {
+    let name = name.strip_prefix('_').unwrap_or(name);
+    let mut rest = name.strip_prefix("ZN").or_else(|| name.strip_prefix("_ZN"))?;
+    let mut segments = Vec::new();
+    while !rest.starts_with('E') {
+        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        let len: usize = rest[..digits].parse().ok()?;
+        let segment = rest.get(digits..digits + len)?;
+        segments.push(unescape(segment)?);
+        rest = &rest[digits + len..];
+    }
+    if segments.is_empty() {
+        return None;
+    }
+    Some(segments.join("::"))
+}
+
+/// Decodes the `$LT$`-style escapes and `..` separators of a legacy symbol segment.
+fn unescape(segment: &str) -> Option<String> {
+    let mut rest = segment.strip_prefix("_$").map_or(segment, |_| &segment[1..]);
+    let mut out = String::with_capacity(rest.len());
+    while let Some(c) = rest.chars().next() {
+        if c == '$' {
+            let end = rest[1..].find('$')? + 1;
+            let escape = &rest[1..end];
+            out.push(match escape {
+                "SP" => '@',
+                "BP" => '*',
+                "RF" => '&',
+                "LT" => '<',
+                "GT" => '>',
+                "LP" => '(',
+                "RP" => ')',
+                "C" => ',',
+                _ => {
+                    let code = escape.strip_prefix('u')?;
+                    char::from_u32(u32::from_str_radix(code, 16).ok()?)?
+                }
+            });
+            rest = &rest[end + 1..];
+        } else if let Some(after) = rest.strip_prefix("..") {
+            out.push_str("::");
+            rest = after;
+        } else {
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    Some(out)
+}
+
+/// Removes a trailing `::h` followed by the 16 hex digits of the symbol hash.
+fn strip_hash(name: &str) -> &str {
+    match name.rsplit_once("::h") {
+        Some((path, hash)) if hash.len() == 16 && hash.bytes().all(|b| b.is_ascii_hexdigit()) => path,
+        _ => name,
+    }
+}
+
+/// Replaces the `closure$0` segments rustc gives closures in PDBs with `{{closure}}`.
+fn closures(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut rest = name;
+    while let Some(index) = rest.find("closure$") {
+        let digits = rest[index + 8..].bytes().take_while(u8::is_ascii_digit).count();
+        out.push_str(&rest[..index]);
+        if digits == 0 {
+            out.push_str("closure$");
+        } else {
+            out.push_str("{{closure}}");
+        }
+        rest = &rest[index + 8 + digits..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Removes the generic argument lists that follow path segments, outside qualified paths.
+fn strip_generics(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut kept = 0;
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '<' if kept == 0 && out.ends_with(|p: char| p.is_alphanumeric() || p == '_' || p == '}' || p == '$') => {
+                // Skip up to the matching `>`, minding the arrows of `fn() -> T`.
+                let mut depth = 1;
+                let mut previous = c;
+                for c in chars.by_ref() {
+                    match c {
+                        '<' => depth += 1,
+                        '>' if previous != '-' => depth -= 1,
+                        _ => {}
+                    }
+                    previous = c;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            '<' => {
+                kept += 1;
+                out.push(c);
+            }
+            '>' if kept > 0 && !out.ends_with('-') => {
+                kept -= 1;
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::pretty;
+
+    #[test]
+    fn pretty_names() {
+        assert_eq!(pretty("_ZN3std2rt10lang_start17h0123456789abcdefE"), "std::rt::lang_start");
+        assert_eq!(
+            pretty("_ZN66_$LT$alloc..vec..Vec$LT$T$GT$$u20$as$u20$core..ops..drop..Drop$GT$4drop17h0123456789abcdefE"),
+            "<alloc::vec::Vec<T> as core::ops::drop::Drop>::drop"
+        );
+        assert_eq!(
+            pretty("core::ops::function::FnOnce::call_once<void (*)(),tuple$<> >"),
+            "core::ops::function::FnOnce::call_once"
+        );
+        assert_eq!(pretty("std::rt::lang_start::closure$0<()>"), "std::rt::lang_start::{{closure}}");
+        assert_eq!(pretty("app::main::h0123456789abcdef"), "app::main");
+        assert_eq!(pretty("main"), "main");
+    }
+}
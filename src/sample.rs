@@ -0,0 +1,101 @@
+//! `--sample`: resolving random addresses of the executable sections as a quick measure of how
+//! much of the code a PDB describes.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use pdb::PDB;
+
+use crate::output::{Resolution, Source};
+use crate::paths;
+
+/// `IMAGE_SCN_CNT_CODE`
+const SECTION_CODE: u32 = 0x20;
+/// `IMAGE_SCN_MEM_EXECUTE`
+const SECTION_EXECUTE: u32 = 0x2000_0000;
+
+/// The RVA ranges of the executable sections of the image described by the PDB at `path`, as
+/// (start, size) pairs.
+pub fn executable_ranges(path: &Path) -> pdb::Result<Vec<(u32, u32)>> {
+    let mut pdb = PDB::open(fs::File::open(paths::long_path(path))?)?;
+    let sections = pdb.sections()?.unwrap_or_default();
+    Ok(sections
+        .iter()
+        .filter(|section| section.characteristics & (SECTION_CODE | SECTION_EXECUTE) != 0)
+        // The section's virtual size, which `pdb` calls by its COFF object file name.
+        .map(|section| (section.virtual_address, section.physical_address))
+        .filter(|&(_, size)| size > 0)
+        .collect())
+}
+
+/// The SplitMix64 generator, small and good enough to spread samples evenly.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Picks `count` addresses uniformly from `ranges`, the same ones for the same `seed`, sorted.
+pub fn pick(ranges: &[(u32, u32)], count: usize, seed: u64) -> Vec<u32> {
+    let total: u64 = ranges.iter().map(|&(_, size)| u64::from(size)).sum();
+    if total == 0 {
+        return Vec::new();
+    }
+    let mut random = SplitMix64(seed);
+    let mut addresses: Vec<u32> = (0..count)
+        .map(|_| {
+            let mut offset = random.next() % total;
+            for &(start, size) in ranges {
+                if offset < u64::from(size) {
+                    return start + offset as u32;
+                }
+                offset -= u64::from(size);
+            }
+            unreachable!("the offset is below the total size of the ranges")
+        })
+        .collect();
+    addresses.sort_unstable();
+    addresses
+}
+
+/// Writes how many of the sampled `results` resolved to line information, only a procedure, only
+/// a public symbol, padding or nothing at all.
+pub fn write_report(out: &mut dyn Write, results: &[Resolution], sections: usize, seed: u64) -> io::Result<()> {
+    let rows = [
+        ("line info", Some(Source::LineInfo)),
+        ("procedure only", Some(Source::ProcedureOnly)),
+        ("public symbol only", Some(Source::PublicSymbol)),
+        ("padding", Some(Source::Padding)),
+        ("nothing", None),
+    ];
+    writeln!(out, "sampled {} addresses in {} executable sections (seed {})", results.len(), sections, seed)?;
+    for (name, source) in &rows {
+        // The outermost frame tells how the address was found; inlinees only add to it.
+        let count = results.iter().filter(|result| result.frames.first().map(|frame| frame.source) == *source).count();
+        let percent = if results.is_empty() { 0.0 } else { 100.0 * count as f64 / results.len() as f64 };
+        writeln!(out, "{:20}{:>8}{:>8.1}%", name, count, percent)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::pick;
+
+    #[test]
+    fn seeded_picks() {
+        let ranges = [(0x1000, 0x10), (0x3000, 0x100)];
+        let addresses = pick(&ranges, 100, 7);
+        assert_eq!(addresses, pick(&ranges, 100, 7));
+        assert_ne!(addresses, pick(&ranges, 100, 8));
+        assert!(addresses.iter().all(|&a| (0x1000..0x1010).contains(&a) || (0x3000..0x3100).contains(&a)));
+        assert!(addresses.iter().any(|&a| a < 0x1010));
+    }
+}
@@ -0,0 +1,200 @@
+//! `--signatures`: function signatures rebuilt from the type records (TPI stream) of a PDB.
+
+use pdb::{FallibleIterator, PointerMode, PrimitiveKind, TypeData, TypeFinder, TypeIndex, TypeInformation};
+
+/// Type chains nest a few levels deep in practice; deeper ones are cut short with `?`.
+const MAX_DEPTH: usize = 32;
+
+/// Formats function signatures from the type records of one PDB.
+pub struct Signatures<'t> {
+    finder: TypeFinder<'t>,
+}
+
+impl<'t> Signatures<'t> {
+    /// Indexes all type records of `types`.
+    pub fn new(types: &'t TypeInformation<'_>) -> pdb::Result<Self> {
+        let mut finder = types.finder();
+        let mut iter = types.iter();
+        while iter.next()?.is_some() {
+            finder.update(&iter);
+        }
+        Ok(Signatures { finder })
+    }
+
+    /// Formats the function `name` with the procedure or member function type `index`, like
+    /// `int __cdecl Foo::Bar(const Baz &, unsigned int) const`, or returns `None` if `index` is
+    /// no function type.
+    pub fn signature(&self, name: &str, index: TypeIndex) -> Option<String> {
+        let (return_type, convention, arguments, qualifier) = match self.find(index)? {
+            TypeData::Procedure(procedure) => {
+                (procedure.return_type, procedure.attributes.calling_convention(), procedure.argument_list, "")
+            }
+            TypeData::MemberFunction(function) => {
+                let constant = function.this_pointer_type.is_some_and(|this| self.points_to_const(this));
+                (
+                    Some(function.return_type),
+                    function.attributes.calling_convention(),
+                    function.argument_list,
+                    if constant { " const" } else { "" },
+                )
+            }
+            _ => return None,
+        };
+        let mut signature = String::new();
+        if let Some(return_type) = return_type {
+            signature.push_str(&self.type_name(return_type, 0));
+            signature.push(' ');
+        }
+        if let Some(convention) = convention_name(convention) {
+            signature.push_str(convention);
+            signature.push(' ');
+        }
+        signature.push_str(&format!("{}({}){}", name, self.arguments(arguments, 0), qualifier));
+        Some(signature)
+    }
+
+    /// The parsed type record `index`, if there is one.
+    pub fn find(&self, index: TypeIndex) -> Option<TypeData<'t>> {
+        self.finder.find(index).ok()?.parse().ok()
+    }
+
+    fn points_to_const(&self, index: TypeIndex) -> bool {
+        match self.find(index) {
+            Some(TypeData::Pointer(pointer)) => {
+                matches!(self.find(pointer.underlying_type), Some(TypeData::Modifier(modifier)) if modifier.constant)
+            }
+            _ => false,
+        }
+    }
+
+    fn arguments(&self, index: TypeIndex, depth: usize) -> String {
+        match self.find(index) {
+            Some(TypeData::ArgumentList(list)) => {
+                let arguments: Vec<String> = list.arguments.iter().map(|&a| self.type_name(a, depth + 1)).collect();
+                if arguments.is_empty() {
+                    "void".to_string()
+                } else {
+                    arguments.join(", ")
+                }
+            }
+            _ => "?".to_string(),
+        }
+    }
+
+    /// Formats the type `index` the way MSVC spells it in undecorated names.
+    pub fn type_name(&self, index: TypeIndex, depth: usize) -> String {
+        if depth > MAX_DEPTH {
+            return "?".to_string();
+        }
+        let data = match self.find(index) {
+            Some(data) => data,
+            None => return "?".to_string(),
+        };
+        match data {
+            TypeData::Primitive(primitive) => match primitive.indirection {
+                Some(_) => format!("{} *", primitive_name(primitive.kind)),
+                None => primitive_name(primitive.kind).to_string(),
+            },
+            TypeData::Class(class) => class.name.to_string().into_owned(),
+            TypeData::Union(union) => union.name.to_string().into_owned(),
+            TypeData::Enumeration(enumeration) => enumeration.name.to_string().into_owned(),
+            TypeData::Pointer(pointer) => {
+                let declarator = match pointer.attributes.pointer_mode() {
+                    PointerMode::LValueReference => "&",
+                    PointerMode::RValueReference => "&&",
+                    _ => "*",
+                };
+                match self.find(pointer.underlying_type) {
+                    Some(TypeData::Procedure(procedure)) => {
+                        let return_type = procedure.return_type.map_or_else(String::new, |r| self.type_name(r, depth + 1) + " ");
+                        let convention = convention_name(procedure.attributes.calling_convention()).map_or(String::new(), |c| c.to_string() + " ");
+                        format!(
+                            "{}({}{})({})",
+                            return_type,
+                            convention,
+                            declarator,
+                            self.arguments(procedure.argument_list, depth + 1)
+                        )
+                    }
+                    _ => format!("{} {}", self.type_name(pointer.underlying_type, depth + 1), declarator),
+                }
+            }
+            TypeData::Modifier(modifier) => {
+                let inner = self.type_name(modifier.underlying_type, depth + 1);
+                let mut qualifiers = Vec::new();
+                if modifier.constant {
+                    qualifiers.push("const");
+                }
+                if modifier.volatile {
+                    qualifiers.push("volatile");
+                }
+                if qualifiers.is_empty() {
+                    inner
+                } else if let Some(TypeData::Pointer(_)) = self.find(modifier.underlying_type) {
+                    // A qualified pointer, as in `char * const`.
+                    format!("{} {}", inner, qualifiers.join(" "))
+                } else {
+                    format!("{} {}", qualifiers.join(" "), inner)
+                }
+            }
+            TypeData::Array(array) => format!("{}[]", self.type_name(array.element_type, depth + 1)),
+            TypeData::Bitfield(bitfield) => self.type_name(bitfield.underlying_type, depth + 1),
+            TypeData::Procedure(procedure) => {
+                let return_type = procedure.return_type.map_or_else(String::new, |r| self.type_name(r, depth + 1) + " ");
+                format!("{}({})", return_type, self.arguments(procedure.argument_list, depth + 1))
+            }
+            _ => "?".to_string(),
+        }
+    }
+}
+
+/// The keyword of a CodeView calling convention (`CV_call_e`), for the conventions that have one.
+fn convention_name(convention: u8) -> Option<&'static str> {
+    match convention {
+        0x00 => Some("__cdecl"),
+        0x04 => Some("__fastcall"),
+        0x07 => Some("__stdcall"),
+        0x0b => Some("__thiscall"),
+        0x16 => Some("__clrcall"),
+        0x18 => Some("__vectorcall"),
+        _ => None,
+    }
+}
+
+fn primitive_name(kind: PrimitiveKind) -> &'static str {
+    match kind {
+        // In an argument list, the absent type marks a variadic function.
+        PrimitiveKind::NoType => "...",
+        PrimitiveKind::Void => "void",
+        PrimitiveKind::Char => "signed char",
+        PrimitiveKind::UChar => "unsigned char",
+        PrimitiveKind::RChar => "char",
+        PrimitiveKind::WChar => "wchar_t",
+        PrimitiveKind::RChar16 => "char16_t",
+        PrimitiveKind::RChar32 => "char32_t",
+        PrimitiveKind::I8 => "__int8",
+        PrimitiveKind::U8 => "unsigned __int8",
+        PrimitiveKind::Short | PrimitiveKind::I16 => "short",
+        PrimitiveKind::UShort | PrimitiveKind::U16 => "unsigned short",
+        PrimitiveKind::Long => "long",
+        PrimitiveKind::ULong => "unsigned long",
+        PrimitiveKind::I32 => "int",
+        PrimitiveKind::U32 => "unsigned int",
+        PrimitiveKind::Quad | PrimitiveKind::I64 => "__int64",
+        PrimitiveKind::UQuad | PrimitiveKind::U64 => "unsigned __int64",
+        PrimitiveKind::Octa | PrimitiveKind::I128 => "__int128",
+        PrimitiveKind::UOcta | PrimitiveKind::U128 => "unsigned __int128",
+        PrimitiveKind::F16 => "half",
+        PrimitiveKind::F32 | PrimitiveKind::F32PP => "float",
+        PrimitiveKind::F48 => "__float48",
+        PrimitiveKind::F64 => "double",
+        PrimitiveKind::F80 => "long double",
+        PrimitiveKind::F128 => "__float128",
+        PrimitiveKind::Complex32
+        | PrimitiveKind::Complex64
+        | PrimitiveKind::Complex80
+        | PrimitiveKind::Complex128 => "_Complex",
+        PrimitiveKind::Bool8 | PrimitiveKind::Bool16 | PrimitiveKind::Bool32 | PrimitiveKind::Bool64 => "bool",
+        PrimitiveKind::HRESULT => "HRESULT",
+    }
+}
@@ -0,0 +1,477 @@
+//! Just enough of the SQLite file format to write whole tables of integers and text in one go and
+//! to read tables back, for `--export-sqlite` and `--db`.
+//!
+//! Written databases have 4 KiB pages, UTF-8 text and no indexes, free pages or journal; any
+//! SQLite can open them. Reading follows the table b-trees of any UTF-8 database and ignores
+//! indexes and write-ahead logs.
+
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+use std::fs;
+use std::path::Path;
+
+use crate::paths;
+
+const PAGE_SIZE: usize = 4096;
+const MAGIC: &[u8; 16] = b"SQLite format 3\0";
+/// The size of the database header at the start of page 1.
+const HEADER_SIZE: usize = 100;
+/// Table b-trees nest a few levels deep even for huge tables; deeper ones are corrupt.
+const MAX_DEPTH: usize = 32;
+
+/// A column value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Text(String),
+}
+
+impl Value {
+    pub fn as_integer(&self) -> Option<i64> {
+        match *self {
+            Value::Integer(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match *self {
+            Value::Text(ref text) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+/// A table to write: its name, its `CREATE TABLE` statement and its rows as (rowid, columns).
+///
+/// A column declared `INTEGER PRIMARY KEY` is the rowid and is written as `Null`.
+pub struct Table {
+    pub name: String,
+    pub sql: String,
+    pub rows: Vec<(i64, Vec<Value>)>,
+}
+
+/// Encodes `tables` as a database file with the given `user_version`.
+pub fn write(tables: &[Table], user_version: u32) -> Result<Vec<u8>, String> {
+    // Page 1 holds the header and the schema table; its contents are filled in last.
+    let mut pages = vec![Vec::new()];
+    let mut schema = Vec::new();
+    for table in tables {
+        let root = write_tree(&mut pages, table.rows.iter().map(|(rowid, values)| (*rowid, record(values))));
+        let values = vec![
+            Value::Text("table".to_string()),
+            Value::Text(table.name.clone()),
+            Value::Text(table.name.clone()),
+            Value::Integer(i64::from(root)),
+            Value::Text(table.sql.clone()),
+        ];
+        schema.push((schema.len() as i64 + 1, record(&values)));
+    }
+    let mut cells = Vec::new();
+    for (rowid, payload) in schema {
+        cells.push((rowid, leaf_cell(&mut pages, rowid, &payload)));
+    }
+    if cells.iter().map(|(_, cell)| cell.len() + 2).sum::<usize>() > PAGE_SIZE - HEADER_SIZE - 8 {
+        return Err("too many tables for the schema page".to_string());
+    }
+    pages[0] = leaf_page(HEADER_SIZE, &cells);
+
+    let count = pages.len() as u32;
+    let header = &mut pages[0][..HEADER_SIZE];
+    header[..16].copy_from_slice(MAGIC);
+    header[16..18].copy_from_slice(&(PAGE_SIZE as u16).to_be_bytes());
+    // File format versions (legacy, no WAL), no reserved bytes, fixed payload fractions.
+    header[18..24].copy_from_slice(&[1, 1, 0, 64, 32, 32]);
+    header[24..28].copy_from_slice(&1u32.to_be_bytes()); // change counter
+    header[28..32].copy_from_slice(&count.to_be_bytes());
+    header[40..44].copy_from_slice(&1u32.to_be_bytes()); // schema cookie
+    header[44..48].copy_from_slice(&4u32.to_be_bytes()); // schema format
+    header[56..60].copy_from_slice(&1u32.to_be_bytes()); // UTF-8
+    header[60..64].copy_from_slice(&user_version.to_be_bytes());
+    header[92..96].copy_from_slice(&1u32.to_be_bytes()); // version-valid-for
+    header[96..100].copy_from_slice(&3_008_000u32.to_be_bytes());
+    Ok(pages.concat())
+}
+
+/// Writes the table b-tree of `rows`, sorted by rowid, and returns its root page number.
+fn write_tree(pages: &mut Vec<Vec<u8>>, rows: impl Iterator<Item = (i64, Vec<u8>)>) -> u32 {
+    // The leaves, each with the greatest rowid it holds.
+    let mut level: Vec<(u32, i64)> = Vec::new();
+    let mut cells: Vec<(i64, Vec<u8>)> = Vec::new();
+    let mut used = 0;
+    for (rowid, payload) in rows {
+        let cell = leaf_cell(pages, rowid, &payload);
+        if used + cell.len() + 2 > PAGE_SIZE - 8 {
+            level.push((add_page(pages, leaf_page(0, &cells)), cells[cells.len() - 1].0));
+            cells.clear();
+            used = 0;
+        }
+        used += cell.len() + 2;
+        cells.push((rowid, cell));
+    }
+    if !cells.is_empty() || level.is_empty() {
+        let last = cells.last().map_or(0, |&(rowid, _)| rowid);
+        level.push((add_page(pages, leaf_page(0, &cells)), last));
+    }
+
+    // Interior pages point to all children but the last with (page, greatest rowid) cells and to
+    // the last with their right-most pointer.
+    while level.len() > 1 {
+        let mut parents = Vec::new();
+        let mut children: Vec<(u32, i64)> = Vec::new();
+        let mut used = 0;
+        for (page, key) in level {
+            let size = 4 + varint(key as u64).len() + 2;
+            if !children.is_empty() && used + size > PAGE_SIZE - 12 {
+                parents.push(finish_interior(pages, &children));
+                children.clear();
+                used = 0;
+            }
+            used += size;
+            children.push((page, key));
+        }
+        parents.push(finish_interior(pages, &children));
+        level = parents;
+    }
+    level[0].0
+}
+
+fn finish_interior(pages: &mut Vec<Vec<u8>>, children: &[(u32, i64)]) -> (u32, i64) {
+    let (last, key) = children[children.len() - 1];
+    let cells: Vec<(i64, Vec<u8>)> = children[..children.len() - 1]
+        .iter()
+        .map(|&(page, key)| {
+            let mut cell = page.to_be_bytes().to_vec();
+            cell.extend(varint(key as u64));
+            (key, cell)
+        })
+        .collect();
+    let mut page = vec![0; PAGE_SIZE];
+    write_cells(&mut page, 0, 0x05, &cells);
+    page[8..12].copy_from_slice(&last.to_be_bytes());
+    (add_page(pages, page), key)
+}
+
+fn add_page(pages: &mut Vec<Vec<u8>>, page: Vec<u8>) -> u32 {
+    pages.push(page);
+    pages.len() as u32
+}
+
+fn leaf_page(offset: usize, cells: &[(i64, Vec<u8>)]) -> Vec<u8> {
+    let mut page = vec![0; PAGE_SIZE];
+    write_cells(&mut page, offset, 0x0d, cells);
+    page
+}
+
+/// Fills in the b-tree page header at `offset` and packs `cells` at the end of the page.
+fn write_cells(page: &mut [u8], offset: usize, kind: u8, cells: &[(i64, Vec<u8>)]) {
+    let header = if kind == 0x05 { 12 } else { 8 };
+    let mut end = PAGE_SIZE;
+    for (i, (_, cell)) in cells.iter().enumerate() {
+        end -= cell.len();
+        page[end..end + cell.len()].copy_from_slice(cell);
+        let pointer = offset + header + 2 * i;
+        page[pointer..pointer + 2].copy_from_slice(&(end as u16).to_be_bytes());
+    }
+    page[offset] = kind;
+    page[offset + 3..offset + 5].copy_from_slice(&(cells.len() as u16).to_be_bytes());
+    page[offset + 5..offset + 7].copy_from_slice(&(end as u16).to_be_bytes());
+}
+
+/// Builds the cell of a table leaf, spilling the end of a large payload to overflow pages.
+fn leaf_cell(pages: &mut Vec<Vec<u8>>, rowid: i64, payload: &[u8]) -> Vec<u8> {
+    let mut cell = varint(payload.len() as u64);
+    cell.extend(varint(rowid as u64));
+    let local = local_size(PAGE_SIZE, payload.len());
+    cell.extend_from_slice(&payload[..local]);
+    if local < payload.len() {
+        let chunks: Vec<&[u8]> = payload[local..].chunks(PAGE_SIZE - 4).collect();
+        let first = pages.len() as u32 + 1;
+        for (i, chunk) in chunks.iter().enumerate() {
+            let next = if i + 1 < chunks.len() { first + i as u32 + 1 } else { 0 };
+            let mut page = next.to_be_bytes().to_vec();
+            page.extend_from_slice(chunk);
+            page.resize(PAGE_SIZE, 0);
+            pages.push(page);
+        }
+        cell.extend_from_slice(&first.to_be_bytes());
+    }
+    cell
+}
+
+/// The number of payload bytes a table leaf cell keeps on its page, given the usable page size.
+fn local_size(usable: usize, payload: usize) -> usize {
+    let max = usable - 35;
+    if payload <= max {
+        return payload;
+    }
+    let min = (usable - 12) * 32 / 255 - 23;
+    let size = min + (payload - min) % (usable - 4);
+    if size <= max {
+        size
+    } else {
+        min
+    }
+}
+
+/// Encodes `values` in the record format.
+fn record(values: &[Value]) -> Vec<u8> {
+    let mut types = Vec::new();
+    let mut body = Vec::new();
+    for value in values {
+        match *value {
+            Value::Null => types.extend(varint(0)),
+            Value::Integer(0) => types.extend(varint(8)),
+            Value::Integer(1) => types.extend(varint(9)),
+            Value::Integer(value) => {
+                let (serial, size) = match value {
+                    -0x80..=0x7f => (1, 1),
+                    -0x8000..=0x7fff => (2, 2),
+                    -0x80_0000..=0x7f_ffff => (3, 3),
+                    -0x8000_0000..=0x7fff_ffff => (4, 4),
+                    -0x8000_0000_0000..=0x7fff_ffff_ffff => (5, 6),
+                    _ => (6, 8),
+                };
+                types.extend(varint(serial));
+                body.extend_from_slice(&value.to_be_bytes()[8 - size..]);
+            }
+            Value::Text(ref text) => {
+                types.extend(varint(13 + 2 * text.len() as u64));
+                body.extend_from_slice(text.as_bytes());
+            }
+        }
+    }
+    // The header size counts its own varint.
+    let mut size = types.len() + 1;
+    while varint(size as u64).len() + types.len() != size {
+        size += 1;
+    }
+    let mut record = varint(size as u64);
+    record.extend(types);
+    record.extend(body);
+    record
+}
+
+/// Encodes `value` as a big-endian varint of at most 9 bytes.
+fn varint(value: u64) -> Vec<u8> {
+    if value >> 56 != 0 {
+        let mut bytes = vec![0; 9];
+        bytes[8] = value as u8;
+        let mut rest = value >> 8;
+        for byte in bytes[..8].iter_mut().rev() {
+            *byte = (rest & 0x7f) as u8 | 0x80;
+            rest >>= 7;
+        }
+        return bytes;
+    }
+    let mut bytes = vec![(value & 0x7f) as u8];
+    let mut rest = value >> 7;
+    while rest != 0 {
+        bytes.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+    bytes.reverse();
+    bytes
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for i in 0..9 {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        if i == 8 {
+            return Some((value << 8) | u64::from(byte));
+        }
+        value = (value << 7) | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// A database file read into memory.
+pub struct Database {
+    data: Vec<u8>,
+    page_size: usize,
+    usable: usize,
+    /// The root page of each table, by name.
+    roots: HashMap<String, u32>,
+}
+
+/// Returns whether the file at `path` starts like a SQLite database.
+pub fn is_database(path: &Path) -> bool {
+    use std::io::Read;
+    let mut magic = [0u8; 16];
+    fs::File::open(paths::long_path(path)).and_then(|mut file| file.read_exact(&mut magic)).is_ok() && &magic == MAGIC
+}
+
+impl Database {
+    /// Reads the database at `path`.
+    pub fn open(path: &Path) -> Result<Database, String> {
+        let data = fs::read(paths::long_path(path)).map_err(|e| format!("cannot read '{}': {}", path.display(), e))?;
+        Database::parse(data)
+    }
+
+    /// Checks the header of the database in `data` and reads its schema.
+    pub fn parse(data: Vec<u8>) -> Result<Database, String> {
+        if data.len() < HEADER_SIZE || &data[..16] != MAGIC {
+            return Err("not a SQLite database".to_string());
+        }
+        let page_size = match u16::from_be_bytes([data[16], data[17]]) {
+            1 => 65536,
+            size => usize::from(size),
+        };
+        if page_size < 512 || !page_size.is_power_of_two() {
+            return Err(format!("invalid page size {}", page_size));
+        }
+        if be32(&data, 56) != Some(1) {
+            return Err("only UTF-8 databases are supported".to_string());
+        }
+        let usable = page_size - usize::from(data[20]);
+        let mut database = Database { data, page_size, usable, roots: HashMap::new() };
+        for (_, values) in database.rows(1)? {
+            let entry = (values.first().and_then(Value::as_text), values.get(1).and_then(Value::as_text), values.get(3));
+            if let (Some("table"), Some(name), Some(&Value::Integer(root))) = entry {
+                let root = u32::try_from(root).map_err(|_| format!("invalid root page of table '{}'", name))?;
+                database.roots.insert(name.to_string(), root);
+            }
+        }
+        Ok(database)
+    }
+
+    /// The `user_version` stored in the header, which applications use to version their schema.
+    pub fn user_version(&self) -> u32 {
+        be32(&self.data, 60).unwrap_or(0)
+    }
+
+    /// Returns the rows of the table `name` as (rowid, columns), in rowid order.
+    pub fn table(&self, name: &str) -> Result<Vec<(i64, Vec<Value>)>, String> {
+        let root = *self.roots.get(name).ok_or_else(|| format!("no table '{}'", name))?;
+        self.rows(root)
+    }
+
+    fn rows(&self, root: u32) -> Result<Vec<(i64, Vec<Value>)>, String> {
+        let mut rows = Vec::new();
+        self.visit(root, 0, &mut rows).ok_or_else(|| format!("corrupt b-tree at page {}", root))?;
+        Ok(rows)
+    }
+
+    fn page(&self, number: u32) -> Option<&[u8]> {
+        let start = (number as usize).checked_sub(1)?.checked_mul(self.page_size)?;
+        self.data.get(start..start + self.page_size)
+    }
+
+    fn visit(&self, number: u32, depth: usize, rows: &mut Vec<(i64, Vec<Value>)>) -> Option<()> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        let page = self.page(number)?;
+        let offset = if number == 1 { HEADER_SIZE } else { 0 };
+        let kind = *page.get(offset)?;
+        let count = usize::from(be16(page, offset + 3)?);
+        let header = match kind {
+            0x05 => 12,
+            0x0d => 8,
+            _ => return None,
+        };
+        for i in 0..count {
+            let mut pos = usize::from(be16(page, offset + header + 2 * i)?);
+            if kind == 0x05 {
+                self.visit(be32(page, pos)?, depth + 1, rows)?;
+                continue;
+            }
+            let size = usize::try_from(read_varint(page, &mut pos)?).ok()?;
+            let rowid = read_varint(page, &mut pos)? as i64;
+            let local = local_size(self.usable, size);
+            let mut payload = page.get(pos..pos + local)?.to_vec();
+            if local < size {
+                let mut next = be32(page, pos + local)?;
+                while payload.len() < size {
+                    let overflow = self.page(next)?;
+                    let take = (size - payload.len()).min(self.usable - 4);
+                    payload.extend_from_slice(overflow.get(4..4 + take)?);
+                    next = be32(overflow, 0)?;
+                }
+            }
+            rows.push((rowid, parse_record(&payload)?));
+        }
+        if kind == 0x05 {
+            self.visit(be32(page, offset + 8)?, depth + 1, rows)?;
+        }
+        Some(())
+    }
+}
+
+fn parse_record(payload: &[u8]) -> Option<Vec<Value>> {
+    let mut pos = 0;
+    let header_size = usize::try_from(read_varint(payload, &mut pos)?).ok()?;
+    let mut types = Vec::new();
+    while pos < header_size {
+        types.push(read_varint(payload, &mut pos)?);
+    }
+    let mut body = header_size;
+    let mut values = Vec::with_capacity(types.len());
+    for serial in types {
+        let size = match serial {
+            0 | 8 | 9 => 0,
+            1..=4 => serial as usize,
+            5 => 6,
+            6 | 7 => 8,
+            12.. => ((serial - 12) / 2) as usize,
+            _ => return None,
+        };
+        let bytes = payload.get(body..body + size)?;
+        body += size;
+        values.push(match serial {
+            0 => Value::Null,
+            8 => Value::Integer(0),
+            9 => Value::Integer(1),
+            1..=6 => {
+                // Sign-extend the big-endian two's complement value.
+                let mut full = if bytes[0] & 0x80 != 0 { [0xff; 8] } else { [0; 8] };
+                full[8 - size..].copy_from_slice(bytes);
+                Value::Integer(i64::from_be_bytes(full))
+            }
+            // Floats and blobs are not used by the tables this tool reads.
+            7 => Value::Null,
+            _ if serial % 2 == 1 => Value::Text(String::from_utf8_lossy(bytes).into_owned()),
+            _ => Value::Null,
+        });
+    }
+    Some(values)
+}
+
+fn be16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn be32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write, Database, Table, Value};
+
+    #[test]
+    fn round_trip() {
+        let long = "x".repeat(10_000);
+        let rows: Vec<(i64, Vec<Value>)> = (1..=5000)
+            .map(|i| {
+                let name = if i == 42 { long.clone() } else { format!("f{}", i) };
+                (i, vec![Value::Null, Value::Text(name), Value::Integer(i * 0x1_0001 - 70_000)])
+            })
+            .collect();
+        let table = Table {
+            name: "functions".to_string(),
+            sql: "CREATE TABLE functions (id INTEGER PRIMARY KEY, name TEXT, address INTEGER)".to_string(),
+            rows: rows.clone(),
+        };
+        let database = Database::parse(write(&[table], 7).unwrap()).unwrap();
+        assert_eq!(database.user_version(), 7);
+        assert_eq!(database.table("functions").unwrap(), rows);
+        assert!(database.table("lines").is_err());
+    }
+}
@@ -0,0 +1,50 @@
+//! Symbol stores in the two-tier layout of Microsoft's SymStore (`name/ID/name`).
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use pdb::PDB;
+
+use crate::paths;
+
+/// Returns the identifier a symbol store files a PDB under: its GUID followed by its age, in
+/// upper case hex.
+///
+/// The age is the one the linker wrote to the DBI stream, which matches the age recorded in
+/// the image; the PDB stream's age is only used for old PDBs without one.
+pub fn debug_id(path: &Path) -> pdb::Result<String> {
+    let mut pdb = PDB::open(fs::File::open(paths::long_path(path))?)?;
+    let info = pdb.pdb_information()?;
+    let age = pdb.debug_information()?.age().unwrap_or(info.age);
+    Ok(format!("{:X}{:X}", info.guid.to_simple(), age))
+}
+
+/// The path a file named `name` with the given debug id has in the store at `store`.
+pub fn store_path(store: &Path, name: &str, id: &str) -> PathBuf {
+    store.join(name).join(id).join(name)
+}
+
+/// Adds the PDB at `path` to the store at `store`, returning where it was placed.
+///
+/// The file is hard linked if possible and copied otherwise, for example across volumes. A PDB
+/// that is already in the store is left alone.
+pub fn add(path: &Path, store: &Path) -> Result<PathBuf, String> {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("invalid PDB path '{}'", path.display()))?;
+    let id = debug_id(path).map_err(|e| e.to_string())?;
+    let destination = store_path(store, name, &id);
+    if paths::long_path(&destination).is_file() {
+        return Ok(destination);
+    }
+
+    let place = |destination: &Path| -> io::Result<()> {
+        let (source, destination) = (paths::long_path(path), paths::long_path(destination));
+        fs::create_dir_all(destination.parent().expect("store path has a parent"))?;
+        fs::hard_link(&source, &destination).or_else(|_| fs::copy(&source, &destination).map(|_| ()))
+    };
+    place(&destination).map_err(|e| format!("cannot write '{}': {}", destination.display(), e))?;
+    Ok(destination)
+}
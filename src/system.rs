@@ -0,0 +1,64 @@
+//! Telling frames of the C runtime and the C++ standard library from the application's own, for
+//! the `[system]` tag and `--hide-system-frames`.
+
+use std::path::Path;
+
+use crate::output::Resolution;
+
+/// The libraries of the MSVC C runtime, vcruntime, the Universal CRT and the C++ standard
+/// library, release and debug, static and import.
+const RUNTIME_LIBRARIES: &[&str] = &[
+    "libcmt", "libcmtd", "msvcrt", "msvcrtd", "libvcruntime", "libvcruntimed", "vcruntime", "vcruntimed", "libucrt",
+    "libucrtd", "ucrt", "ucrtd", "libcpmt", "libcpmtd", "msvcprt", "msvcprtd", "libconcrt", "libconcrtd", "oldnames",
+];
+
+/// Directories of runtime sources and of the toolset and SDK headers, whose code ends up inlined
+/// into the application's functions.
+const RUNTIME_SOURCES: &[&str] =
+    &["\\vc\\tools\\msvc\\", "\\windows kits\\", "\\vctools\\crt\\", "\\minkernel\\crts\\", "\\vcstartup\\src\\"];
+
+/// Whether the compiland whose object file is `object_file`, the library for library members,
+/// belongs to the runtime.
+pub fn is_runtime_compiland(object_file: &str) -> bool {
+    let name = object_file.rsplit(['\\', '/']).next().unwrap_or(object_file);
+    let stem = Path::new(name).file_stem().map_or_else(String::new, |stem| stem.to_string_lossy().to_lowercase());
+    RUNTIME_LIBRARIES.contains(&stem.as_str())
+}
+
+/// Whether `file` is a runtime source file or a toolset or SDK header.
+pub fn is_runtime_source(file: &str) -> bool {
+    let file = file.replace('/', "\\").to_lowercase();
+    RUNTIME_SOURCES.iter().any(|dir| file.contains(dir))
+}
+
+/// Marks the frames whose code comes from a runtime source file or header as system frames, in
+/// addition to those of runtime compilands, which the providers mark.
+pub fn classify(results: &mut [Resolution]) {
+    for frame in results.iter_mut().flat_map(|result| result.frames.iter_mut()) {
+        if is_runtime_source(&frame.file) {
+            frame.system = true;
+        }
+    }
+}
+
+/// Drops the system frames.
+pub fn hide(results: &mut [Resolution]) {
+    for result in results {
+        result.frames.retain(|frame| !frame.system);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_runtime_compiland, is_runtime_source};
+
+    #[test]
+    fn runtime_code() {
+        assert!(is_runtime_compiland(r"D:\a01\_work\12\s\binaries\amd64ret\lib\amd64\libcmt.lib"));
+        assert!(is_runtime_compiland("MSVCRTD.lib"));
+        assert!(!is_runtime_compiland(r"C:\build\app\main.obj"));
+        assert!(is_runtime_source(r"C:\Program Files\Microsoft Visual Studio\2022\Community\VC\Tools\MSVC\14.38.33130\include\vector"));
+        assert!(is_runtime_source(r"D:\a01\_work\12\s\src\vctools\crt\vcstartup\src\startup\exe_common.inl"));
+        assert!(!is_runtime_source(r"C:\src\app\vector.cpp"));
+    }
+}
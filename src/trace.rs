@@ -0,0 +1,136 @@
+//! `--rebase-from-trace`: inferring the load address of a module from a pasted stack trace, so
+//! logs of ASLR-relocated processes can be symbolized without looking up the base first.
+
+use std::fs;
+use std::path::Path;
+
+use pdb::PDB;
+
+use crate::load_publics;
+use crate::paths;
+
+/// A frame of a trace that names its module and symbol, like `00007ff6`12341234 app!main+0x24`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceFrame {
+    pub address: u64,
+    pub module: String,
+    pub symbol: String,
+    pub offset: u64,
+}
+
+/// Finds the frames of the trace `text` that give both an address and `MODULE!SYMBOL[+OFFSET]`.
+///
+/// The address is the hexadecimal token right before the symbolic frame, as in `dps` output or
+/// logs of `ADDRESS MODULE!SYMBOL+OFFSET` lines; tokens may use WinDbg's `fffff803`12400000`
+/// form. Lines without both are skipped.
+pub fn parse_frames(text: &str) -> Vec<TraceFrame> {
+    let mut frames = Vec::new();
+    for line in text.lines() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        for (i, token) in tokens.iter().enumerate().skip(1) {
+            let (module, rest) = match token.split_once('!') {
+                Some((module, rest)) if !module.is_empty() && !rest.is_empty() => (module, rest),
+                _ => continue,
+            };
+            let (symbol, offset) = match rest.rsplit_once('+') {
+                Some((symbol, offset)) => match parse_hex(offset) {
+                    Some(offset) => (symbol, offset),
+                    None => continue,
+                },
+                None => (rest, 0),
+            };
+            if let Some(address) = parse_hex(tokens[i - 1]) {
+                frames.push(TraceFrame { address, module: module.to_string(), symbol: symbol.to_string(), offset });
+                break;
+            }
+        }
+    }
+    frames
+}
+
+fn parse_hex(s: &str) -> Option<u64> {
+    let digits: String = s.trim_start_matches("0x").chars().filter(|&c| c != '`').collect();
+    if digits.is_empty() {
+        return None;
+    }
+    u64::from_str_radix(&digits, 16).ok()
+}
+
+/// Whether the trace's `symbol` names the public symbol `public`.
+///
+/// Traces show exports undecorated, so the `_` prefix and `@N` suffix of 32-bit C names are
+/// ignored.
+fn names_public(symbol: &str, public: &str) -> bool {
+    if symbol == public {
+        return true;
+    }
+    let undecorated = public.strip_prefix('_').unwrap_or(public);
+    let undecorated = match undecorated.rsplit_once('@') {
+        Some((name, size)) if !name.is_empty() && size.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => undecorated,
+    };
+    symbol == undecorated
+}
+
+/// Infers where `module` was loaded from the first frame in `frames` that lies in it and names
+/// one of its public symbols, which covers exports and entry points.
+///
+/// Returns the base with the frame it was inferred from.
+pub fn infer_base<'f>(
+    frames: &'f [TraceFrame],
+    module: &str,
+    publics: &[(u32, String)],
+) -> Option<(u64, &'f TraceFrame)> {
+    frames.iter().filter(|frame| frame.module.eq_ignore_ascii_case(module)).find_map(|frame| {
+        let &(rva, _) = publics.iter().find(|(_, name)| names_public(&frame.symbol, name))?;
+        let base = frame.address.checked_sub(frame.offset)?.checked_sub(u64::from(rva))?;
+        Some((base, frame))
+    })
+}
+
+/// Infers the base of the module with symbol file `pdb` named `module` from the trace at `trace`.
+pub fn rebase_from_trace(trace: &Path, module: &str, pdb: &Path) -> Result<u64, String> {
+    let text = fs::read_to_string(paths::long_path(trace))
+        .map_err(|e| format!("cannot read trace '{}': {}", trace.display(), e))?;
+    let frames = parse_frames(&text);
+    let publics = (|| -> pdb::Result<_> {
+        let mut pdb = PDB::open(fs::File::open(paths::long_path(pdb))?)?;
+        let address_map = pdb.address_map()?;
+        load_publics(&mut pdb, &address_map, usize::MAX)
+    })()
+    .map_err(|e| format!("error reading '{}': {}", pdb.display(), e))?;
+
+    match infer_base(&frames, module, &publics) {
+        Some((base, _)) => Ok(base),
+        None => Err(format!(
+            "no frame of '{}' in trace '{}' names one of its public symbols, so its base cannot be inferred",
+            module,
+            trace.display()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{infer_base, parse_frames, TraceFrame};
+
+    #[test]
+    fn base_from_export() {
+        let trace = "\
+00000000`0012f3a0  00007ff6`12345678 app!helper+0x18
+00000000`0012f3a8  00007ffd`00011234 KERNEL32!BaseThreadInitThunk+0x14
+0x7ff612341050 app!wmainCRTStartup+0x50";
+        let frames = parse_frames(trace);
+        assert_eq!(
+            frames[1],
+            TraceFrame { address: 0x7ffd_0001_1234, module: "KERNEL32".into(), symbol: "BaseThreadInitThunk".into(), offset: 0x14 }
+        );
+        assert_eq!(frames.len(), 3);
+
+        // `helper` is not public, so the entry point decides.
+        let publics = vec![(0x1000, "_wmainCRTStartup".to_string())];
+        let (base, frame) = infer_base(&frames, "APP", &publics).unwrap();
+        assert_eq!(base, 0x7ff6_1234_0000);
+        assert_eq!(frame.symbol, "wmainCRTStartup");
+    }
+}
@@ -0,0 +1,84 @@
+//! The `type` subcommand: classes, structs, unions and enums looked up in the type records (TPI
+//! stream) alone, which type server PDBs have although they lack the DBI stream and everything
+//! resolving addresses needs.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use pdb::{ClassKind, FallibleIterator, TypeData, TypeIndex, PDB};
+
+use crate::paths;
+use crate::signature::Signatures;
+
+/// Writes the definition of every class, struct, union and enum whose name contains `query`:
+/// its size and data members with their offsets, or its enumerators with their values. Returns
+/// how many were found.
+pub fn write_types(out: &mut dyn Write, path: &Path, query: &str) -> Result<usize, String> {
+    let error = |e: std::io::Error| e.to_string();
+    let reading = |e: pdb::Error| e.to_string();
+    let mut pdb = PDB::open(fs::File::open(paths::long_path(path)).map_err(error)?).map_err(reading)?;
+    let types = pdb.type_information().map_err(reading)?;
+    let signatures = Signatures::new(&types).map_err(reading)?;
+
+    let mut found = 0;
+    let mut records = types.iter();
+    while let Some(record) = records.next().map_err(reading)? {
+        // Forward references only name a type defined by a later record.
+        let (name, header, fields) = match record.parse() {
+            Ok(TypeData::Class(class)) if !class.properties.forward_reference() => {
+                let kind = match class.kind {
+                    ClassKind::Class => "class",
+                    ClassKind::Struct => "struct",
+                    ClassKind::Interface => "interface",
+                };
+                (class.name, format!("{} {}, {} bytes", kind, class.name, class.size), class.fields)
+            }
+            Ok(TypeData::Union(union)) if !union.properties.forward_reference() => {
+                (union.name, format!("union {}, {} bytes", union.name, union.size), Some(union.fields))
+            }
+            Ok(TypeData::Enumeration(enumeration)) if !enumeration.properties.forward_reference() => (
+                enumeration.name,
+                format!("enum {} : {}", enumeration.name, signatures.type_name(enumeration.underlying_type, 0)),
+                Some(enumeration.fields),
+            ),
+            _ => continue,
+        };
+        if !name.to_string().contains(query) {
+            continue;
+        }
+        found += 1;
+        writeln!(out, "{}", header).map_err(error)?;
+        for field in field_list(&signatures, fields) {
+            match field {
+                TypeData::Member(member) => writeln!(
+                    out,
+                    "  +{:#x} {} {}",
+                    member.offset,
+                    signatures.type_name(member.field_type, 0),
+                    member.name
+                ),
+                TypeData::Enumerate(enumerate) => writeln!(out, "  {} = {}", enumerate.name, enumerate.value),
+                _ => continue,
+            }
+            .map_err(error)?;
+        }
+    }
+    Ok(found)
+}
+
+/// The fields of the field list `index` and the lists it continues in.
+fn field_list<'t>(signatures: &Signatures<'t>, mut index: Option<TypeIndex>) -> Vec<TypeData<'t>> {
+    let mut fields = Vec::new();
+    // A malformed list could continue in itself; no real one is anywhere near this long.
+    for _ in 0..1024 {
+        match index.and_then(|index| signatures.find(index)) {
+            Some(TypeData::FieldList(list)) => {
+                fields.extend(list.fields);
+                index = list.continuation;
+            }
+            _ => break,
+        }
+    }
+    fields
+}
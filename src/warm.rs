@@ -0,0 +1,137 @@
+//! The `warm` subcommand: writing the `--export-sqlite` database of every PDB in a symbol store
+//! ahead of time, so later lookups of those PDBs start instantly instead of reading the PDB.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::export;
+use crate::paths;
+
+/// The database `warm` writes for the PDB at `pdb`: next to it, with the extension `sqlite`.
+pub fn database_path(pdb: &Path) -> PathBuf {
+    pdb.with_extension("sqlite")
+}
+
+/// The PDBs in the store at `store` whose file names match `filter`, in the `name/ID/name`
+/// layout of store.rs, sorted by path.
+fn store_pdbs(store: &Path, filter: &str) -> io::Result<Vec<PathBuf>> {
+    let mut pdbs = Vec::new();
+    for name in fs::read_dir(paths::long_path(store))? {
+        let name = name?.file_name();
+        if !name.to_str().is_some_and(|name| matches(filter, name)) {
+            continue;
+        }
+        let ids = match fs::read_dir(paths::long_path(&store.join(&name))) {
+            Ok(ids) => ids,
+            Err(_) => continue,
+        };
+        for id in ids.filter_map(Result::ok) {
+            let pdb = id.path().join(&name);
+            if paths::long_path(&pdb).is_file() {
+                pdbs.push(pdb);
+            }
+        }
+    }
+    pdbs.sort();
+    Ok(pdbs)
+}
+
+/// Whether the database of `pdb` was written after the PDB was, so an interrupted run can be
+/// resumed without writing it again and lookups can read the database instead of the PDB.
+pub fn is_current(pdb: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(paths::long_path(path)).and_then(|metadata| metadata.modified()).ok();
+    match (modified(pdb), modified(&database_path(pdb))) {
+        (Some(pdb), Some(database)) => database >= pdb,
+        _ => false,
+    }
+}
+
+/// Writes the databases of the PDBs in `store` matching `filter` that are missing or older than
+/// their PDB, on `jobs` threads. Each written database is printed and each failure reported on
+/// stderr. Returns the number of failures.
+pub fn warm(store: &Path, filter: &str, jobs: usize) -> Result<usize, String> {
+    let pdbs = store_pdbs(store, filter).map_err(|e| format!("cannot read the store '{}': {}", store.display(), e))?;
+    let queue = Arc::new(Mutex::new(pdbs.into_iter().filter(|pdb| !is_current(pdb))));
+    let workers: Vec<_> = (0..jobs.max(1))
+        .map(|_| {
+            let queue = Arc::clone(&queue);
+            thread::spawn(move || {
+                let mut failures = 0;
+                loop {
+                    let next = queue.lock().expect("no worker panics holding the queue").next();
+                    let pdb = match next {
+                        Some(pdb) => pdb,
+                        None => break failures,
+                    };
+                    match write_database(&pdb) {
+                        Ok(database) => println!("{}", database.display()),
+                        Err(e) => {
+                            writeln!(&mut io::stderr(), "warning: {}", e).expect("stderr write");
+                            failures += 1;
+                        }
+                    }
+                }
+            })
+        })
+        .collect();
+    Ok(workers.into_iter().map(|worker| worker.join().expect("warm worker panicked")).sum())
+}
+
+/// Writes the database of `pdb` under a temporary name first, so an interrupted run never
+/// leaves a truncated database that looks current.
+fn write_database(pdb: &Path) -> Result<PathBuf, String> {
+    let database = database_path(pdb);
+    let partial = pdb.with_extension("sqlite.partial");
+    export::export_sqlite(pdb, &partial, None)?;
+    fs::rename(paths::long_path(&partial), paths::long_path(&database))
+        .map_err(|e| format!("cannot write '{}': {}", database.display(), e))?;
+    Ok(database)
+}
+
+/// Whether `name` matches `pattern`, where `*` stands for any run of characters and `?` for any
+/// one character, ignoring ASCII case as Windows file names do.
+fn matches(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) =
+        (pattern.chars().map(|c| c.to_ascii_lowercase()).collect(), name.chars().map(|c| c.to_ascii_lowercase()).collect());
+    // The positions after the last `*` to retry from when the rest does not match.
+    let (mut p, mut n, mut retry) = (0, 0, None);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                retry = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match retry {
+                Some((star, from)) => {
+                    retry = Some((star, from + 1));
+                    p = star;
+                    n = from + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matches;
+
+    #[test]
+    fn filters() {
+        assert!(matches("*.pdb", "App.PDB"));
+        assert!(matches("app*.pdb", "app_core.pdb"));
+        assert!(matches("?pp.*", "app.pdb"));
+        assert!(matches("*", "anything"));
+        assert!(!matches("*.pdb", "app.dll"));
+        assert!(!matches("app.pdb", "app.pdbx"));
+    }
+}
@@ -0,0 +1,183 @@
+//! End-to-end tests running the built binary in its major modes against the fixtures and
+//! comparing its output with the golden files in tests/fixtures/golden.
+//!
+//! Run with `UPDATE_GOLDEN=1` to rewrite the golden files after an intended change of output.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+/// Runs pdb-addr2line with `args` and `stdin`, in the fixtures directory so that paths in the
+/// output do not depend on the checkout.
+fn run(args: &[&str], stdin: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pdb-addr2line"))
+        .args(args)
+        .current_dir(fixture(""))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("run pdb-addr2line");
+    child.stdin.take().expect("stdin is piped").write_all(stdin.as_bytes()).expect("write stdin");
+    let output = child.wait_with_output().expect("wait for pdb-addr2line");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).expect("utf-8 output")
+}
+
+fn check_golden(name: &str, actual: &str) {
+    let path = fixture("golden").join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, actual).expect("write golden file");
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|e| panic!("cannot read {}: {}", path.display(), e));
+    assert_eq!(actual, expected, "output differs from {}", path.display());
+}
+
+#[test]
+fn text() {
+    check_golden("text.txt", &run(&["-v", "--relative-to-function", "split_lines.pdb", "0x1004", "0x1014", "0x101c", "0x1040"], ""));
+}
+
+#[test]
+fn json() {
+    let args = ["--json", "--confidence", "split_lines.pdb", "0x1004", "0x101c", "0x1040"];
+    check_golden("json.json", &run(&args, ""));
+    // Shortened paths are for display only, JSON keeps the full ones.
+    assert_eq!(run(&[&["--basenames-only-unique"][..], &args].concat(), ""), run(&args, ""));
+}
+
+#[test]
+fn styles() {
+    let mut output = String::new();
+    for style in &["windbg", "gcc"] {
+        output += &run(&["--style", style, "split_lines.pdb", "0x1004", "0x1014"], "");
+    }
+    output += &run(&["-a", "-p", "-i", "--functions", "split_lines.pdb", "0x1004"], "");
+    check_golden("styles.txt", &output);
+}
+
+#[test]
+fn timeout_batches() {
+    // More addresses than --timeout resolves at a time, all in the PDB's one function.
+    let addresses: Vec<String> = (0..2100).map(|i| format!("{:#x}", 0x1000 + i % 0x40)).collect();
+    let args = |timeout: &[&'static str]| -> Vec<&str> {
+        timeout.iter().copied().chain(Some("split_lines.pdb")).chain(addresses.iter().map(String::as_str)).collect()
+    };
+    let batched = run(&args(&["--timeout", "60"]), "");
+    assert_eq!(batched.lines().filter(|line| line.contains("main")).count(), 2100);
+    assert_eq!(batched, run(&args(&[]), ""));
+}
+
+#[test]
+fn breakpad_symbols() {
+    check_golden("breakpad.txt", &run(&["-v", "inline.sym", "0x1004", "0x1014"], ""));
+}
+
+#[test]
+fn sqlite_export_and_stdin_addresses() {
+    let db = Path::new(env!("CARGO_TARGET_TMPDIR")).join("split_lines.sqlite");
+    run(&["--export-sqlite", db.to_str().expect("utf-8 path"), "split_lines.pdb"], "");
+    // The database answers as the PDB does, for addresses read from stdin.
+    let from_db = run(&["--db", db.to_str().expect("utf-8 path")], "0x1004\n0x1014\n0x101c\n");
+    assert_eq!(from_db, run(&["split_lines.pdb", "0x1004", "0x1014", "0x101c"], ""));
+    check_golden("sqlite.txt", &from_db);
+}
+
+#[test]
+fn json_rpc() {
+    let requests = concat!(
+        r#"{"jsonrpc":"2.0","id":1,"method":"resolve","params":{"file":"split_lines.pdb","addresses":["0x1004","0x1014"]}}"#,
+        "\n",
+        r#"{"jsonrpc":"2.0","id":2,"method":"findSymbol","params":{"file":"split_lines.pdb","name":"ma"}}"#,
+        "\n",
+        r#"{"jsonrpc":"2.0","id":3,"method":"nope"}"#,
+        "\n",
+    );
+    check_golden("jsonrpc.txt", &run(&["--stdio-jsonrpc"], requests));
+
+    // A Content-Length beyond the limit is answered with an error, not allocated.
+    let response = run(&["--stdio-jsonrpc"], "Content-Length: 1000000000000\r\n\r\n{}");
+    assert!(response.contains(r#""error":{"code":-32600,"message":"message longer than the limit"#), "{}", response);
+}
+
+#[test]
+fn deterministic_runs() {
+    let tmp = Path::new(env!("CARGO_TARGET_TMPDIR")).join("deterministic");
+    let _ = fs::remove_dir_all(&tmp);
+    fs::create_dir_all(&tmp).expect("create temporary directory");
+    let lm = tmp.join("lm.txt");
+    fs::write(&lm, "00001000 00003000   split_lines  (pdb symbols)  split_lines.pdb\n").expect("write lm listing");
+    let store = tmp.join("store");
+    for name in &["a.pdb", "b.pdb", "c.pdb"] {
+        fs::copy(fixture("split_lines.pdb"), tmp.join(name)).expect("copy fixture");
+        run(&["store", "add", tmp.join(name).to_str().expect("utf-8 path"), "--store", store.to_str().expect("utf-8 path")], "");
+    }
+
+    let mut outputs = Vec::new();
+    for _ in 0..2 {
+        let mut output = String::new();
+        for format in &["text", "json"] {
+            let lm = lm.to_str().expect("utf-8 path");
+            output += &run(&["--deterministic", "--format", format, "--loaded-modules", lm, "--list-modules"], "");
+            output += &run(&["--deterministic", "--format", format, "split_lines.pdb", "0x1004", "0x101c"], "");
+        }
+        // Warm again from scratch: the databases are in the `name/ID` directories of the store.
+        for name in &["a", "b", "c"] {
+            for id in fs::read_dir(store.join(format!("{}.pdb", name))).expect("read store") {
+                let _ = fs::remove_file(id.expect("read store").path().join(format!("{}.sqlite", name)));
+            }
+        }
+        output += &run(&["--deterministic", "warm", "--store", store.to_str().expect("utf-8 path")], "");
+        outputs.push(output);
+    }
+    assert!(!outputs[0].contains(" ms") && !outputs[0].contains("load_ms"), "{}", outputs[0]);
+    assert_eq!(outputs[0].matches(".sqlite").count(), 3, "{}", outputs[0]);
+    assert_eq!(outputs[0], outputs[1]);
+}
+
+#[test]
+fn invalid_warm_database() {
+    let tmp = Path::new(env!("CARGO_TARGET_TMPDIR")).join("invalid_warm");
+    let _ = fs::remove_dir_all(&tmp);
+    fs::create_dir_all(&tmp).expect("create temporary directory");
+    let pdb = tmp.join("app.pdb");
+    fs::copy(fixture("split_lines.pdb"), &pdb).expect("copy fixture");
+    // Newer than the PDB, so it looks current, but not a database.
+    fs::write(tmp.join("app.sqlite"), "not a database").expect("write database");
+    let pdb = pdb.to_str().expect("utf-8 path");
+    assert_eq!(run(&[pdb, "0x1004", "0x101c"], ""), run(&["split_lines.pdb", "0x1004", "0x101c"], ""));
+    assert!(!run(&["--dry-run", pdb, "0x1004"], "").contains("app.sqlite"));
+}
+
+#[test]
+fn warm_database() {
+    let tmp = Path::new(env!("CARGO_TARGET_TMPDIR")).join("warm_database");
+    let _ = fs::remove_dir_all(&tmp);
+    fs::create_dir_all(&tmp).expect("create temporary directory");
+    let store = tmp.join("store");
+    let store = store.to_str().expect("utf-8 path");
+    run(&["store", "add", fixture("split_lines.pdb").to_str().expect("utf-8 path"), "--store", store], "");
+    let pdb = Path::new(run(&["warm", "--store", store], "").trim()).with_extension("pdb");
+    let pdb = pdb.to_str().expect("utf-8 path");
+    assert!(run(&["--dry-run", pdb, "0x1004"], "").contains("(written by warm)"));
+
+    // The database answers exactly as the PDB does.
+    let query = |options: &[&str]| {
+        let mut output = String::new();
+        for style in &[&["--json", "--confidence", "-v"][..], &["--style", "gcc"], &["-v", "--functions-only"]] {
+            let addresses = vec![pdb, "0x1004", "0x101c", "0x1040"];
+            let args: Vec<&str> = options.iter().chain(style.iter()).copied().chain(addresses).collect();
+            output += &run(&args, "");
+        }
+        output
+    };
+    let from_database = query(&[]);
+    assert_eq!(from_database, query(&["--deterministic"]));
+    check_golden("warm.txt", &from_database);
+}
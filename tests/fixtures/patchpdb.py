@@ -0,0 +1,93 @@
+#!/usr/bin/env python3
+"""Adds section headers and public symbols to a PDB written by llvm-pdbutil yaml2pdb.
+
+usage: patchpdb.py in.pdb out.pdb SECTIONS PUBLICS
+  SECTIONS: comma separated name:rva:size, e.g. .text:0x1000:0x2000
+  PUBLICS:  comma separated seg:off:name (function publics)
+"""
+import struct, sys
+
+def read_msf(data):
+    bs, fpm, nblocks, dirbytes, _, bmaddr = struct.unpack_from('<6I', data, 32)
+    ndirblocks = (dirbytes + bs - 1) // bs
+    dirblocks = struct.unpack_from('<%dI' % ndirblocks, data, bmaddr * bs)
+    d = b''.join(data[b * bs:(b + 1) * bs] for b in dirblocks)[:dirbytes]
+    n = struct.unpack_from('<I', d, 0)[0]
+    sizes = struct.unpack_from('<%dI' % n, d, 4)
+    pos = 4 + 4 * n
+    streams = []
+    for sz in sizes:
+        if sz == 0xffffffff:
+            streams.append(None)
+            continue
+        nb = (sz + bs - 1) // bs
+        blocks = struct.unpack_from('<%dI' % nb, d, pos)
+        pos += 4 * nb
+        streams.append(b''.join(data[b * bs:(b + 1) * bs] for b in blocks)[:sz])
+    return bs, streams
+
+def write_msf(bs, streams):
+    blocks = [None, b'', b'']  # superblock, fpm1, fpm2
+    dirparts = [struct.pack('<I', len(streams))]
+    dirparts += [struct.pack('<I', 0xffffffff if s is None else len(s)) for s in streams]
+    for s in streams:
+        if not s:
+            continue
+        for i in range(0, len(s), bs):
+            dirparts.append(struct.pack('<I', len(blocks)))
+            blocks.append(s[i:i + bs])
+    directory = b''.join(dirparts)
+    dirblocks = []
+    for i in range(0, len(directory), bs):
+        dirblocks.append(len(blocks))
+        blocks.append(directory[i:i + bs])
+    bmaddr = len(blocks)
+    blocks.append(struct.pack('<%dI' % len(dirblocks), *dirblocks))
+    nblocks = len(blocks)
+    fpm = bytearray(b'\xff' * bs)
+    for b in range(nblocks):
+        fpm[b // 8] &= ~(1 << (b % 8)) & 0xff
+    blocks[1] = bytes(fpm)
+    blocks[0] = b'Microsoft C/C++ MSF 7.00\r\n\x1aDS\0\0\0' + struct.pack(
+        '<6I', bs, 1, nblocks, len(directory), 0, bmaddr)
+    return b''.join(b.ljust(bs, b'\0') for b in blocks)
+
+def main():
+    src, dst, sections, publics = sys.argv[1:5]
+    data = open(src, 'rb').read()
+    bs, streams = read_msf(data)
+    dbi = bytearray(streams[3])
+
+    hdrs = b''
+    for spec in filter(None, sections.split(',')):
+        name, rva, size = spec.split(':')
+        hdrs += struct.pack('<8s6I2HI', name.encode(), int(size, 0), int(rva, 0), int(size, 0),
+                            0, 0, 0, 0, 0, 0x60000020)
+    sym = b''
+    for spec in filter(None, publics.split(',')):
+        seg, off, name = spec.split(':', 2)
+        body = struct.pack('<HIIH', 0x110e, 2, int(off, 0), int(seg, 0)) + name.encode() + b'\0'
+        body += b'\0' * (-(len(body) + 2) % 4)
+        sym += struct.pack('<H', len(body)) + body
+
+    streams.append(hdrs)
+    hdr_index = len(streams) - 1
+    streams.append(sym)
+    sym_index = len(streams) - 1
+
+    struct.pack_into('<H', dbi, 20, sym_index)
+    sizes = struct.unpack_from("<5i", dbi, 24)
+    opt_size_off = 48
+    opt_size = struct.unpack_from('<i', dbi, opt_size_off)[0]
+    ec_size = struct.unpack_from('<i', dbi, opt_size_off + 4)[0]
+    opt_start = 64 + sum(sizes) + ec_size
+    opt = bytearray(dbi[opt_start:opt_start + opt_size])
+    if len(opt) < 22:
+        opt = bytearray(b'\xff\xff' * 11)
+    struct.pack_into('<H', opt, 10, hdr_index)
+    dbi = dbi[:opt_start] + opt + dbi[opt_start + opt_size:]
+    struct.pack_into('<i', dbi, opt_size_off, len(opt))
+    streams[3] = bytes(dbi)
+    open(dst, 'wb').write(write_msf(bs, streams))
+
+main()
@@ -0,0 +1,21 @@
+use std::path::Path;
+use std::process::Command;
+
+fn resolve(fixture: &str, addresses: &[&str]) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(fixture);
+    let output = Command::new(env!("CARGO_BIN_EXE_pdb-addr2line"))
+        .arg(path)
+        .args(addresses)
+        .output()
+        .expect("run pdb-addr2line");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).expect("utf-8 output")
+}
+
+#[test]
+fn lines_from_every_subsection_of_a_procedure() {
+    assert_eq!(
+        resolve("split_lines.pdb", &["0x1004", "0x1014", "0x101c"]),
+        "0x1004 main (C:\\src\\a.cpp:10)\n0x1014 main (C:\\src\\a.cpp:20)\n0x101c main (C:\\src\\a.cpp:21)\n"
+    );
+}
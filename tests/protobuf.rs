@@ -0,0 +1,176 @@
+//! Decodes the `--format protobuf` output of the built binary against schema/results.proto, so
+//! the encoder and the schema cannot drift apart: every field written must be declared with the
+//! number and wire type it is written with.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// The fields of each message of the schema, by number: their name and type. Map fields have the
+/// type `map<K,V>`.
+type Schema = HashMap<String, HashMap<u64, (String, String)>>;
+
+/// Reads the message declarations of schema/results.proto, one field per line as it is written.
+fn schema() -> Schema {
+    let text = fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("schema/results.proto")).expect("read schema");
+    let mut schema = Schema::new();
+    let mut message = None;
+    for line in text.lines().map(|line| line.split("//").next().unwrap_or("").trim()) {
+        if let Some(name) = line.strip_prefix("message ") {
+            let name = name.trim_end_matches('{').trim().to_string();
+            schema.insert(name.clone(), HashMap::new());
+            message = Some(name);
+        } else if line == "}" {
+            message = None;
+        } else if let (Some(message), Some((declaration, number))) =
+            (&message, line.trim_end_matches(';').split_once('='))
+        {
+            let declaration = declaration.replace(", ", ",");
+            let words: Vec<&str> =
+                declaration.split_whitespace().filter(|&word| word != "repeated" && word != "optional").collect();
+            let number = number.trim().parse().expect("field number");
+            schema.get_mut(message).unwrap().insert(number, (words[1].to_string(), words[0].to_string()));
+        }
+    }
+    schema
+}
+
+/// A decoded field value.
+#[derive(Debug, PartialEq)]
+enum Value {
+    Number(u64),
+    Double(f64),
+    String(String),
+    Message(Vec<(String, Value)>),
+}
+
+impl Value {
+    fn fields(&self) -> &[(String, Value)] {
+        match self {
+            Value::Message(fields) => fields,
+            other => panic!("{:?} is not a message", other),
+        }
+    }
+
+    /// The values of the field `name`, in the order they were written.
+    fn all(&self, name: &str) -> Vec<&Value> {
+        self.fields().iter().filter(|(field, _)| field == name).map(|(_, value)| value).collect()
+    }
+
+    fn get(&self, name: &str) -> &Value {
+        self.all(name).pop().unwrap_or_else(|| panic!("no field {}", name))
+    }
+
+    fn string(&self, name: &str) -> &str {
+        match self.get(name) {
+            Value::String(s) => s,
+            other => panic!("{} is {:?}", name, other),
+        }
+    }
+
+    fn number(&self, name: &str) -> u64 {
+        match self.get(name) {
+            Value::Number(n) => *n,
+            other => panic!("{} is {:?}", name, other),
+        }
+    }
+}
+
+fn varint(data: &[u8], offset: &mut usize) -> u64 {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = data[*offset];
+        *offset += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte < 0x80 {
+            return value;
+        }
+    }
+    panic!("varint longer than 10 bytes");
+}
+
+/// Decodes `data` as the message `name`, failing on fields the schema does not declare or
+/// declares with another wire type.
+fn decode(schema: &Schema, name: &str, data: &[u8]) -> Value {
+    let message = schema.get(name).unwrap_or_else(|| panic!("no message {} in the schema", name));
+    let mut fields = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        let key = varint(data, &mut offset);
+        let (field, kind) = message.get(&(key >> 3)).unwrap_or_else(|| panic!("{} has no field {}", name, key >> 3));
+        let value = match (key & 7, kind.as_str()) {
+            (0, "uint32" | "uint64" | "bool") => Value::Number(varint(data, &mut offset)),
+            (1, "double") => {
+                offset += 8;
+                Value::Double(f64::from_le_bytes(data[offset - 8..offset].try_into().unwrap()))
+            }
+            (2, _) => {
+                let len = varint(data, &mut offset) as usize;
+                let bytes = &data[offset..offset + len];
+                offset += len;
+                match kind.as_str() {
+                    "string" => Value::String(String::from_utf8(bytes.to_vec()).expect("utf-8 string")),
+                    "map<string,bool>" => {
+                        let key = (1, ("key".to_string(), "string".to_string()));
+                        let value = (2, ("value".to_string(), "bool".to_string()));
+                        let mut schema = Schema::new();
+                        schema.insert("entry".to_string(), vec![key, value].into_iter().collect());
+                        decode(&schema, "entry", bytes)
+                    }
+                    _ => decode(schema, kind, bytes),
+                }
+            }
+            (wire_type, _) => panic!("{}.{} is a {} but has wire type {}", name, field, kind, wire_type),
+        };
+        fields.push((field.clone(), value));
+    }
+    Value::Message(fields)
+}
+
+fn run(args: &[&str]) -> Vec<u8> {
+    let output = Command::new(env!("CARGO_BIN_EXE_pdb-addr2line"))
+        .args(args)
+        .current_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures"))
+        .output()
+        .expect("run pdb-addr2line");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    output.stdout
+}
+
+#[test]
+fn results() {
+    let data = run(&["--format", "protobuf", "--confidence", "split_lines.pdb", "0x1004", "0x101c", "0x1040"]);
+    let results = decode(&schema(), "Results", &data);
+    let json = String::from_utf8(run(&["--json", "split_lines.pdb", "0x1004"])).unwrap();
+    assert!(json.starts_with(&format!("{{\"schema_version\":\"{}\"", results.string("schema_version"))));
+
+    let results = results.all("results");
+    let addresses: Vec<u64> = results.iter().map(|result| result.number("address")).collect();
+    assert_eq!(addresses, [0x1004, 0x101c, 0x1040]);
+    let frame = results[1].get("frames");
+    assert_eq!(frame.string("function"), "main");
+    assert_eq!(frame.string("file"), r"C:\src\a.cpp");
+    assert_eq!(frame.number("line"), 21);
+    assert_eq!(frame.string("source"), "line-info");
+    assert_eq!(frame.get("confidence"), &Value::Double(1.0));
+    assert_eq!(results[2].get("frames").string("source"), "padding");
+}
+
+#[test]
+fn address_map() {
+    let map = decode(&schema(), "AddressMap", &run(&["--dump-address-map", "split_lines.pdb", "--format", "protobuf"]));
+    let section = map.get("sections");
+    assert_eq!((section.string("name"), section.number("rva"), section.number("size")), (".text", 0x1000, 0x1000));
+    assert!(map.all("original_sections").is_empty() && map.all("omap_to_source").is_empty());
+}
+
+#[test]
+fn capabilities() {
+    let capabilities = decode(&schema(), "Capabilities", &run(&["capabilities", "--format", "protobuf"]));
+    assert_eq!(capabilities.string("version"), env!("CARGO_PKG_VERSION"));
+    let inline_frames = capabilities.all("capabilities").into_iter().find(|entry| entry.string("key") == "inline-frames");
+    assert_eq!(inline_frames.map(|entry| entry.number("value")), Some(1));
+    assert!(capabilities.all("rpc_methods").contains(&&Value::String("resolve".to_string())));
+}